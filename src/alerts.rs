@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Metric {
+    Cpu,
+    Ram,
+    Battery,
+    Disk,
+    Temperature,
+    NetDown,
+    NetUp,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 7] = [
+        Metric::Cpu,
+        Metric::Ram,
+        Metric::Battery,
+        Metric::Disk,
+        Metric::Temperature,
+        Metric::NetDown,
+        Metric::NetUp,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Metric::Cpu => "CPU %",
+            Metric::Ram => "RAM %",
+            Metric::Battery => "Battery %",
+            Metric::Disk => "Fullest disk %",
+            Metric::Temperature => "Temperature °C",
+            Metric::NetDown => "Download %",
+            Metric::NetUp => "Upload %",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    Above,
+    Below,
+}
+
+impl Comparison {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Comparison::Above => ">",
+            Comparison::Below => "<",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum AlertTarget {
+    Left,
+    Right,
+    Both,
+}
+
+impl AlertTarget {
+    pub const ALL: [AlertTarget; 3] = [AlertTarget::Left, AlertTarget::Right, AlertTarget::Both];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlertTarget::Left => "Left",
+            AlertTarget::Right => "Right",
+            AlertTarget::Both => "Both",
+        }
+    }
+
+    pub fn includes_left(&self) -> bool {
+        matches!(self, AlertTarget::Left | AlertTarget::Both)
+    }
+
+    pub fn includes_right(&self) -> bool {
+        matches!(self, AlertTarget::Right | AlertTarget::Both)
+    }
+}
+
+/// What a side shows while a rule is firing
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum AlertAction {
+    Panic,
+    Flash,
+    CustomPreset(String),
+}
+
+impl AlertAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlertAction::Panic => "Panic pattern",
+            AlertAction::Flash => "Flashing alert",
+            AlertAction::CustomPreset(_) => "Custom preset",
+        }
+    }
+}
+
+/// A condition on a metric that, once held for `duration_secs`, overrides
+/// the preset on the target side(s) until the condition clears
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AlertRule {
+    pub name: String,
+    pub enabled: bool,
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub threshold: f32,
+    pub duration_secs: u64,
+    pub target: AlertTarget,
+    pub action: AlertAction,
+}
+
+impl Default for AlertRule {
+    fn default() -> Self {
+        Self {
+            name: "High CPU".to_string(),
+            enabled: true,
+            metric: Metric::Cpu,
+            comparison: Comparison::Above,
+            threshold: 90.0,
+            duration_secs: 10,
+            target: AlertTarget::Both,
+            action: AlertAction::Flash,
+        }
+    }
+}
+
+/// Current metric values the rules are evaluated against
+/// Metrics that aren't available (e.g. no battery) are `None` and never fire
+#[derive(Default, Clone, Copy)]
+pub struct MetricSnapshot {
+    pub cpu: Option<f32>,
+    pub ram: Option<f32>,
+    pub battery: Option<f32>,
+    pub disk: Option<f32>,
+    pub temperature: Option<f32>,
+    pub net_down: Option<f32>,
+    pub net_up: Option<f32>,
+}
+
+impl MetricSnapshot {
+    pub fn value(&self, metric: Metric) -> Option<f32> {
+        match metric {
+            Metric::Cpu => self.cpu,
+            Metric::Ram => self.ram,
+            Metric::Battery => self.battery,
+            Metric::Disk => self.disk,
+            Metric::Temperature => self.temperature,
+            Metric::NetDown => self.net_down,
+            Metric::NetUp => self.net_up,
+        }
+    }
+}
+
+/// Tracks how long each rule's condition has been held
+#[derive(Default)]
+pub struct AlertEngine {
+    condition_since: Vec<Option<Instant>>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluate every rule against the snapshot and return the indices of
+    /// the rules that are currently firing, in rule order
+    pub fn evaluate(&mut self, rules: &[AlertRule], snapshot: &MetricSnapshot, now: Instant) -> Vec<usize> {
+        self.condition_since.resize(rules.len(), None);
+
+        let mut firing = Vec::new();
+        for (i, rule) in rules.iter().enumerate() {
+            let held = rule.enabled
+                && match snapshot.value(rule.metric) {
+                    Some(value) => match rule.comparison {
+                        Comparison::Above => value > rule.threshold,
+                        Comparison::Below => value < rule.threshold,
+                    },
+                    None => false,
+                };
+
+            if !held {
+                self.condition_since[i] = None;
+                continue;
+            }
+
+            let since = *self.condition_since[i].get_or_insert(now);
+            if now.duration_since(since) >= Duration::from_secs(rule.duration_secs) {
+                firing.push(i);
+            }
+        }
+        firing
+    }
+
+    /// Forget all timing state, e.g. after the rule list was edited
+    pub fn reset(&mut self) {
+        self.condition_since.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_rule(duration_secs: u64) -> AlertRule {
+        AlertRule {
+            threshold: 90.0,
+            duration_secs,
+            ..AlertRule::default()
+        }
+    }
+
+    fn cpu(value: f32) -> MetricSnapshot {
+        MetricSnapshot {
+            cpu: Some(value),
+            ..MetricSnapshot::default()
+        }
+    }
+
+    #[test]
+    fn fires_once_condition_is_held_for_the_duration() {
+        let rules = [cpu_rule(10)];
+        let mut engine = AlertEngine::new();
+        let start = Instant::now();
+
+        assert!(engine.evaluate(&rules, &cpu(95.0), start).is_empty());
+        assert!(engine.evaluate(&rules, &cpu(95.0), start + Duration::from_secs(9)).is_empty());
+        assert_eq!(engine.evaluate(&rules, &cpu(95.0), start + Duration::from_secs(10)), vec![0]);
+    }
+
+    #[test]
+    fn zero_duration_fires_immediately() {
+        let rules = [cpu_rule(0)];
+        let mut engine = AlertEngine::new();
+        assert_eq!(engine.evaluate(&rules, &cpu(91.0), Instant::now()), vec![0]);
+    }
+
+    #[test]
+    fn clearing_the_condition_restarts_the_hold() {
+        let rules = [cpu_rule(10)];
+        let mut engine = AlertEngine::new();
+        let start = Instant::now();
+
+        engine.evaluate(&rules, &cpu(95.0), start);
+        assert!(engine.evaluate(&rules, &cpu(50.0), start + Duration::from_secs(8)).is_empty());
+        assert!(engine.evaluate(&rules, &cpu(95.0), start + Duration::from_secs(12)).is_empty());
+        assert_eq!(engine.evaluate(&rules, &cpu(95.0), start + Duration::from_secs(22)), vec![0]);
+    }
+
+    #[test]
+    fn stops_firing_when_the_condition_clears() {
+        let rules = [cpu_rule(0)];
+        let mut engine = AlertEngine::new();
+        let start = Instant::now();
+
+        assert_eq!(engine.evaluate(&rules, &cpu(95.0), start), vec![0]);
+        assert!(engine.evaluate(&rules, &cpu(90.0), start + Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn missing_metric_never_fires() {
+        let rules = [AlertRule {
+            metric: Metric::Battery,
+            comparison: Comparison::Below,
+            threshold: 20.0,
+            duration_secs: 0,
+            ..AlertRule::default()
+        }];
+        let mut engine = AlertEngine::new();
+        let start = Instant::now();
+
+        assert!(engine.evaluate(&rules, &MetricSnapshot::default(), start).is_empty());
+        let low = MetricSnapshot {
+            battery: Some(10.0),
+            ..MetricSnapshot::default()
+        };
+        assert_eq!(engine.evaluate(&rules, &low, start), vec![0]);
+    }
+
+    #[test]
+    fn disabled_rules_never_fire() {
+        let rules = [AlertRule {
+            enabled: false,
+            ..cpu_rule(0)
+        }];
+        let mut engine = AlertEngine::new();
+        assert!(engine.evaluate(&rules, &cpu(100.0), Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn reports_every_firing_rule_in_order() {
+        let rules = [
+            cpu_rule(0),
+            AlertRule {
+                metric: Metric::Ram,
+                threshold: 50.0,
+                duration_secs: 0,
+                ..AlertRule::default()
+            },
+        ];
+        let snapshot = MetricSnapshot {
+            cpu: Some(99.0),
+            ram: Some(60.0),
+            ..MetricSnapshot::default()
+        };
+        let mut engine = AlertEngine::new();
+        assert_eq!(engine.evaluate(&rules, &snapshot, Instant::now()), vec![0, 1]);
+    }
+}
@@ -0,0 +1,708 @@
+// src/animations.rs
+//! Host-drawn animations. Each is a `Preset` that keeps its own state and
+//! moves by the time since its last tick, so it runs at the same speed
+//! whatever frame rate it's drawn at.
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::params::ParamSpec;
+use crate::presets::{MATRIX_HEIGHT, MATRIX_WIDTH};
+use crate::registry::{Preset, PresetCategory, PresetContext, PresetOutput, ANIMATED_FPS};
+use crate::utils::{render_breathing_animation, BreathingCurve};
+
+const WIDTH: f32 = MATRIX_WIDTH as f32;
+const HEIGHT: f32 = MATRIX_HEIGHT as f32;
+
+fn blank() -> Vec<u8> {
+    vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT]
+}
+
+/// Brighten a pixel to `level` of `brightness`; off-screen points are ignored
+fn plot(frame: &mut [u8], x: i32, y: i32, level: f32, brightness: u8) {
+    if x < 0 || y < 0 || x >= MATRIX_WIDTH as i32 || y >= MATRIX_HEIGHT as i32 {
+        return;
+    }
+    let pixel = &mut frame[x as usize + y as usize * MATRIX_WIDTH];
+    *pixel = (*pixel).max((level.clamp(0.0, 1.0) * brightness as f32).round() as u8);
+}
+
+/// xorshift64*; plenty for scattering raindrops and stars, and behind the
+/// scripts' `random()`
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Self(nanos | 1)
+    }
+
+    /// The same sequence every time; the seed must not be 0
+    pub(crate) fn seeded(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in 0.0..1.0
+    fn next(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in 0.0..1.0 with full double precision
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next() * (max - min)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next() * n as f32) as usize).min(n.saturating_sub(1))
+    }
+}
+
+/// Seconds since the previous tick, capped so an animation that was off
+/// screen for a while doesn't jump when it comes back
+#[derive(Default)]
+struct FrameClock {
+    last: Option<Instant>,
+}
+
+impl FrameClock {
+    fn delta(&mut self) -> f32 {
+        let now = Instant::now();
+        let delta = self.last.map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+        self.last = Some(now);
+        delta.min(0.25)
+    }
+}
+
+/// Counts up at `rate` per second and hands out whole spawns
+#[derive(Default)]
+struct Spawner {
+    pending: f32,
+}
+
+impl Spawner {
+    fn due(&mut self, rate: f32, delta: f32) -> usize {
+        self.pending += rate * delta;
+        let due = self.pending.floor();
+        self.pending -= due;
+        due as usize
+    }
+}
+
+/// The whole matrix fading in and out
+#[derive(Default)]
+pub struct BreathingPreset {
+    clock: FrameClock,
+    phase: f32,
+}
+
+impl Preset for BreathingPreset {
+    fn id(&self) -> &str {
+        "breathing"
+    }
+
+    fn name(&self) -> &str {
+        "Breathing"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::choice("curve", "Curve", BreathingCurve::ALL.iter().map(|c| c.label()).collect(), BreathingCurve::Sine.label()),
+            ParamSpec::range("period", "Seconds per breath", 0.5, 10.0, 4.0),
+            ParamSpec::level("floor", "Dimmest", 0),
+            ParamSpec::level("peak", "Brightest", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let period = ctx.params.number("period").max(0.1) as f32;
+        self.phase = (self.phase + self.clock.delta() / period).fract();
+        let curve = BreathingCurve::ALL
+            .into_iter()
+            .find(|curve| curve.label() == ctx.params.text("curve"))
+            .unwrap_or(BreathingCurve::Sine);
+        PresetOutput::Frame(render_breathing_animation(self.phase, curve, ctx.params.level("floor"), ctx.params.level("peak")))
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+struct Raindrop {
+    x: i32,
+    y: f32,
+    /// Multiplier on the preset's speed so drops don't fall in lockstep
+    speed: f32,
+}
+
+/// Drops falling down the matrix with fading trails
+pub struct RainPreset {
+    clock: FrameClock,
+    spawner: Spawner,
+    rng: Rng,
+    drops: Vec<Raindrop>,
+}
+
+impl Default for RainPreset {
+    fn default() -> Self {
+        Self {
+            clock: FrameClock::default(),
+            spawner: Spawner::default(),
+            rng: Rng::new(),
+            drops: Vec::new(),
+        }
+    }
+}
+
+impl Preset for RainPreset {
+    fn id(&self) -> &str {
+        "rain"
+    }
+
+    fn name(&self) -> &str {
+        "Rain"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::range("drops", "Drops per second", 1.0, 40.0, 8.0),
+            ParamSpec::range("speed", "Rows per second", 5.0, 60.0, 20.0),
+            ParamSpec::range("trail", "Trail length", 1.0, 15.0, 6.0),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let delta = self.clock.delta();
+        let speed = ctx.params.number("speed") as f32;
+        let trail = ctx.params.number("trail").max(1.0) as f32;
+
+        for _ in 0..self.spawner.due(ctx.params.number("drops") as f32, delta) {
+            let drop = Raindrop {
+                x: self.rng.below(MATRIX_WIDTH) as i32,
+                y: 0.0,
+                speed: self.rng.range(0.7, 1.3),
+            };
+            self.drops.push(drop);
+        }
+        for drop in &mut self.drops {
+            drop.y += speed * drop.speed * delta;
+        }
+        self.drops.retain(|drop| drop.y - trail < HEIGHT);
+
+        let mut frame = blank();
+        let brightness = ctx.params.level("brightness");
+        for drop in &self.drops {
+            for behind in 0..trail.ceil() as i32 {
+                let level = 1.0 - behind as f32 / trail;
+                plot(&mut frame, drop.x, drop.y as i32 - behind, level * level, brightness);
+            }
+        }
+        PresetOutput::Frame(frame)
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+/// Heat rising from the bottom edge and cooling as it goes
+pub struct FirePreset {
+    clock: FrameClock,
+    rng: Rng,
+    heat: Vec<f32>,
+    /// Time not yet simulated, in seconds
+    pending: f32,
+}
+
+impl Default for FirePreset {
+    fn default() -> Self {
+        Self {
+            clock: FrameClock::default(),
+            rng: Rng::new(),
+            heat: vec![0.0; MATRIX_WIDTH * MATRIX_HEIGHT],
+            pending: 0.0,
+        }
+    }
+}
+
+impl FirePreset {
+    /// Simulation steps per second, independent of the frame rate
+    const STEPS_PER_SECOND: f32 = 30.0;
+
+    fn step(&mut self, intensity: f32, cooling: f32) {
+        let bottom = (MATRIX_HEIGHT - 1) * MATRIX_WIDTH;
+        for x in 0..MATRIX_WIDTH {
+            self.heat[bottom + x] = intensity * self.rng.range(0.6, 1.0);
+        }
+        // Each cell takes the heat of a cell below it, drifting sideways a little
+        for y in 0..MATRIX_HEIGHT - 1 {
+            for x in 0..MATRIX_WIDTH {
+                let source_x = (x as i32 + self.rng.below(3) as i32 - 1).clamp(0, MATRIX_WIDTH as i32 - 1) as usize;
+                let below = self.heat[source_x + (y + 1) * MATRIX_WIDTH];
+                self.heat[x + y * MATRIX_WIDTH] = (below - self.rng.next() * cooling).max(0.0);
+            }
+        }
+    }
+}
+
+impl Preset for FirePreset {
+    fn id(&self) -> &str {
+        "fire"
+    }
+
+    fn name(&self) -> &str {
+        "Fire"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::range("intensity", "Intensity", 0.2, 1.0, 0.9),
+            ParamSpec::range("cooling", "Cooling", 0.01, 0.2, 0.06),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        self.pending += self.clock.delta();
+        let step = 1.0 / Self::STEPS_PER_SECOND;
+        while self.pending >= step {
+            self.pending -= step;
+            self.step(ctx.params.number("intensity") as f32, ctx.params.number("cooling") as f32);
+        }
+
+        let brightness = ctx.params.level("brightness") as f32;
+        // Squared so the flame tips fade out rather than ending in a flat edge
+        let frame = self.heat.iter().map(|heat| (heat * heat * brightness).round() as u8).collect();
+        PresetOutput::Frame(frame)
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+/// Overlapping sine waves drifting across the matrix
+pub struct PlasmaPreset {
+    started: Instant,
+}
+
+impl Default for PlasmaPreset {
+    fn default() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Preset for PlasmaPreset {
+    fn id(&self) -> &str {
+        "plasma"
+    }
+
+    fn name(&self) -> &str {
+        "Plasma"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::range("speed", "Speed", 0.1, 5.0, 1.0),
+            ParamSpec::range("scale", "Scale", 0.1, 1.0, 0.35),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let t = self.started.elapsed().as_secs_f32() * ctx.params.number("speed") as f32;
+        let scale = ctx.params.number("scale") as f32;
+        let brightness = ctx.params.level("brightness");
+
+        let mut frame = blank();
+        for y in 0..MATRIX_HEIGHT {
+            for x in 0..MATRIX_WIDTH {
+                let (fx, fy) = (x as f32 * scale, y as f32 * scale);
+                let cx = fx - WIDTH * scale / 2.0 + (t * 0.7).sin() * 2.0;
+                let cy = fy - HEIGHT * scale / 2.0 + (t * 0.5).cos() * 4.0;
+                let value = (fx + t).sin()
+                    + (fy * 0.5 + t * 1.3).sin()
+                    + ((fx + fy) * 0.5 + t * 0.7).sin()
+                    + ((cx * cx + cy * cy).sqrt() - t).sin();
+                // The sum is in -4..4; map it to 0..1
+                plot(&mut frame, x as i32, y as i32, (value + 4.0) / 8.0, brightness);
+            }
+        }
+        PresetOutput::Frame(frame)
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+struct Star {
+    x: f32,
+    y: f32,
+    /// Distance from the viewer; stars are drawn brighter as it shrinks
+    z: f32,
+}
+
+/// Stars streaming out from the centre, as if flying through them
+pub struct StarfieldPreset {
+    clock: FrameClock,
+    rng: Rng,
+    stars: Vec<Star>,
+}
+
+impl Default for StarfieldPreset {
+    fn default() -> Self {
+        Self {
+            clock: FrameClock::default(),
+            rng: Rng::new(),
+            stars: Vec::new(),
+        }
+    }
+}
+
+impl StarfieldPreset {
+    fn new_star(&mut self, z: f32) -> Star {
+        Star {
+            x: self.rng.range(-1.0, 1.0),
+            y: self.rng.range(-1.0, 1.0),
+            z,
+        }
+    }
+}
+
+impl Preset for StarfieldPreset {
+    fn id(&self) -> &str {
+        "starfield"
+    }
+
+    fn name(&self) -> &str {
+        "Starfield"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::number("count", "Stars", 5.0, 100.0, 40.0),
+            ParamSpec::range("speed", "Speed", 0.1, 3.0, 0.6),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let delta = self.clock.delta();
+        let count = ctx.params.number("count") as usize;
+        self.stars.truncate(count);
+        while self.stars.len() < count {
+            // Spread the first stars out so the field doesn't start empty
+            let z = self.rng.range(0.1, 1.0);
+            let star = self.new_star(z);
+            self.stars.push(star);
+        }
+
+        let speed = ctx.params.number("speed") as f32;
+        let brightness = ctx.params.level("brightness");
+        let (half_w, half_h) = (WIDTH / 2.0, HEIGHT / 2.0);
+        let mut frame = blank();
+        for i in 0..self.stars.len() {
+            self.stars[i].z -= speed * delta;
+            let star = &self.stars[i];
+            let (sx, sy) = (half_w + star.x / star.z * half_w, half_h + star.y / star.z * half_h);
+            if star.z <= 0.01 || sx < 0.0 || sy < 0.0 || sx >= WIDTH || sy >= HEIGHT {
+                self.stars[i] = self.new_star(1.0);
+                continue;
+            }
+            plot(&mut frame, sx as i32, sy as i32, 1.0 - star.z, brightness);
+        }
+        PresetOutput::Frame(frame)
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+/// A ball bouncing off the edges, optionally under gravity
+pub struct BallPreset {
+    clock: FrameClock,
+    position: (f32, f32),
+    velocity: (f32, f32),
+    /// Previous frame, faded to leave a trail
+    trail: Vec<f32>,
+}
+
+impl Default for BallPreset {
+    fn default() -> Self {
+        Self {
+            clock: FrameClock::default(),
+            position: (WIDTH / 2.0, HEIGHT / 4.0),
+            velocity: (0.6, 1.0),
+            trail: vec![0.0; MATRIX_WIDTH * MATRIX_HEIGHT],
+        }
+    }
+}
+
+impl BallPreset {
+    /// Downward acceleration when gravity is on, in rows per second squared
+    const GRAVITY: f32 = 60.0;
+}
+
+impl Preset for BallPreset {
+    fn id(&self) -> &str {
+        "ball"
+    }
+
+    fn name(&self) -> &str {
+        "Bouncing Ball"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::range("speed", "Rows per second", 5.0, 60.0, 20.0),
+            ParamSpec::number("size", "Size", 1.0, 3.0, 1.0),
+            ParamSpec::toggle("gravity", "Gravity", false),
+            ParamSpec::toggle("trail", "Trail", true),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let delta = self.clock.delta();
+        let speed = ctx.params.number("speed") as f32;
+        let size = ctx.params.number("size").round() as f32;
+        let radius = size / 2.0;
+
+        if ctx.params.flag("gravity") {
+            self.velocity.1 += Self::GRAVITY / speed * delta;
+        }
+        // Velocity is in units of `speed`, so the slider rescales the motion
+        // without changing the ball's path
+        let (mut x, mut y) = (self.position.0 + self.velocity.0 * speed * delta, self.position.1 + self.velocity.1 * speed * delta);
+        if x < radius || x > WIDTH - radius {
+            self.velocity.0 = -self.velocity.0;
+            x = x.clamp(radius, WIDTH - radius);
+        }
+        if y < radius || y > HEIGHT - radius {
+            self.velocity.1 = -self.velocity.1;
+            y = y.clamp(radius, HEIGHT - radius);
+        }
+        self.position = (x, y);
+
+        let fade = if ctx.params.flag("trail") { 0.02f32.powf(delta) } else { 0.0 };
+        for level in &mut self.trail {
+            *level *= fade;
+        }
+        let (left, top) = ((x - radius).round() as i32, (y - radius).round() as i32);
+        for dy in 0..size as i32 {
+            for dx in 0..size as i32 {
+                let (px, py) = (left + dx, top + dy);
+                if px >= 0 && py >= 0 && px < MATRIX_WIDTH as i32 && py < MATRIX_HEIGHT as i32 {
+                    self.trail[px as usize + py as usize * MATRIX_WIDTH] = 1.0;
+                }
+            }
+        }
+
+        let brightness = ctx.params.level("brightness") as f32;
+        PresetOutput::Frame(self.trail.iter().map(|level| (level * brightness).round() as u8).collect())
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+/// Sweep directions for the scanner
+const SCANNER_DIRECTIONS: [&str; 2] = ["Up and down", "Side to side"];
+
+/// A light sweeping back and forth with a fading tail, like KITT's
+pub struct ScannerPreset {
+    started: Instant,
+}
+
+impl Default for ScannerPreset {
+    fn default() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Preset for ScannerPreset {
+    fn id(&self) -> &str {
+        "scanner"
+    }
+
+    fn name(&self) -> &str {
+        "Knight Rider Scanner"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::choice("direction", "Direction", SCANNER_DIRECTIONS.to_vec(), SCANNER_DIRECTIONS[0]),
+            ParamSpec::range("speed", "Pixels per second", 5.0, 100.0, 30.0),
+            ParamSpec::range("tail", "Tail length", 1.0, 20.0, 8.0),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let vertical = ctx.params.text("direction") == SCANNER_DIRECTIONS[0];
+        let length = if vertical { MATRIX_HEIGHT } else { MATRIX_WIDTH };
+        let tail = ctx.params.number("tail").max(1.0) as f32;
+        let brightness = ctx.params.level("brightness");
+
+        // Distance travelled along a there-and-back loop; the head passes
+        // each cell twice per loop, once each way
+        let period = 2.0 * (length - 1) as f32;
+        let travelled = (self.started.elapsed().as_secs_f32() * ctx.params.number("speed") as f32) % period;
+
+        let mut frame = blank();
+        for cell in 0..length {
+            let passes = [cell as f32, period - cell as f32];
+            let behind = passes.iter().map(|pass| (travelled - pass).rem_euclid(period)).fold(f32::MAX, f32::min);
+            let level = 1.0 - behind / tail;
+            if level <= 0.0 {
+                continue;
+            }
+            if vertical {
+                for x in 0..MATRIX_WIDTH {
+                    plot(&mut frame, x as i32, cell as i32, level * level, brightness);
+                }
+            } else {
+                for y in 0..MATRIX_HEIGHT {
+                    plot(&mut frame, cell as i32, y as i32, level * level, brightness);
+                }
+            }
+        }
+        PresetOutput::Frame(frame)
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+struct Ripple {
+    x: f32,
+    y: f32,
+    /// Seconds since the drop landed
+    age: f32,
+}
+
+/// Rings spreading out from random drops, fading as they grow
+pub struct RipplePreset {
+    clock: FrameClock,
+    spawner: Spawner,
+    rng: Rng,
+    ripples: Vec<Ripple>,
+}
+
+impl Default for RipplePreset {
+    fn default() -> Self {
+        Self {
+            clock: FrameClock::default(),
+            spawner: Spawner::default(),
+            rng: Rng::new(),
+            ripples: Vec::new(),
+        }
+    }
+}
+
+impl RipplePreset {
+    /// Seconds a ring lasts before it has faded out
+    const LIFETIME: f32 = 2.5;
+}
+
+impl Preset for RipplePreset {
+    fn id(&self) -> &str {
+        "ripple"
+    }
+
+    fn name(&self) -> &str {
+        "Ripple"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Animations
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::range("drops", "Drops per second", 0.2, 5.0, 1.0),
+            ParamSpec::range("speed", "Pixels per second", 3.0, 40.0, 10.0),
+            ParamSpec::level("brightness", "Brightness", 255),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let delta = self.clock.delta();
+        for _ in 0..self.spawner.due(ctx.params.number("drops") as f32, delta) {
+            let ripple = Ripple {
+                x: self.rng.range(0.0, WIDTH),
+                y: self.rng.range(0.0, HEIGHT),
+                age: 0.0,
+            };
+            self.ripples.push(ripple);
+        }
+        for ripple in &mut self.ripples {
+            ripple.age += delta;
+        }
+        self.ripples.retain(|ripple| ripple.age < Self::LIFETIME);
+
+        let speed = ctx.params.number("speed") as f32;
+        let brightness = ctx.params.level("brightness");
+        let mut frame = blank();
+        for ripple in &self.ripples {
+            let radius = ripple.age * speed;
+            let fade = 1.0 - ripple.age / Self::LIFETIME;
+            for y in 0..MATRIX_HEIGHT {
+                for x in 0..MATRIX_WIDTH {
+                    let (dx, dy) = (x as f32 + 0.5 - ripple.x, y as f32 + 0.5 - ripple.y);
+                    // Rings are about a pixel and a half wide
+                    let off_ring = ((dx * dx + dy * dy).sqrt() - radius).abs();
+                    plot(&mut frame, x as i32, y as i32, (1.0 - off_ring / 0.75) * fade, brightness);
+                }
+            }
+        }
+        PresetOutput::Frame(frame)
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
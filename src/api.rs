@@ -0,0 +1,695 @@
+// src/api.rs
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::presets::{MATRIX_HEIGHT, MATRIX_WIDTH};
+use crate::stream::{self, StreamHub};
+
+/// Largest request body accepted, plenty for a PNG of one frame
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// How long a request waits for the app to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the listener checks whether it should stop
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Requests handled at once, each on its own thread; more are turned away
+const MAX_CONNECTIONS: usize = 16;
+
+/// Test page for frame streaming, served from the API so its WebSocket
+/// comes from an allowed origin
+const STREAM_CLIENT_PAGE: &str = include_str!("../web/stream_client.html");
+
+/// Names the API may be addressed by in `Host` and `Origin`
+const LOCAL_HOSTS: [&str; 2] = ["127.0.0.1", "localhost"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Accept WebSocket frame streams on `/api/stream`; they ride on the
+    /// API server, so this only matters while it's enabled
+    pub stream: bool,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7879,
+            stream: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Side::Left),
+            "right" => Some(Side::Right),
+            _ => None,
+        }
+    }
+}
+
+/// What an HTTP request asks the app to do
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiRequest {
+    ListDevices,
+    SetBrightness(Side, u8),
+    SetPreset(Side, String),
+    PushFrame(Side, Vec<u8>),
+    ListPresets,
+    GetPreset(String),
+    SavePreset(String, Vec<u8>),
+    DeletePreset(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl ApiResponse {
+    pub fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "error": message.into() }),
+        }
+    }
+}
+
+/// A request handed to the app, which must answer it with `respond`
+pub struct ApiCall {
+    pub request: ApiRequest,
+    reply: Sender<ApiResponse>,
+}
+
+impl ApiCall {
+    pub fn respond(self, response: ApiResponse) {
+        // The client may have given up waiting already
+        let _ = self.reply.send(response);
+    }
+}
+
+/// A running HTTP server; it stops listening when dropped
+pub struct ApiServer {
+    pub rx: Receiver<ApiCall>,
+    pub port: u16,
+    stop: Arc<AtomicBool>,
+    streaming: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    /// Start or stop accepting WebSocket frame streams without restarting
+    pub fn set_streaming(&self, enabled: bool) {
+        self.streaming.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wait for the listener to close, so the port can be bound again
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+/// Serve the REST API on localhost from a background thread
+/// Requests are passed to the app through `ApiServer::rx`; `notify` is
+/// called for each one so the GUI wakes up to answer it. While `streaming`
+/// is on, WebSocket clients on `/api/stream` send frames straight to `hub`
+/// instead
+pub fn spawn_server<F>(port: u16, hub: Arc<StreamHub>, streaming: bool, notify: F) -> std::io::Result<ApiServer>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    listener.set_nonblocking(true)?;
+    let (tx, rx) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let notify = Arc::new(notify);
+    let active = Arc::new(AtomicUsize::new(0));
+    let streaming = Arc::new(AtomicBool::new(streaming));
+
+    let stopped = stop.clone();
+    let stream_toggle = streaming.clone();
+    let listener = thread::spawn(move || {
+        while !stopped.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((mut stream, _)) => {
+                    if active.load(Ordering::Relaxed) >= MAX_CONNECTIONS {
+                        let _ = stream.set_nonblocking(false);
+                        let _ = write_response(&mut stream, &ApiResponse::error(503, "too many connections"));
+                        continue;
+                    }
+                    // A slow client only holds up its own thread
+                    active.fetch_add(1, Ordering::Relaxed);
+                    let (tx, notify, active) = (tx.clone(), notify.clone(), active.clone());
+                    let hub = stream_toggle.load(Ordering::Relaxed).then(|| hub.clone());
+                    thread::spawn(move || {
+                        handle_connection(stream, port, &tx, hub.as_ref(), &*notify);
+                        active.fetch_sub(1, Ordering::Relaxed);
+                    });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+                Err(_) => break,
+            }
+        }
+    });
+
+    Ok(ApiServer {
+        rx,
+        port,
+        stop,
+        streaming,
+        listener: Some(listener),
+    })
+}
+
+/// A parsed HTTP request
+struct HttpRequest {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+    content_type: Option<String>,
+    websocket_key: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Answer a single request; connections are not kept alive
+fn handle_connection<F: Fn()>(
+    stream: TcpStream,
+    port: u16,
+    tx: &Sender<ApiCall>,
+    hub: Option<&Arc<StreamHub>>,
+    notify: &F,
+) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(REPLY_TIMEOUT));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    let response = match read_request(&stream) {
+        Ok(http) if http.path.split('?').next() == Some("/api/stream") => {
+            match check_local(&http, port).and_then(|_| stream_side(&http, hub)) {
+                Ok((key, side, hub)) => {
+                    // The stream gets its own threads; this one is done
+                    let _ = stream::serve(stream, &key, side, hub);
+                    return;
+                }
+                Err(response) => response,
+            }
+        }
+        Ok(http) if http.method == "GET" && http.path.split('?').next() == Some("/stream") => {
+            match check_local(&http, port) {
+                Ok(()) => {
+                    let _ = write_page(&mut writer, STREAM_CLIENT_PAGE);
+                    return;
+                }
+                Err(response) => response,
+            }
+        }
+        Ok(http) => match check_local(&http, port).and_then(|_| route(&http)) {
+            Ok(request) => {
+                let (reply, response) = mpsc::channel();
+                if tx.send(ApiCall { request, reply }).is_err() {
+                    return;
+                }
+                notify();
+                response
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| ApiResponse::error(503, "the app did not answer in time"))
+            }
+            Err(response) => response,
+        },
+        Err(e) => ApiResponse::error(400, e),
+    };
+
+    let _ = write_response(&mut writer, &response);
+}
+
+fn read_request(stream: &TcpStream) -> Result<HttpRequest, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    // "PUT /api/devices/left/brightness HTTP/1.1"
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".to_string());
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut content_length = 0;
+    let mut host = None;
+    let mut origin = None;
+    let mut content_type = None;
+    let mut websocket_key = None;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.trim().parse().map_err(|_| "bad Content-Length")?,
+            "host" => host = Some(value.trim().to_ascii_lowercase()),
+            "origin" => origin = Some(value.trim().to_ascii_lowercase()),
+            "content-type" => content_type = Some(value.trim().to_ascii_lowercase()),
+            "sec-websocket-key" => websocket_key = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    if content_length > MAX_BODY_BYTES {
+        return Err(format!("body larger than {} bytes", MAX_BODY_BYTES));
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(HttpRequest {
+        method,
+        path,
+        host,
+        origin,
+        content_type,
+        websocket_key,
+        body,
+    })
+}
+
+fn write_response(writer: &mut impl Write, response: &ApiResponse) -> std::io::Result<()> {
+    let body = response.body.to_string();
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        403 => "Forbidden",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+fn write_page(writer: &mut impl Write, html: &str) -> std::io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        html.len(),
+        html
+    )?;
+    writer.flush()
+}
+
+/// Only serve requests addressed to this server by a local name and not
+/// sent by a web page from elsewhere, so a browser can't be turned against
+/// the API through DNS rebinding or a cross-site request
+fn check_local(http: &HttpRequest, port: u16) -> Result<(), ApiResponse> {
+    let local = |authority: &str| {
+        LOCAL_HOSTS
+            .iter()
+            .any(|host| authority == format!("{}:{}", host, port))
+    };
+    if !http.host.as_deref().is_some_and(local) {
+        return Err(ApiResponse::error(403, format!("Host must be 127.0.0.1:{} or localhost:{}", port, port)));
+    }
+    if let Some(origin) = &http.origin {
+        if !origin.strip_prefix("http://").is_some_and(local) {
+            return Err(ApiResponse::error(403, format!("requests from {} are not allowed", origin)));
+        }
+    }
+    Ok(())
+}
+
+/// WebSocket key, target side and hub of a `/api/stream?side=left|right`
+/// upgrade, when streaming is turned on
+fn stream_side(http: &HttpRequest, hub: Option<&Arc<StreamHub>>) -> Result<(String, Side, Arc<StreamHub>), ApiResponse> {
+    let Some(hub) = hub else {
+        return Err(ApiResponse::error(404, "frame streaming is turned off"));
+    };
+    let Some(key) = &http.websocket_key else {
+        return Err(ApiResponse::error(400, "/api/stream expects a WebSocket upgrade"));
+    };
+    let query = http.path.split_once('?').map(|(_, query)| query).unwrap_or_default();
+    let side = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("side="))
+        .map(|name| Side::parse(name).ok_or_else(|| ApiResponse::error(404, format!("no side '{}'", name))))
+        .transpose()?
+        .unwrap_or(Side::Left);
+    Ok((key.clone(), side, hub.clone()))
+}
+
+/// Map method and path to a request for the app
+fn route(http: &HttpRequest) -> Result<ApiRequest, ApiResponse> {
+    let path = http.path.split('?').next().unwrap_or_default();
+    let segments: Vec<String> = path.trim_matches('/').split('/').map(percent_decode).collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let side = |name: &str| Side::parse(name).ok_or_else(|| ApiResponse::error(404, format!("no side '{}'", name)));
+
+    match (http.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "devices"]) => Ok(ApiRequest::ListDevices),
+        ("PUT" | "POST", ["api", "devices", name, "brightness"]) => {
+            let level = json_field(&http.body, "brightness")?
+                .as_u64()
+                .filter(|level| *level <= 255)
+                .ok_or_else(|| ApiResponse::error(400, "brightness must be 0-255"))?;
+            Ok(ApiRequest::SetBrightness(side(name)?, level as u8))
+        }
+        ("PUT" | "POST", ["api", "devices", name, "preset"]) => {
+            let preset = json_field(&http.body, "preset")?
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| ApiResponse::error(400, "preset must be a string"))?;
+            Ok(ApiRequest::SetPreset(side(name)?, preset))
+        }
+        ("PUT" | "POST", ["api", "devices", name, "frame"]) => Ok(ApiRequest::PushFrame(side(name)?, frame_body(http)?)),
+        ("GET", ["api", "presets"]) => Ok(ApiRequest::ListPresets),
+        ("GET", ["api", "presets", name]) => Ok(ApiRequest::GetPreset(name.to_string())),
+        ("PUT" | "POST", ["api", "presets", name]) => Ok(ApiRequest::SavePreset(name.to_string(), frame_body(http)?)),
+        ("DELETE", ["api", "presets", name]) => Ok(ApiRequest::DeletePreset(name.to_string())),
+        (_, ["api", "devices", ..] | ["api", "presets", ..]) => Err(ApiResponse::error(405, "method not allowed")),
+        _ => Err(ApiResponse::error(404, format!("no endpoint {}", path))),
+    }
+}
+
+/// Read `field` from a JSON object body, or accept a bare JSON value
+fn json_field(body: &[u8], field: &str) -> Result<Value, ApiResponse> {
+    let value: Value = serde_json::from_slice(body).map_err(|e| ApiResponse::error(400, format!("invalid JSON: {}", e)))?;
+    Ok(match value {
+        Value::Object(mut object) => object.remove(field).unwrap_or(Value::Null),
+        value => value,
+    })
+}
+
+fn frame_body(http: &HttpRequest) -> Result<Vec<u8>, ApiResponse> {
+    decode_frame(http.content_type.as_deref(), &http.body).map_err(|e| ApiResponse::error(400, e))
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Turn an uploaded frame into 9x34 brightness values, row by row
+/// Accepts raw bytes (`application/octet-stream`), JSON (`application/json`:
+/// a flat array of 306 values, 34 rows of 9, or either under a `frame` key)
+/// and grayscale or color PNGs (`image/png`). Without a content type the
+/// format is guessed from the body
+pub fn decode_frame(content_type: Option<&str>, body: &[u8]) -> Result<Vec<u8>, String> {
+    let content_type = content_type
+        .map(|t| t.split(';').next().unwrap_or_default().trim())
+        // curl sends this for `--data-binary` unless told otherwise
+        .filter(|t| *t != "application/x-www-form-urlencoded");
+    let frame = match content_type {
+        Some("image/png") => decode_png(body)?,
+        Some("application/json") => decode_json_frame(body)?,
+        Some("application/octet-stream") => body.to_vec(),
+        Some(other) => return Err(format!("unsupported content type {}", other)),
+        None if body.starts_with(&PNG_SIGNATURE) => decode_png(body)?,
+        None if matches!(body.first(), Some(b'[' | b'{')) => decode_json_frame(body)?,
+        None => body.to_vec(),
+    };
+
+    if frame.len() != MATRIX_WIDTH * MATRIX_HEIGHT {
+        return Err(format!(
+            "expected {} pixels ({}x{}), got {}",
+            MATRIX_WIDTH * MATRIX_HEIGHT,
+            MATRIX_WIDTH,
+            MATRIX_HEIGHT,
+            frame.len()
+        ));
+    }
+    Ok(frame)
+}
+
+fn decode_json_frame(body: &[u8]) -> Result<Vec<u8>, String> {
+    let value: Value = serde_json::from_slice(body).map_err(|e| format!("invalid JSON: {}", e))?;
+    let value = match value {
+        Value::Object(mut object) => object.remove("frame").ok_or("missing 'frame'")?,
+        value => value,
+    };
+    let Value::Array(items) = value else {
+        return Err("frame must be an array".to_string());
+    };
+
+    let mut frame = Vec::with_capacity(MATRIX_WIDTH * MATRIX_HEIGHT);
+    for item in items {
+        match item {
+            // Nested rows are flattened in order
+            Value::Array(row) => {
+                for pixel in row {
+                    frame.push(json_pixel(&pixel)?);
+                }
+            }
+            pixel => frame.push(json_pixel(&pixel)?),
+        }
+    }
+    Ok(frame)
+}
+
+fn json_pixel(value: &Value) -> Result<u8, String> {
+    value
+        .as_u64()
+        .filter(|v| *v <= 255)
+        .map(|v| v as u8)
+        .ok_or_else(|| format!("pixel {} is not 0-255", value))
+}
+
+fn decode_png(body: &[u8]) -> Result<Vec<u8>, String> {
+    let mut decoder = png::Decoder::new(body);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| format!("invalid PNG: {}", e))?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| format!("invalid PNG: {}", e))?;
+    if info.width as usize != MATRIX_WIDTH || info.height as usize != MATRIX_HEIGHT {
+        return Err(format!(
+            "PNG must be {}x{}, got {}x{}",
+            MATRIX_WIDTH, MATRIX_HEIGHT, info.width, info.height
+        ));
+    }
+
+    let channels = info.color_type.samples();
+    // Alpha is ignored; color is reduced to its luma
+    let luma = |pixel: &[u8]| match *pixel {
+        [r, g, b, ..] => ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8,
+        [gray, ..] => gray,
+        [] => 0,
+    };
+    Ok(buf[..info.buffer_size()]
+        .chunks(info.line_size)
+        .flat_map(|line| line.chunks(channels).take(MATRIX_WIDTH).map(luma))
+        .collect())
+}
+
+/// Decode `%XX` escapes in a path segment
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORT: u16 = 7879;
+
+    fn request(method: &str, path: &str, host: Option<&str>, origin: Option<&str>) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            host: host.map(str::to_string),
+            origin: origin.map(str::to_string),
+            content_type: None,
+            websocket_key: None,
+            body: Vec::new(),
+        }
+    }
+
+    fn upgrade(origin: Option<&str>) -> HttpRequest {
+        HttpRequest {
+            websocket_key: Some("dGhlIHNhbXBsZSBub25jZQ==".to_string()),
+            ..request("GET", "/api/stream?side=right", Some("127.0.0.1:7879"), origin)
+        }
+    }
+
+    #[test]
+    fn accepts_local_hosts_on_the_server_port() {
+        for host in ["127.0.0.1:7879", "localhost:7879"] {
+            assert!(check_local(&request("GET", "/api/devices", Some(host), None), PORT).is_ok(), "{}", host);
+        }
+        let same_origin = request("GET", "/api/devices", Some("localhost:7879"), Some("http://localhost:7879"));
+        assert!(check_local(&same_origin, PORT).is_ok());
+    }
+
+    #[test]
+    fn rejects_foreign_or_missing_hosts() {
+        for host in [None, Some("evil.example:7879"), Some("127.0.0.1"), Some("localhost:80"), Some("127.0.0.1.evil.example:7879")] {
+            let response = check_local(&request("GET", "/api/devices", host, None), PORT).unwrap_err();
+            assert_eq!(response.status, 403, "{:?}", host);
+        }
+    }
+
+    #[test]
+    fn rejects_foreign_origins() {
+        for origin in ["http://evil.example", "https://localhost:7879", "http://localhost:8080", "null"] {
+            let http = request("POST", "/api/devices/left/preset", Some("127.0.0.1:7879"), Some(origin));
+            assert_eq!(check_local(&http, PORT).unwrap_err().status, 403, "{}", origin);
+        }
+    }
+
+    #[test]
+    fn stream_upgrade_needs_streaming_turned_on() {
+        let http = upgrade(None);
+        assert_eq!(stream_side(&http, None).err().map(|response| response.status), Some(404));
+        let hub = StreamHub::new();
+        let (key, side, _) = stream_side(&http, Some(&hub)).unwrap();
+        assert_eq!(key, "dGhlIHNhbXBsZSBub25jZQ==");
+        assert_eq!(side, Side::Right);
+    }
+
+    #[test]
+    fn stream_upgrade_from_a_foreign_page_is_refused() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let _server = spawn_server(port, StreamHub::new(), true, || {}).unwrap();
+
+        let handshake = |origin: &str| {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            write!(
+                stream,
+                "GET /api/stream?side=left HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nOrigin: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+                port, origin
+            )
+            .unwrap();
+            let mut status = String::new();
+            BufReader::new(stream).read_line(&mut status).unwrap();
+            status
+        };
+        assert!(handshake("http://evil.example").starts_with("HTTP/1.1 403"));
+        assert!(handshake(&format!("http://127.0.0.1:{}", port)).starts_with("HTTP/1.1 101"));
+    }
+
+    #[test]
+    fn routes_requests() {
+        let mut http = request("PUT", "/api/devices/right/preset", Some("localhost:7879"), None);
+        http.body = br#"{"preset": "clock"}"#.to_vec();
+        assert_eq!(route(&http).unwrap(), ApiRequest::SetPreset(Side::Right, "clock".to_string()));
+
+        let http = request("GET", "/api/presets/my%20smile", Some("localhost:7879"), None);
+        assert_eq!(route(&http).unwrap(), ApiRequest::GetPreset("my smile".to_string()));
+
+        let http = request("PATCH", "/api/presets", Some("localhost:7879"), None);
+        assert_eq!(route(&http).unwrap_err().status, 405);
+        let http = request("GET", "/nowhere", Some("localhost:7879"), None);
+        assert_eq!(route(&http).unwrap_err().status, 404);
+    }
+
+    #[test]
+    fn serves_requests_on_separate_connections() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let server = spawn_server(port, StreamHub::new(), false, || {}).unwrap();
+
+        let send = |host: &str| {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            write!(stream, "GET /api/devices HTTP/1.1\r\nHost: {}\r\n\r\n", host).unwrap();
+            stream
+        };
+        // A client that never finishes its request doesn't block the next one
+        let _idle = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let mut forbidden = send("evil.example");
+        let mut reply = String::new();
+        forbidden.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 403"), "{}", reply);
+
+        let mut allowed = send(&format!("localhost:{}", port));
+        let call = server.rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(call.request, ApiRequest::ListDevices);
+        call.respond(ApiResponse::ok(json!([])));
+        let mut reply = String::new();
+        allowed.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200"), "{}", reply);
+    }
+
+    #[test]
+    fn port_is_free_again_once_dropped() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let server = spawn_server(port, StreamHub::new(), false, || {}).unwrap();
+        drop(server);
+        let restarted = spawn_server(port, StreamHub::new(), false, || {});
+        assert!(restarted.is_ok(), "{:?}", restarted.err());
+    }
+
+    #[test]
+    fn streaming_can_be_toggled_while_running() {
+        let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let server = spawn_server(port, StreamHub::new(), false, || {}).unwrap();
+        let handshake = || {
+            let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+            write!(
+                stream,
+                "GET /api/stream?side=left HTTP/1.1\r\nHost: 127.0.0.1:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+                port
+            )
+            .unwrap();
+            let mut status = String::new();
+            BufReader::new(stream).read_line(&mut status).unwrap();
+            status
+        };
+        assert!(handshake().starts_with("HTTP/1.1 404"));
+        server.set_streaming(true);
+        assert!(handshake().starts_with("HTTP/1.1 101"));
+    }
+}
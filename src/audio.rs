@@ -0,0 +1,684 @@
+// src/audio.rs
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Samples per FFT; a power of two
+const FFT_SIZE: usize = 1024;
+
+/// New samples between two spectra, so consecutive windows overlap by half
+const HOP_SIZE: usize = FFT_SIZE / 2;
+
+/// Frequency range spread over the bands, logarithmically
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 16_000.0;
+
+/// Band levels are shown from this many dB below full scale up to 0 dB
+const DYNAMIC_RANGE_DB: f32 = 60.0;
+
+/// How fast bars and dropped peaks fall, in full heights per second
+const BAR_FALL_RATE: f32 = 1.5;
+const PEAK_FALL_RATE: f32 = 0.8;
+
+/// How long a peak marker stays put before falling
+const PEAK_HOLD: Duration = Duration::from_secs(1);
+
+/// Something producing mono samples in -1.0..=1.0
+pub trait AudioSource: Send {
+    fn name(&self) -> String;
+    fn sample_rate(&self) -> u32;
+    /// Whether samples arrive in real time; others (files) are paced by the reader
+    fn is_live(&self) -> bool;
+    /// Fill `buf` with up to `buf.len()` samples, returning how many were
+    /// written; 0 means the source has ended
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize>;
+}
+
+/// Layout of one sample in interleaved PCM data
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SampleFormat {
+    U8,
+    S16,
+    S24,
+    S32,
+    F32,
+}
+
+impl SampleFormat {
+    fn bytes(&self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::S16 => 2,
+            SampleFormat::S24 => 3,
+            SampleFormat::S32 | SampleFormat::F32 => 4,
+        }
+    }
+
+    /// Decode one little-endian sample
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+            SampleFormat::S16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            SampleFormat::S24 => (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8_388_608.0,
+            SampleFormat::S32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2_147_483_648.0,
+            SampleFormat::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+/// Interleaved little-endian PCM from any reader, mixed down to mono
+pub struct PcmSource<R: Read + Send> {
+    name: String,
+    reader: R,
+    format: SampleFormat,
+    channels: usize,
+    sample_rate: u32,
+    live: bool,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read + Send> PcmSource<R> {
+    pub fn new(name: String, reader: R, format: SampleFormat, channels: usize, sample_rate: u32, live: bool) -> Self {
+        Self {
+            name,
+            reader,
+            format,
+            channels: channels.max(1),
+            sample_rate,
+            live,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl<R: Read + Send> AudioSource for PcmSource<R> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn is_live(&self) -> bool {
+        self.live
+    }
+
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+        let frame_bytes = self.format.bytes() * self.channels;
+        self.bytes.resize(buf.len() * frame_bytes, 0);
+
+        // Read whole frames only, so channels never get out of step
+        let mut filled = 0;
+        while filled < frame_bytes || filled % frame_bytes != 0 {
+            match self.reader.read(&mut self.bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let frames = filled / frame_bytes;
+        for (sample, frame) in buf.iter_mut().zip(self.bytes[..frames * frame_bytes].chunks(frame_bytes)) {
+            let sum: f32 = frame.chunks(self.format.bytes()).map(|s| self.format.decode(s)).sum();
+            *sample = sum / self.channels as f32;
+        }
+        Ok(frames)
+    }
+}
+
+/// Open a WAV file (PCM 8/16/24/32-bit or 32-bit float) as a source
+pub fn open_wav(path: &str) -> io::Result<PcmSource<BufReader<File>>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, message));
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut riff = [0u8; 12];
+    reader.read_exact(&mut riff)?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(invalid("not a WAV file"));
+    }
+
+    let mut format = None;
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).map_err(|_| invalid("no data chunk"))?;
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        match &header[0..4] {
+            b"fmt " => {
+                let mut fmt = vec![0u8; size];
+                reader.read_exact(&mut fmt)?;
+                if fmt.len() < 16 {
+                    return Err(invalid("short fmt chunk"));
+                }
+                let tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]) as usize;
+                let rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+                // 1 = integer PCM, 3 = float, 0xFFFE = extensible (judged by bit depth)
+                let sample_format = match (tag, bits) {
+                    (1 | 0xFFFE, 8) => SampleFormat::U8,
+                    (1 | 0xFFFE, 16) => SampleFormat::S16,
+                    (1 | 0xFFFE, 24) => SampleFormat::S24,
+                    (1, 32) => SampleFormat::S32,
+                    (3 | 0xFFFE, 32) => SampleFormat::F32,
+                    _ => return Err(invalid(&format!("unsupported format {} with {} bits", tag, bits))),
+                };
+                format = Some((sample_format, channels, rate));
+            }
+            b"data" => {
+                let (sample_format, channels, rate) = format.ok_or_else(|| invalid("data before fmt"))?;
+                let name = format!("WAV {}", path);
+                return Ok(PcmSource::new(name, reader, sample_format, channels, rate, false));
+            }
+            _ => {
+                // Chunks are padded to an even size
+                io::copy(&mut (&mut reader).take((size + size % 2) as u64), &mut io::sink())?;
+            }
+        }
+    }
+}
+
+/// Raw PCM on standard input, e.g. `sox song.mp3 -t raw -e signed -b 16 -c 1 -r 44100 - | led_controller`
+pub fn open_stdin(sample_rate: u32) -> PcmSource<io::Stdin> {
+    let name = format!("stdin (s16le mono {} Hz)", sample_rate);
+    PcmSource::new(name, io::stdin(), SampleFormat::S16, 1, sample_rate, true)
+}
+
+/// What the desktop is playing, recorded from the default sink's monitor
+/// with `parec`; works with PulseAudio and PipeWire's Pulse server
+pub struct MonitorSource {
+    child: Child,
+    pcm: PcmSource<std::process::ChildStdout>,
+}
+
+impl MonitorSource {
+    const SAMPLE_RATE: u32 = 44_100;
+
+    pub fn open() -> io::Result<Self> {
+        let mut child = Command::new("parec")
+            .args([
+                "--device=@DEFAULT_MONITOR@",
+                "--format=s16le",
+                "--channels=1",
+                "--latency-msec=20",
+            ])
+            .arg(format!("--rate={}", Self::SAMPLE_RATE))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let name = "PulseAudio/PipeWire monitor".to_string();
+        let pcm = PcmSource::new(name, stdout, SampleFormat::S16, 1, Self::SAMPLE_RATE, true);
+        Ok(Self { child, pcm })
+    }
+}
+
+impl AudioSource for MonitorSource {
+    fn name(&self) -> String {
+        self.pcm.name()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.pcm.sample_rate()
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+
+    fn read(&mut self, buf: &mut [f32]) -> io::Result<usize> {
+        self.pcm.read(buf)
+    }
+}
+
+impl Drop for MonitorSource {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum AudioSourceKind {
+    Monitor,
+    WavFile,
+    Stdin,
+}
+
+impl AudioSourceKind {
+    pub const ALL: [AudioSourceKind; 3] = [AudioSourceKind::Monitor, AudioSourceKind::WavFile, AudioSourceKind::Stdin];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AudioSourceKind::Monitor => "Desktop audio (PulseAudio/PipeWire)",
+            AudioSourceKind::WavFile => "WAV file",
+            AudioSourceKind::Stdin => "Raw PCM on stdin",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub source: AudioSourceKind,
+    pub wav_path: String,
+    /// Sample rate of s16le mono PCM read from stdin
+    pub stdin_rate: u32,
+    /// Spread 18 bands over both sides when both show the spectrum
+    pub spanned: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            source: AudioSourceKind::Monitor,
+            wav_path: String::new(),
+            stdin_rate: 44_100,
+            spanned: false,
+        }
+    }
+}
+
+/// Open the source picked in `settings`
+pub fn open_source(settings: &AudioSettings) -> io::Result<Box<dyn AudioSource>> {
+    Ok(match settings.source {
+        AudioSourceKind::Monitor => Box::new(MonitorSource::open()?),
+        AudioSourceKind::WavFile => Box::new(open_wav(&settings.wav_path)?),
+        AudioSourceKind::Stdin => Box::new(open_stdin(settings.stdin_rate)),
+    })
+}
+
+/// In-place radix-2 FFT; the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        let (w_re, w_im) = (angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let (mut c_re, mut c_im) = (1.0f32, 0.0f32);
+            for k in 0..len / 2 {
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * c_re - im[b] * c_im;
+                let t_im = re[b] * c_im + im[b] * c_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+                (c_re, c_im) = (c_re * w_re - c_im * w_im, c_re * w_im + c_im * w_re);
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Bar heights and peak markers, 0.0..=1.0, lowest band first
+#[derive(Clone, Default, Debug)]
+pub struct Spectrum {
+    pub levels: Vec<f32>,
+    pub peaks: Vec<f32>,
+}
+
+/// Turns a stream of samples into smoothed band levels with peak-hold
+pub struct SpectrumAnalyzer {
+    sample_rate: u32,
+    window: Vec<f32>,
+    history: Vec<f32>,
+    spectrum: Spectrum,
+    /// Time since each peak was last pushed up, in stream time
+    peak_age: Vec<Duration>,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(bands: usize, sample_rate: u32) -> Self {
+        // Hann window
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())
+            .collect();
+        Self {
+            sample_rate: sample_rate.max(1),
+            window,
+            history: Vec::with_capacity(FFT_SIZE + HOP_SIZE),
+            spectrum: Spectrum {
+                levels: vec![0.0; bands],
+                peaks: vec![0.0; bands],
+            },
+            peak_age: vec![Duration::ZERO; bands],
+        }
+    }
+
+    pub fn spectrum(&self) -> &Spectrum {
+        &self.spectrum
+    }
+
+    /// Feed samples; the spectrum is updated every `HOP_SIZE` samples
+    pub fn process(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.history.push(sample);
+            if self.history.len() == FFT_SIZE {
+                self.analyze();
+                self.history.drain(..HOP_SIZE);
+            }
+        }
+    }
+
+    fn analyze(&mut self) {
+        let mut re: Vec<f32> = self.history.iter().zip(&self.window).map(|(s, w)| s * w).collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+
+        let bands = self.spectrum.levels.len();
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let max_frequency = MAX_FREQUENCY.min(nyquist);
+        let bin_width = self.sample_rate as f32 / FFT_SIZE as f32;
+        let dt = Duration::from_secs_f32(HOP_SIZE as f32 / self.sample_rate as f32);
+        // A full scale sine through the Hann window peaks at about N/4
+        let full_scale = FFT_SIZE as f32 / 4.0;
+
+        for band in 0..bands {
+            let edge = |i: usize| MIN_FREQUENCY * (max_frequency / MIN_FREQUENCY).powf(i as f32 / bands as f32);
+            let first_bin = ((edge(band) / bin_width) as usize).max(1);
+            let last_bin = ((edge(band + 1) / bin_width) as usize).clamp(first_bin, FFT_SIZE / 2 - 1);
+            let magnitude = (first_bin..=last_bin)
+                .map(|bin| (re[bin] * re[bin] + im[bin] * im[bin]).sqrt())
+                .fold(0.0, f32::max);
+
+            let db = 20.0 * (magnitude / full_scale).max(1e-9).log10();
+            let target = ((db + DYNAMIC_RANGE_DB) / DYNAMIC_RANGE_DB).clamp(0.0, 1.0);
+
+            // Bars jump up and fall back slowly
+            let level = &mut self.spectrum.levels[band];
+            *level = target.max(*level - BAR_FALL_RATE * dt.as_secs_f32());
+
+            let peak = &mut self.spectrum.peaks[band];
+            if *level >= *peak {
+                *peak = *level;
+                self.peak_age[band] = Duration::ZERO;
+            } else {
+                self.peak_age[band] += dt;
+                if self.peak_age[band] > PEAK_HOLD {
+                    *peak = (*peak - PEAK_FALL_RATE * dt.as_secs_f32()).max(*level);
+                }
+            }
+        }
+    }
+}
+
+/// Shared view of a running visualizer's latest spectrum
+#[derive(Clone)]
+pub struct SpectrumFeed(Arc<Mutex<Spectrum>>);
+
+impl SpectrumFeed {
+    pub fn spectrum(&self) -> Spectrum {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Reads a source and analyses it on a background thread; stops when dropped
+pub struct AudioVisualizer {
+    spectrum: Arc<Mutex<Spectrum>>,
+    status: Arc<Mutex<String>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl AudioVisualizer {
+    pub fn start(mut source: Box<dyn AudioSource>, bands: usize) -> Self {
+        let spectrum = Arc::new(Mutex::new(Spectrum {
+            levels: vec![0.0; bands],
+            peaks: vec![0.0; bands],
+        }));
+        let status = Arc::new(Mutex::new(format!("Listening to {}", source.name())));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let (shared, thread_status, stopped) = (spectrum.clone(), status.clone(), stop.clone());
+        thread::spawn(move || {
+            let rate = source.sample_rate();
+            let mut analyzer = SpectrumAnalyzer::new(bands, rate);
+            let mut buf = vec![0.0f32; HOP_SIZE];
+            let started = Instant::now();
+            let mut samples_read = 0u64;
+
+            let ended = loop {
+                if stopped.load(Ordering::Relaxed) {
+                    break None;
+                }
+                match source.read(&mut buf) {
+                    Ok(0) => break Some(format!("{} ended", source.name())),
+                    Ok(n) => {
+                        analyzer.process(&buf[..n]);
+                        *shared.lock().unwrap_or_else(|e| e.into_inner()) = analyzer.spectrum().clone();
+                        samples_read += n as u64;
+                    }
+                    Err(e) => break Some(format!("{}: {}", source.name(), e)),
+                }
+                // Files would be read in an instant; play them back in real time
+                if !source.is_live() {
+                    let due = Duration::from_secs_f64(samples_read as f64 / rate as f64);
+                    if let Some(wait) = due.checked_sub(started.elapsed()) {
+                        thread::sleep(wait);
+                    }
+                }
+            };
+
+            if let Some(message) = ended {
+                *thread_status.lock().unwrap_or_else(|e| e.into_inner()) = message;
+                // Let the bars drop to nothing rather than freeze
+                *shared.lock().unwrap_or_else(|e| e.into_inner()) = Spectrum {
+                    levels: vec![0.0; bands],
+                    peaks: vec![0.0; bands],
+                };
+            }
+        });
+
+        Self { spectrum, status, stop }
+    }
+
+    /// A handle render threads can read the spectrum through
+    pub fn feed(&self) -> SpectrumFeed {
+        SpectrumFeed(self.spectrum.clone())
+    }
+
+    pub fn status(&self) -> String {
+        self.status.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Drop for AudioVisualizer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44_100;
+
+    fn sine(frequency: f32, samples: usize) -> Vec<f32> {
+        (0..samples)
+            .map(|i| (2.0 * PI * frequency * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn fft_puts_a_sine_in_its_bin() {
+        // Exactly 37 cycles over the block
+        let mut re: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| (2.0 * PI * 37.0 * i as f32 / FFT_SIZE as f32).sin())
+            .collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+
+        let magnitudes: Vec<f32> = (0..FFT_SIZE / 2).map(|bin| (re[bin] * re[bin] + im[bin] * im[bin]).sqrt()).collect();
+        let loudest = (0..magnitudes.len()).max_by(|&a, &b| magnitudes[a].total_cmp(&magnitudes[b])).unwrap();
+        assert_eq!(loudest, 37);
+        assert!((magnitudes[37] - FFT_SIZE as f32 / 2.0).abs() < 1.0);
+        assert!(magnitudes.iter().enumerate().filter(|&(bin, _)| bin != 37).all(|(_, &m)| m < 0.1));
+    }
+
+    #[test]
+    fn analyzer_lights_the_band_of_a_tone() {
+        let bands = 9;
+        let mut analyzer = SpectrumAnalyzer::new(bands, RATE);
+        analyzer.process(&sine(1000.0, FFT_SIZE * 4));
+        let levels = &analyzer.spectrum().levels;
+
+        // Bands are spaced logarithmically between the frequency limits
+        let band = ((1000.0f32 / MIN_FREQUENCY).ln() / (MAX_FREQUENCY / MIN_FREQUENCY).ln() * bands as f32) as usize;
+        let loudest = (0..bands).max_by(|&a, &b| levels[a].total_cmp(&levels[b])).unwrap();
+        assert_eq!(loudest, band);
+        assert!(levels[band] > 0.9, "full scale tone fills its bar: {:?}", levels);
+        assert!(levels[0] < 0.3 && levels[bands - 1] < 0.3, "far bands stay low: {:?}", levels);
+    }
+
+    #[test]
+    fn analyzer_waits_for_a_full_block() {
+        let mut analyzer = SpectrumAnalyzer::new(4, RATE);
+        analyzer.process(&sine(1000.0, FFT_SIZE - 1));
+        assert!(analyzer.spectrum().levels.iter().all(|&level| level == 0.0));
+        analyzer.process(&[0.0]);
+        assert!(analyzer.spectrum().levels.iter().any(|&level| level > 0.0));
+    }
+
+    fn wav(format_tag: u16, channels: u16, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(format_tag.to_le_bytes());
+        fmt.extend(channels.to_le_bytes());
+        fmt.extend(RATE.to_le_bytes());
+        fmt.extend((RATE * channels as u32 * bits as u32 / 8).to_le_bytes());
+        fmt.extend((channels * bits / 8).to_le_bytes());
+        fmt.extend(bits.to_le_bytes());
+
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        // An unrelated chunk with odd size, which is padded
+        bytes.extend(b"LIST\x03\0\0\0abc\0");
+        bytes.extend(b"fmt ");
+        bytes.extend((fmt.len() as u32).to_le_bytes());
+        bytes.extend(fmt);
+        bytes.extend(b"data");
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn open(bytes: &[u8]) -> io::Result<PcmSource<BufReader<File>>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("test.wav");
+        std::fs::write(&path, bytes)?;
+        // The open file outlives the directory on Unix
+        open_wav(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn reads_a_stereo_wav_mixed_to_mono() {
+        let samples: [i16; 4] = [16384, 0, -32768, -32768];
+        let data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut source = open(&wav(1, 2, 16, &data)).unwrap();
+        assert_eq!(source.sample_rate(), RATE);
+        assert!(!source.is_live());
+
+        let mut buf = [9.0; 4];
+        assert_eq!(source.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], [0.25, -1.0]);
+        assert_eq!(source.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_wav_headers() {
+        let error = |bytes: &[u8]| open(bytes).err().map(|e| e.to_string()).unwrap_or_default();
+
+        assert!(error(b"RIFX\0\0\0\0WAVE").contains("not a WAV file"));
+        assert!(error(b"RIFF\0\0\0\0AVI ").contains("not a WAV file"));
+        assert!(!error(b"RIFF").is_empty(), "truncated header");
+        assert!(error(&wav(1, 1, 12, &[])).contains("unsupported format 1 with 12 bits"));
+        assert!(error(&wav(2, 1, 16, &[])).contains("unsupported format 2"));
+
+        let mut no_data = wav(1, 1, 16, &[]);
+        no_data.truncate(no_data.len() - 8);
+        assert!(error(&no_data).contains("no data chunk"));
+
+        let mut data_first = b"RIFF\0\0\0\0WAVE".to_vec();
+        data_first.extend(b"data\x02\0\0\0\0\0");
+        assert!(error(&data_first).contains("data before fmt"));
+
+        let mut short_fmt = b"RIFF\0\0\0\0WAVE".to_vec();
+        short_fmt.extend(b"fmt \x04\0\0\0\x01\0\x01\0");
+        assert!(error(&short_fmt).contains("short fmt chunk"));
+    }
+
+    /// Hands out a few bytes per read, like a pipe
+    struct Trickle {
+        data: Vec<u8>,
+        position: usize,
+        chunk: usize,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let end = (self.position + self.chunk).min(self.data.len());
+            let n = (end - self.position).min(buf.len());
+            buf[..n].copy_from_slice(&self.data[self.position..self.position + n]);
+            self.position += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn stdin_pcm_is_read_in_whole_frames() {
+        // s16le mono, as `open_stdin` reads it, arriving 3 bytes at a time
+        let samples: Vec<i16> = vec![0, 8192, -8192, 32767, -32768];
+        let mut data: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        // A trailing half sample at the end of the stream is dropped
+        data.push(0x7F);
+        let reader = Trickle { data, position: 0, chunk: 3 };
+        let mut source = PcmSource::new("stdin".to_string(), reader, SampleFormat::S16, 1, RATE, true);
+
+        let mut decoded = Vec::new();
+        let mut buf = [0.0; 2];
+        loop {
+            let frames = source.read(&mut buf).unwrap();
+            if frames == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&buf[..frames]);
+        }
+        assert_eq!(decoded, [0.0, 0.25, -0.25, 32767.0 / 32768.0, -1.0]);
+    }
+
+    #[test]
+    fn stereo_pcm_keeps_channels_in_step() {
+        let frames: [[i16; 2]; 3] = [[32767, -32768], [16384, 16384], [0, -16384]];
+        let data: Vec<u8> = frames.iter().flatten().flat_map(|s| s.to_le_bytes()).collect();
+        let reader = Trickle { data, position: 0, chunk: 5 };
+        let mut source = PcmSource::new("pipe".to_string(), reader, SampleFormat::S16, 2, RATE, true);
+
+        let mut decoded = Vec::new();
+        let mut buf = [0.0; 1];
+        while source.read(&mut buf).unwrap() == 1 {
+            decoded.push(buf[0]);
+        }
+        assert_eq!(decoded, [-0.5 / 32768.0, 0.5, -0.25]);
+    }
+}
@@ -0,0 +1,571 @@
+// src/builtins.rs
+//! The presets that ship with the app, plus the wrappers that show custom
+//! images and scripts through the same `Preset` trait.
+use chrono::Local;
+use std::time::Instant;
+
+use crate::animations::*;
+use crate::api::Side;
+use crate::commands::*;
+use crate::params::ParamSpec;
+use crate::presets::{MATRIX_HEIGHT, MATRIX_WIDTH};
+use crate::registry::{Preset, PresetCategory, PresetContext, PresetOutput, ANIMATED_FPS};
+use crate::script::{Program, ScriptInputs, ScriptInstance, SCRIPT_PREFIX};
+use crate::timer::{PomodoroPhase, TimerMode};
+use crate::utils::*;
+
+/// Every built-in, in the order the pickers list them
+pub fn all() -> Vec<Box<dyn Preset>> {
+    vec![
+        Box::new(IdlePreset),
+        Box::new(PercentagePreset::new("cpu", "CPU Usage", |ctx| ctx.cpu_percent)),
+        Box::new(PercentagePreset::new("ram", "RAM Usage", |ctx| ctx.ram_percent)),
+        Box::new(NetworkPreset),
+        Box::new(PercentagePreset::new("net_up", "Network Upload", |ctx| ctx.net_tx_percent)),
+        Box::new(PercentagePreset::new("net_down", "Network Download", |ctx| ctx.net_rx_percent)),
+        Box::new(DiskPreset),
+        Box::new(DiskIoPreset),
+        Box::new(TemperaturePreset),
+        Box::new(PercentagePreset::new("fan", "Fan Speed", |ctx| ctx.fan_percent)),
+        Box::new(ClockPreset),
+        Box::new(BatteryPreset),
+        Box::new(TimerPreset),
+        Box::new(TextPreset::default()),
+        Box::new(ApiFramePreset),
+        Box::new(AudioPreset),
+        Box::new(BreathingPreset::default()),
+        Box::new(RainPreset::default()),
+        Box::new(FirePreset::default()),
+        Box::new(PlasmaPreset::default()),
+        Box::new(StarfieldPreset::default()),
+        Box::new(BallPreset::default()),
+        Box::new(ScannerPreset::default()),
+        Box::new(RipplePreset::default()),
+        Box::new(FirmwarePattern::new("gradient", "Gradient", pattern_gradient)),
+        Box::new(FirmwarePattern::new("double_gradient", "Double Gradient", pattern_double_gradient)),
+        Box::new(FirmwarePattern::new("zigzag", "ZigZag", pattern_zigzag)),
+        Box::new(FirmwarePattern::new("lotus_h", "LOTUS Horiz", pattern_lotus_horizontal)),
+        Box::new(FirmwarePattern::new("lotus_v", "LOTUS Vert", pattern_lotus_vertical)),
+        Box::new(FirmwarePattern::new("full_brightness", "Full Bright", pattern_full_brightness)),
+        Box::new(FirmwarePattern::new("panic", "PANIC", pattern_panic)),
+    ]
+}
+
+/// Whether `id` belongs to a built-in, which a custom preset of the same
+/// name would be hidden behind
+pub fn is_builtin_id(id: &str) -> bool {
+    all().iter().any(|preset| preset.id() == id)
+}
+
+/// Cycle through all of the firmware's idle animations, or stay on one
+const IDLE_ANIMATIONS: [&str; 4] = ["Cycle", "Animation 1", "Animation 2", "Animation 3"];
+
+/// Cycles through the firmware's built-in animations
+struct IdlePreset;
+
+impl Preset for IdlePreset {
+    fn id(&self) -> &str {
+        "idle"
+    }
+
+    fn name(&self) -> &str {
+        "Idle Animation"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::General
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::choice("animation", "Animation", IDLE_ANIMATIONS.to_vec(), IDLE_ANIMATIONS[0]),
+            ParamSpec::range("updates_per_step", "Updates per step", 1.0, 20.0, 4.0),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let pattern = match IDLE_ANIMATIONS.iter().position(|a| *a == ctx.params.text("animation")) {
+            Some(index) if index > 0 => index as u8 - 1,
+            _ => (ctx.idle_frame / ctx.params.number("updates_per_step").max(1.0) as u8) % 3,
+        };
+        PresetOutput::Command(vec![MAGIC1, MAGIC2, 0x14, pattern])
+    }
+}
+
+/// A metric shown with the firmware's percentage bar
+struct PercentagePreset {
+    id: &'static str,
+    name: &'static str,
+    value: fn(&PresetContext) -> u8,
+}
+
+impl PercentagePreset {
+    fn new(id: &'static str, name: &'static str, value: fn(&PresetContext) -> u8) -> Self {
+        Self { id, name, value }
+    }
+}
+
+impl Preset for PercentagePreset {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Metrics
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        PresetOutput::Command(pattern_percentage((self.value)(ctx)))
+    }
+}
+
+struct NetworkPreset;
+
+impl Preset for NetworkPreset {
+    fn id(&self) -> &str {
+        "net"
+    }
+
+    fn name(&self) -> &str {
+        "Network Up/Down"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Metrics
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        PresetOutput::Frame(render_network_display(ctx.net_rx_percent, ctx.net_tx_percent))
+    }
+}
+
+struct DiskPreset;
+
+impl Preset for DiskPreset {
+    fn id(&self) -> &str {
+        "disk"
+    }
+
+    fn name(&self) -> &str {
+        "Disk Usage"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Metrics
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let usages: Vec<u8> = ctx.disks.iter().map(|d| d.usage_percent).collect();
+        PresetOutput::Frame(render_disk_usage_display(&usages, ctx.idle_frame))
+    }
+}
+
+struct DiskIoPreset;
+
+impl Preset for DiskIoPreset {
+    fn id(&self) -> &str {
+        "disk_io"
+    }
+
+    fn name(&self) -> &str {
+        "Disk I/O"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Metrics
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        PresetOutput::Frame(render_disk_io_sparkline(ctx.disk_io_history))
+    }
+}
+
+struct TemperaturePreset;
+
+impl Preset for TemperaturePreset {
+    fn id(&self) -> &str {
+        "temp"
+    }
+
+    fn name(&self) -> &str {
+        "Temperature"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Metrics
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        PresetOutput::Frame(render_thermometer_display(
+            ctx.temperature,
+            ctx.temp_warning,
+            ctx.temp_critical,
+            ctx.idle_frame,
+        ))
+    }
+}
+
+struct ClockPreset;
+
+impl Preset for ClockPreset {
+    fn id(&self) -> &str {
+        "clock"
+    }
+
+    fn name(&self) -> &str {
+        "Clock"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Display
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        let defaults = ClockOptions::default();
+        vec![
+            ParamSpec::choice("face", "Face", ClockFace::ALL.iter().map(|f| f.label()).collect(), defaults.face.label()),
+            ParamSpec::toggle("twelve_hour", "12-hour", defaults.twelve_hour),
+            ParamSpec::toggle("show_seconds", "Seconds bar", defaults.show_seconds),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let options = ClockOptions {
+            face: ClockFace::ALL
+                .into_iter()
+                .find(|face| face.label() == ctx.params.text("face"))
+                .unwrap_or(ClockOptions::default().face),
+            twelve_hour: ctx.params.flag("twelve_hour"),
+            show_seconds: ctx.params.flag("show_seconds"),
+        };
+        PresetOutput::Frame(render_clock_display(&options, &Local::now()))
+    }
+}
+
+struct BatteryPreset;
+
+impl Preset for BatteryPreset {
+    fn id(&self) -> &str {
+        "battery"
+    }
+
+    fn name(&self) -> &str {
+        "Battery"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Display
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        let defaults = BatteryStyle::default();
+        vec![
+            ParamSpec::level("outline", "Outline", defaults.outline),
+            ParamSpec::level("high", "Charged", defaults.high),
+            ParamSpec::level("medium", "Medium", defaults.medium),
+            ParamSpec::level("low", "Low", defaults.low),
+            ParamSpec::number("medium_below", "Medium at or below %", 0.0, 100.0, defaults.medium_below as f64),
+            ParamSpec::number("low_below", "Low at or below %", 0.0, 100.0, defaults.low_below as f64),
+        ]
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let style = BatteryStyle {
+            outline: ctx.params.level("outline"),
+            high: ctx.params.level("high"),
+            medium: ctx.params.level("medium"),
+            low: ctx.params.level("low"),
+            medium_below: ctx.params.number("medium_below") as f32,
+            low_below: ctx.params.number("low_below") as f32,
+        };
+        PresetOutput::Frame(render_battery_display(ctx.batteries, ctx.idle_frame, &style))
+    }
+}
+
+struct TimerPreset;
+
+impl Preset for TimerPreset {
+    fn id(&self) -> &str {
+        "timer"
+    }
+
+    fn name(&self) -> &str {
+        "Timer"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Display
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let now = Instant::now();
+        let timer = ctx.timer;
+        PresetOutput::Frame(render_timer_display(
+            timer.display_time(now),
+            timer.fraction_remaining(now),
+            timer.mode == TimerMode::Pomodoro && timer.phase() == PomodoroPhase::Break,
+            timer.is_flashing(now),
+            ctx.idle_frame,
+        ))
+    }
+}
+
+/// Text scrolling up the matrix
+struct TextPreset {
+    started: Instant,
+}
+
+impl Default for TextPreset {
+    fn default() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Preset for TextPreset {
+    fn id(&self) -> &str {
+        "text"
+    }
+
+    fn name(&self) -> &str {
+        "Scrolling Text"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Display
+    }
+
+    fn params(&self) -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::text("text", "Text", 64, "HELLO"),
+            ParamSpec::level("brightness", "Brightness", 200),
+            ParamSpec::range("speed", "Rows per second", 1.0, 30.0, 8.0),
+        ]
+    }
+
+    /// Starts below the bottom edge and scrolls until the last character
+    /// has left the top, then repeats
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let text = ctx.params.text("text");
+        let travel = (MATRIX_HEIGHT + text.chars().count() * TEXT_LINE_HEIGHT) as f64;
+        let scrolled = (self.started.elapsed().as_secs_f64() * ctx.params.number("speed")) % travel;
+        let offset = MATRIX_HEIGHT as i32 - scrolled as i32;
+        PresetOutput::Frame(render_vertical_text(text, offset, ctx.params.level("brightness")))
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+/// The last frame pushed over the HTTP API or MQTT
+struct ApiFramePreset;
+
+impl Preset for ApiFramePreset {
+    fn id(&self) -> &str {
+        "api_frame"
+    }
+
+    fn name(&self) -> &str {
+        "API Frame"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Display
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        match ctx.api_frame {
+            Some(frame) => PresetOutput::Frame(frame.to_vec()),
+            None => PresetOutput::Command(Vec::new()),
+        }
+    }
+}
+
+struct AudioPreset;
+
+impl Preset for AudioPreset {
+    fn id(&self) -> &str {
+        "audio"
+    }
+
+    fn name(&self) -> &str {
+        "Audio Spectrum"
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Display
+    }
+
+    /// In spanned mode the left side shows the low bands and the right side
+    /// the high ones
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let Some(feed) = ctx.audio else {
+            return PresetOutput::Frame(vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT]);
+        };
+        let spectrum = feed.spectrum();
+        let start = match ctx.side {
+            Side::Right if spectrum.levels.len() > MATRIX_WIDTH => MATRIX_WIDTH,
+            _ => 0,
+        };
+        PresetOutput::Frame(render_spectrum(&spectrum.levels[start..], &spectrum.peaks[start..]))
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+}
+
+/// A pattern the firmware draws itself
+struct FirmwarePattern {
+    id: &'static str,
+    name: &'static str,
+    command: fn() -> Vec<u8>,
+}
+
+impl FirmwarePattern {
+    fn new(id: &'static str, name: &'static str, command: fn() -> Vec<u8>) -> Self {
+        Self { id, name, command }
+    }
+}
+
+impl Preset for FirmwarePattern {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Patterns
+    }
+
+    fn tick(&mut self, _ctx: &PresetContext) -> PresetOutput {
+        PresetOutput::Command((self.command)())
+    }
+}
+
+/// An image saved in the preset editor; its id is its name
+pub struct CustomImagePreset {
+    name: String,
+}
+
+impl CustomImagePreset {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string() }
+    }
+}
+
+impl Preset for CustomImagePreset {
+    fn id(&self) -> &str {
+        &self.name
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Custom
+    }
+
+    /// Looked up on every tick so edits and deletes show straight away
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        match ctx.custom.get_preset(&self.name) {
+            Some(image_data) => PresetOutput::Frame(image_data),
+            None => PresetOutput::Command(Vec::new()),
+        }
+    }
+}
+
+/// A script from the scripts folder, parsed the first time it's shown.
+/// A script that fails stays blank until the scripts are reloaded
+pub struct ScriptPreset {
+    id: String,
+    name: String,
+    started: bool,
+    instance: Option<ScriptInstance>,
+    messages: Vec<String>,
+}
+
+impl ScriptPreset {
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: Self::id_for(name),
+            name: name.to_string(),
+            started: false,
+            instance: None,
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn id_for(name: &str) -> String {
+        format!("{}{}", SCRIPT_PREFIX, name)
+    }
+}
+
+impl Preset for ScriptPreset {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn category(&self) -> PresetCategory {
+        PresetCategory::Script
+    }
+
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput {
+        let blank = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+        if !self.started {
+            self.started = true;
+            match ctx.scripts.source(&self.name).map(Program::parse) {
+                Some(Ok(program)) => self.instance = Some(ScriptInstance::new(program)),
+                Some(Err(e)) => self.messages.push(format!("Script '{}' {}", self.name, e)),
+                None => {}
+            }
+        }
+        let Some(script) = &mut self.instance else {
+            return PresetOutput::Frame(blank);
+        };
+
+        let inputs = ScriptInputs {
+            cpu: ctx.cpu_percent as f64,
+            ram: ctx.ram_percent as f64,
+            net_down: ctx.net_rx_percent as f64,
+            net_up: ctx.net_tx_percent as f64,
+            disk: ctx.disks.iter().map(|d| d.usage_percent as f64).reduce(f64::max).unwrap_or(0.0),
+            temperature: ctx.temperature as f64,
+            battery: average_battery_percentage(ctx.batteries).map_or(-1.0, |p| p as f64),
+            charging: ctx.batteries.iter().any(|b| b.state == ChargeState::Charging),
+            now: Local::now(),
+        };
+        let result = script.render(&inputs);
+        if let Some(output) = script.output.take() {
+            self.messages.push(format!("Script '{}': {}", self.name, output));
+        }
+        match result {
+            Ok(frame) => PresetOutput::Frame(frame),
+            Err(e) => {
+                self.messages.push(format!("Script '{}' stopped at {}", self.name, e));
+                self.instance = None;
+                PresetOutput::Frame(blank)
+            }
+        }
+    }
+
+    fn default_fps(&self) -> f64 {
+        ANIMATED_FPS
+    }
+
+    fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.messages)
+    }
+}
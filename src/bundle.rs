@@ -0,0 +1,383 @@
+// src/bundle.rs
+//! Files for sharing custom presets. A bundle holds one or more 9x34
+//! images and comes in two encodings:
+//!
+//! JSON, version 1:
+//! `{"format": "led-preset-bundle", "version": 1, "width": 9, "height": 34,
+//!   "presets": [{"name": "smile", "image_data": [306 values, row by row]}]}`
+//! Presets may also carry `description`, `tags`, `favorite` and `created` /
+//! `modified` (Unix seconds); readers must accept them missing.
+//!
+//! Binary, version 1, all integers little endian:
+//! `"LEDP"`, version (u8), width (u8), height (u8), preset count (u16), then
+//! per preset: name length in bytes (u8), UTF-8 name, width * height
+//! brightness bytes row by row. It carries images only, no details.
+//!
+//! A single preset can also be a 9x34 grayscale PNG, the image format
+//! `inputmodule-control --image-gray` from inputmodule-rs displays. Imports
+//! also accept old custom_presets.json files.
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::api::decode_frame;
+use crate::presets::{ConflictPolicy, CustomPreset, PresetManager, MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Value of the JSON `format` field
+pub const BUNDLE_FORMAT: &str = "led-preset-bundle";
+
+/// Newest bundle version this build reads and the one it writes
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Start of a binary bundle
+const BINARY_MAGIC: &[u8; 4] = b"LEDP";
+
+/// Extension picking the binary encoding on export
+pub const BINARY_EXTENSION: &str = "ledpack";
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+#[derive(Serialize, Deserialize)]
+struct JsonBundle {
+    format: String,
+    version: u32,
+    width: usize,
+    height: usize,
+    presets: Vec<CustomPreset>,
+}
+
+/// File encodings, picked by extension on export
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BundleFormat {
+    Json,
+    Binary,
+    /// A single preset as an image
+    Png,
+}
+
+impl BundleFormat {
+    /// `.ledpack` is binary, `.png` an image and anything else JSON
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some(BINARY_EXTENSION) => BundleFormat::Binary,
+            Some("png") => BundleFormat::Png,
+            _ => BundleFormat::Json,
+        }
+    }
+}
+
+pub fn encode(presets: &[CustomPreset], format: BundleFormat) -> Result<Vec<u8>, String> {
+    match format {
+        BundleFormat::Json => {
+            let bundle = JsonBundle {
+                format: BUNDLE_FORMAT.to_string(),
+                version: BUNDLE_VERSION,
+                width: MATRIX_WIDTH,
+                height: MATRIX_HEIGHT,
+                presets: presets.to_vec(),
+            };
+            serde_json::to_vec_pretty(&bundle).map_err(|e| e.to_string())
+        }
+        BundleFormat::Binary => encode_binary(presets),
+        BundleFormat::Png => match presets {
+            [preset] => encode_png(&preset.image_data),
+            _ => Err(format!("a PNG holds one preset, not {}", presets.len())),
+        },
+    }
+}
+
+fn encode_binary(presets: &[CustomPreset]) -> Result<Vec<u8>, String> {
+    let count = u16::try_from(presets.len()).map_err(|_| "too many presets for one bundle".to_string())?;
+    let mut bytes = BINARY_MAGIC.to_vec();
+    bytes.extend([BUNDLE_VERSION as u8, MATRIX_WIDTH as u8, MATRIX_HEIGHT as u8]);
+    bytes.extend(count.to_le_bytes());
+    for preset in presets {
+        let name = preset.name.as_bytes();
+        let length = u8::try_from(name.len()).map_err(|_| format!("preset name '{}' is too long", preset.name))?;
+        // Readers find each name by counting whole images, so a short one
+        // would garble everything after it
+        if preset.image_data.len() != MATRIX_WIDTH * MATRIX_HEIGHT {
+            return Err(format!(
+                "preset '{}' has {} pixels, expected {}",
+                preset.name,
+                preset.image_data.len(),
+                MATRIX_WIDTH * MATRIX_HEIGHT
+            ));
+        }
+        bytes.push(length);
+        bytes.extend(name);
+        bytes.extend(&preset.image_data);
+    }
+    Ok(bytes)
+}
+
+fn encode_png(image_data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, MATRIX_WIDTH as u32, MATRIX_HEIGHT as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(image_data).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+/// Presets in a file of any supported kind, recognised by its contents.
+/// A PNG becomes one preset named `name`
+pub fn decode(bytes: &[u8], name: &str) -> Result<Vec<CustomPreset>, String> {
+    if bytes.starts_with(BINARY_MAGIC) {
+        decode_binary(bytes)
+    } else if bytes.starts_with(&PNG_SIGNATURE) {
+        let image_data = decode_frame(Some("image/png"), bytes)?;
+        Ok(vec![CustomPreset {
+            name: name.to_string(),
+            image_data,
+            ..CustomPreset::default()
+        }])
+    } else {
+        decode_json(bytes)
+    }
+}
+
+fn decode_json(bytes: &[u8]) -> Result<Vec<CustomPreset>, String> {
+    let value: serde_json::Value = serde_json::from_slice(bytes).map_err(|e| format!("not a preset file: {}", e))?;
+    if value.get("format").is_none() {
+        // custom_presets.json from before bundles existed
+        let manager: PresetManager = serde_json::from_value(value).map_err(|e| format!("not a preset file: {}", e))?;
+        let mut presets: Vec<CustomPreset> = manager.presets.into_values().collect();
+        presets.sort_by(|a, b| a.name.cmp(&b.name));
+        return Ok(presets);
+    }
+
+    let bundle: JsonBundle = serde_json::from_value(value).map_err(|e| format!("invalid bundle: {}", e))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(format!("unknown format '{}'", bundle.format));
+    }
+    check_header(bundle.version, bundle.width, bundle.height)?;
+    Ok(bundle.presets)
+}
+
+fn decode_binary(bytes: &[u8]) -> Result<Vec<CustomPreset>, String> {
+    let mut rest = &bytes[BINARY_MAGIC.len()..];
+    let mut take = |n: usize| -> Result<&[u8], String> {
+        if rest.len() < n {
+            return Err("bundle is truncated".to_string());
+        }
+        let (head, tail) = rest.split_at(n);
+        rest = tail;
+        Ok(head)
+    };
+
+    let header = take(5)?;
+    let (width, height) = (header[1] as usize, header[2] as usize);
+    check_header(header[0] as u32, width, height)?;
+    let count = u16::from_le_bytes([header[3], header[4]]);
+
+    let mut presets = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let length = take(1)?[0] as usize;
+        let name = String::from_utf8(take(length)?.to_vec()).map_err(|_| "preset name is not UTF-8".to_string())?;
+        let image_data = take(width * height)?.to_vec();
+        presets.push(CustomPreset {
+            name,
+            image_data,
+            ..CustomPreset::default()
+        });
+    }
+    Ok(presets)
+}
+
+fn check_header(version: u32, width: usize, height: usize) -> Result<(), String> {
+    if version == 0 || version > BUNDLE_VERSION {
+        return Err(format!("bundle version {} is not supported (newest is {})", version, BUNDLE_VERSION));
+    }
+    if (width, height) != (MATRIX_WIDTH, MATRIX_HEIGHT) {
+        return Err(format!("bundle is for {}x{} images, not {}x{}", width, height, MATRIX_WIDTH, MATRIX_HEIGHT));
+    }
+    Ok(())
+}
+
+/// Write the named presets, or all of them in the user's order when `names`
+/// is empty, to `path` in the format its extension asks for; returns how
+/// many were written
+pub fn export_file(manager: &PresetManager, names: &[String], path: &Path) -> Result<usize, String> {
+    let presets: Vec<CustomPreset> = if names.is_empty() {
+        manager.ordered().into_iter().cloned().collect()
+    } else {
+        names
+            .iter()
+            .map(|name| manager.presets.get(name).cloned().ok_or_else(|| format!("no preset '{}'", name)))
+            .collect::<Result<_, _>>()?
+    };
+    if presets.is_empty() {
+        return Err("there are no presets to export".to_string());
+    }
+
+    let bytes = encode(&presets, BundleFormat::for_path(path))?;
+    fs::write(path, bytes).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    Ok(presets.len())
+}
+
+/// Read presets from `path` into the library
+pub fn import_file(manager: &mut PresetManager, path: &Path, policy: ConflictPolicy) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("imported");
+    let presets = decode(&bytes, name)?;
+    manager.import(presets, policy).map(|summary| summary.to_string())
+}
+
+/// Whether command line arguments are a preset file command, which runs on
+/// the files directly instead of going to the app
+pub fn is_file_command(args: &[String]) -> bool {
+    matches!(
+        args.iter().map(String::as_str).collect::<Vec<_>>().as_slice(),
+        ["presets", "import" | "export", ..]
+    )
+}
+
+/// The preset library; a damaged one is left for the app to recover rather
+/// than replaced
+fn load_library() -> Result<PresetManager, String> {
+    PresetManager::load_from_file().map_err(|e| format!("{}; open the app to restore it from a backup", e))
+}
+
+/// Run `presets export <file> [name...]` or
+/// `presets import <file> [rename|skip|overwrite]` against custom_presets.json
+pub fn run_file_command(args: &[String]) -> Result<String, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["presets", "export", path, names @ ..] => {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            let count = export_file(&load_library()?, &names, Path::new(path))?;
+            Ok(format!("Exported {} preset(s) to {}", count, path))
+        }
+        ["presets", "import", path, rest @ ..] => {
+            let policy = match rest {
+                [] => ConflictPolicy::Rename,
+                [name] => ConflictPolicy::parse(name).ok_or_else(|| format!("unknown conflict policy '{}'", name))?,
+                _ => return Err("usage: presets import <file> [rename|skip|overwrite]".to_string()),
+            };
+            let mut manager = load_library()?;
+            let summary = import_file(&mut manager, Path::new(path), policy)?;
+            Ok(format!("Imported from {}: {}", path, summary))
+        }
+        _ => Err("usage: presets export <file> [name...] | presets import <file> [rename|skip|overwrite]".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, brightness: u8) -> CustomPreset {
+        CustomPreset {
+            name: name.to_string(),
+            image_data: (0..MATRIX_WIDTH * MATRIX_HEIGHT).map(|i| (i as u8).wrapping_add(brightness)).collect(),
+            ..CustomPreset::default()
+        }
+    }
+
+    fn images(presets: &[CustomPreset]) -> Vec<(String, Vec<u8>)> {
+        presets.iter().map(|p| (p.name.clone(), p.image_data.clone())).collect()
+    }
+
+    #[test]
+    fn json_round_trip_keeps_details() {
+        let mut smile = preset("smile", 1);
+        smile.description = "a smile".to_string();
+        smile.tags = vec!["faces".to_string()];
+        smile.favorite = true;
+        let presets = vec![smile, preset("frown", 2)];
+
+        let decoded = decode(&encode(&presets, BundleFormat::Json).unwrap(), "unused").unwrap();
+        assert_eq!(images(&decoded), images(&presets));
+        assert_eq!(decoded[0].description, "a smile");
+        assert_eq!(decoded[0].tags, ["faces"]);
+        assert!(decoded[0].favorite);
+    }
+
+    #[test]
+    fn binary_round_trip_keeps_images() {
+        let presets = vec![preset("smile", 1), preset("ünïcode", 2)];
+        let bytes = encode(&presets, BundleFormat::Binary).unwrap();
+        assert!(bytes.starts_with(BINARY_MAGIC));
+        assert_eq!(images(&decode(&bytes, "unused").unwrap()), images(&presets));
+    }
+
+    #[test]
+    fn binary_refuses_wrong_sized_images() {
+        let mut short = preset("short", 0);
+        short.image_data.truncate(10);
+        let error = encode(&[preset("fine", 0), short], BundleFormat::Binary).unwrap_err();
+        assert!(error.contains("short"), "{}", error);
+    }
+
+    #[test]
+    fn truncated_binary_is_an_error() {
+        let bytes = encode(&[preset("smile", 1)], BundleFormat::Binary).unwrap();
+        for cut in [BINARY_MAGIC.len() + 2, BINARY_MAGIC.len() + 6, bytes.len() - 1] {
+            assert_eq!(decode(&bytes[..cut], "unused").unwrap_err(), "bundle is truncated", "cut at {}", cut);
+        }
+    }
+
+    #[test]
+    fn unsupported_headers_are_refused() {
+        let bytes = encode(&[preset("smile", 1)], BundleFormat::Binary).unwrap();
+        let header = BINARY_MAGIC.len();
+
+        let mut newer = bytes.clone();
+        newer[header] = BUNDLE_VERSION as u8 + 1;
+        assert!(decode(&newer, "unused").unwrap_err().contains("version"));
+
+        let mut smaller = bytes.clone();
+        smaller[header + 1] = 8;
+        assert!(decode(&smaller, "unused").unwrap_err().contains("8x34"));
+
+        let json = serde_json::json!({
+            "format": BUNDLE_FORMAT,
+            "version": BUNDLE_VERSION + 1,
+            "width": MATRIX_WIDTH,
+            "height": MATRIX_HEIGHT,
+            "presets": [],
+        });
+        assert!(decode(json.to_string().as_bytes(), "unused").unwrap_err().contains("version"));
+
+        let json = serde_json::json!({
+            "format": BUNDLE_FORMAT,
+            "version": BUNDLE_VERSION,
+            "width": MATRIX_WIDTH,
+            "height": 17,
+            "presets": [],
+        });
+        assert!(decode(json.to_string().as_bytes(), "unused").unwrap_err().contains("9x17"));
+    }
+
+    #[test]
+    fn legacy_preset_file_imports() {
+        let image: Vec<u8> = vec![7; MATRIX_WIDTH * MATRIX_HEIGHT];
+        let legacy = serde_json::json!({
+            "presets": {
+                "zigzag": {"name": "zigzag", "image_data": image},
+                "arrow": {"name": "arrow", "image_data": image},
+            }
+        });
+        let decoded = decode(legacy.to_string().as_bytes(), "unused").unwrap();
+        assert_eq!(
+            images(&decoded),
+            [("arrow".to_string(), image.clone()), ("zigzag".to_string(), image)]
+        );
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let presets = vec![preset("smile", 3)];
+        let bytes = encode(&presets, BundleFormat::Png).unwrap();
+        assert!(bytes.starts_with(&PNG_SIGNATURE));
+
+        let decoded = decode(&bytes, "from_file").unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].name, "from_file");
+        assert_eq!(decoded[0].image_data, presets[0].image_data);
+        assert!(encode(&[preset("a", 0), preset("b", 0)], BundleFormat::Png).is_err());
+    }
+}
@@ -18,3 +18,39 @@ pub fn brightness(level: u8) -> Vec<u8> {
 pub fn percentage(percent: u8) -> Vec<u8> {
     vec![MAGIC1, MAGIC2, 0x01, 0x00, percent]
 }
+
+pub fn sleep(on: bool) -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, CMD_SLEEP, on as u8]
+}
+
+pub fn pattern_percentage(value: u8) -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x00, value]
+}
+
+pub fn pattern_gradient() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x01]
+}
+
+pub fn pattern_double_gradient() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x02]
+}
+
+pub fn pattern_lotus_horizontal() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x03]
+}
+
+pub fn pattern_zigzag() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x04]
+}
+
+pub fn pattern_full_brightness() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x05]
+}
+
+pub fn pattern_panic() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x06]
+}
+
+pub fn pattern_lotus_vertical() -> Vec<u8> {
+    vec![MAGIC1, MAGIC2, 0x01, 0x07]
+}
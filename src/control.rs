@@ -0,0 +1,203 @@
+// src/control.rs
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::timer::TimerCommand;
+
+/// Localhost address the running app listens on for control commands
+pub const CONTROL_ADDR: &str = "127.0.0.1:7878";
+
+/// A command accepted from the command line or the control socket
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlCommand {
+    Timer(TimerCommand),
+    /// Re-read custom_presets.json, e.g. after `presets import`
+    ReloadPresets,
+}
+
+impl ControlCommand {
+    /// Parse a whitespace separated command line, e.g. `timer countdown 25m`
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["timer", args @ ..] => Ok(ControlCommand::Timer(TimerCommand::parse(args)?)),
+            ["presets", "reload"] => Ok(ControlCommand::ReloadPresets),
+            _ => Err(format!("unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+/// How long either end waits on a quiet connection before giving up
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections handled at once, each on its own thread; more are closed
+const MAX_CONNECTIONS: usize = 8;
+
+/// Start listening for control commands on a background thread
+/// Each connection sends one command per line and gets `ok` or `error: ...`
+/// back; `notify` is called after every accepted command so the GUI can wake up
+pub fn spawn_listener<F>(notify: F) -> std::io::Result<Receiver<ControlCommand>>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    Ok(serve(TcpListener::bind(CONTROL_ADDR)?, notify))
+}
+
+fn serve<F>(listener: TcpListener, notify: F) -> Receiver<ControlCommand>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let notify = Arc::new(notify);
+    let active = Arc::new(AtomicUsize::new(0));
+    let closed = Arc::new(AtomicBool::new(false));
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // Stop once the app has dropped the receiving end
+            if closed.load(Ordering::Relaxed) {
+                break;
+            }
+            if active.load(Ordering::Relaxed) >= MAX_CONNECTIONS {
+                continue;
+            }
+            // A slow client only holds up its own thread
+            active.fetch_add(1, Ordering::Relaxed);
+            let (tx, notify, active, closed) =
+                (tx.clone(), notify.clone(), active.clone(), closed.clone());
+            thread::spawn(move || {
+                if let Err(mpsc::SendError(_)) = handle_connection(stream, &tx, &*notify) {
+                    closed.store(true, Ordering::Relaxed);
+                }
+                active.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+    });
+
+    rx
+}
+
+/// Whether a line is the start of an HTTP request, e.g. a web page trying
+/// to reach the control socket through the browser
+fn is_http_request_line(line: &str) -> bool {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    matches!(words.as_slice(), [_, _, version] if version.starts_with("HTTP/"))
+}
+
+/// Read commands from one connection until it closes
+/// I/O errors just end the connection; a send error means the app has gone
+fn handle_connection<F: Fn()>(
+    stream: TcpStream,
+    tx: &Sender<ControlCommand>,
+    notify: &F,
+) -> Result<(), mpsc::SendError<ControlCommand>> {
+    if stream.set_read_timeout(Some(IO_TIMEOUT)).is_err()
+        || stream.set_write_timeout(Some(IO_TIMEOUT)).is_err()
+    {
+        return Ok(());
+    }
+    let Ok(mut writer) = stream.try_clone() else {
+        return Ok(());
+    };
+    let mut first = true;
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Browsers can reach localhost too; never act on what they send
+        if first && is_http_request_line(&line) {
+            break;
+        }
+        first = false;
+        let reply = match ControlCommand::parse(&line) {
+            Ok(command) => {
+                tx.send(command)?;
+                notify();
+                "ok".to_string()
+            }
+            Err(e) => format!("error: {}", e),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Send a command line to an already running instance and return its reply
+pub fn send_to_running(line: &str) -> std::io::Result<String> {
+    send_to(CONTROL_ADDR.parse().expect("valid control address"), line)
+}
+
+fn send_to(addr: SocketAddr, line: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_millis(200))?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    writeln!(stream, "{}", line)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn listen() -> (SocketAddr, Receiver<ControlCommand>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        (addr, serve(listener, || {}))
+    }
+
+    #[test]
+    fn commands_are_answered() {
+        let (addr, rx) = listen();
+        assert_eq!(send_to(addr, "presets reload").unwrap(), "ok");
+        assert_eq!(
+            rx.recv_timeout(IO_TIMEOUT).unwrap(),
+            ControlCommand::ReloadPresets
+        );
+        assert!(send_to(addr, "bogus").unwrap().starts_with("error:"));
+    }
+
+    #[test]
+    fn idle_connection_does_not_block_others() {
+        let (addr, rx) = listen();
+        let _idle = TcpStream::connect(addr).unwrap();
+        assert_eq!(send_to(addr, "presets reload").unwrap(), "ok");
+        assert_eq!(
+            rx.recv_timeout(IO_TIMEOUT).unwrap(),
+            ControlCommand::ReloadPresets
+        );
+    }
+
+    #[test]
+    fn http_requests_are_refused() {
+        let (addr, rx) = listen();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\npresets reload\n"
+        )
+        .unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert_eq!(reply, "");
+        assert!(rx.try_recv().is_err());
+
+        assert!(is_http_request_line("GET /index.html HTTP/1.0"));
+        assert!(!is_http_request_line("timer countdown 25m"));
+    }
+}
@@ -0,0 +1,160 @@
+// src/hwmon.rs
+use std::fs;
+use std::path::Path;
+
+use crate::stats::{FanReading, TemperatureReading};
+
+pub const HWMON_ROOT: &str = "/sys/class/hwmon";
+
+#[derive(Clone, Default, Debug)]
+pub struct HwmonSensors {
+    pub temperatures: Vec<TemperatureReading>,
+    pub fans: Vec<FanReading>,
+}
+
+/// Read every temperature and fan sensor under a hwmon class directory
+/// Takes the root as a parameter so it can be pointed at a fake sysfs tree
+pub fn read_sensors(root: &Path) -> HwmonSensors {
+    let mut sensors = HwmonSensors::default();
+    let Ok(entries) = fs::read_dir(root) else {
+        return sensors;
+    };
+
+    let mut devices: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    devices.sort();
+
+    for device in devices {
+        let chip = read_trimmed(&device.join("name")).unwrap_or_else(|| "hwmon".to_string());
+        let Ok(files) = fs::read_dir(&device) else {
+            continue;
+        };
+
+        let mut inputs: Vec<String> = files
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|name| name.ends_with("_input"))
+            .collect();
+        inputs.sort();
+
+        for input in inputs {
+            let prefix = input.trim_end_matches("_input");
+            let Some(value) = read_number(&device.join(&input)) else {
+                continue;
+            };
+            let label = read_trimmed(&device.join(format!("{}_label", prefix)))
+                .map(|l| format!("{} {}", chip, l))
+                .unwrap_or_else(|| format!("{} {}", chip, prefix));
+
+            if prefix.starts_with("temp") {
+                sensors.temperatures.push(TemperatureReading {
+                    label,
+                    // hwmon reports millidegrees Celsius
+                    celsius: value as f32 / 1000.0,
+                    critical: read_number(&device.join(format!("{}_crit", prefix)))
+                        .map(|c| c as f32 / 1000.0),
+                });
+            } else if prefix.starts_with("fan") {
+                sensors.fans.push(FanReading {
+                    label,
+                    rpm: value.max(0) as u32,
+                    max_rpm: read_number(&device.join(format!("{}_max", prefix)))
+                        .filter(|&max| max > 0)
+                        .map(|max| max as u32),
+                });
+            }
+        }
+    }
+
+    sensors
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_number(path: &Path) -> Option<i64> {
+    read_trimmed(path)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, value: &str) {
+        fs::write(dir.join(name), format!("{}\n", value)).unwrap();
+    }
+
+    fn fake_tree() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+
+        let cpu = root.path().join("hwmon0");
+        fs::create_dir(&cpu).unwrap();
+        write(&cpu, "name", "coretemp");
+        write(&cpu, "temp1_input", "52000");
+        write(&cpu, "temp1_label", "Package id 0");
+        write(&cpu, "temp1_crit", "100000");
+        write(&cpu, "temp2_input", "48500");
+
+        let ec = root.path().join("hwmon1");
+        fs::create_dir(&ec).unwrap();
+        write(&ec, "name", "cros_ec");
+        write(&ec, "fan1_input", "2400");
+        write(&ec, "fan1_max", "6000");
+        write(&ec, "fan2_input", "1800");
+        write(&ec, "fan2_label", "Rear");
+        write(&ec, "fan2_max", "0");
+
+        root
+    }
+
+    #[test]
+    fn reads_temperatures_with_labels_and_critical() {
+        let root = fake_tree();
+        let sensors = read_sensors(root.path());
+
+        assert_eq!(sensors.temperatures.len(), 2);
+        let package = &sensors.temperatures[0];
+        assert_eq!(package.label, "coretemp Package id 0");
+        assert_eq!(package.celsius, 52.0);
+        assert_eq!(package.critical, Some(100.0));
+        let unlabelled = &sensors.temperatures[1];
+        assert_eq!(unlabelled.label, "coretemp temp2");
+        assert_eq!(unlabelled.celsius, 48.5);
+        assert_eq!(unlabelled.critical, None);
+    }
+
+    #[test]
+    fn reads_fans_with_max_speed() {
+        let root = fake_tree();
+        let sensors = read_sensors(root.path());
+
+        assert_eq!(sensors.fans.len(), 2);
+        assert_eq!(sensors.fans[0].label, "cros_ec fan1");
+        assert_eq!(sensors.fans[0].rpm, 2400);
+        assert_eq!(sensors.fans[0].max_rpm, Some(6000));
+        // A zero maximum means the driver doesn't know it
+        assert_eq!(sensors.fans[1].label, "cros_ec Rear");
+        assert_eq!(sensors.fans[1].max_rpm, None);
+    }
+
+    #[test]
+    fn skips_unreadable_inputs_and_defaults_chip_name() {
+        let root = tempfile::tempdir().unwrap();
+        let device = root.path().join("hwmon3");
+        fs::create_dir(&device).unwrap();
+        write(&device, "temp1_input", "not a number");
+        write(&device, "fan1_input", "900");
+
+        let sensors = read_sensors(root.path());
+        assert!(sensors.temperatures.is_empty());
+        assert_eq!(sensors.fans[0].label, "hwmon fan1");
+    }
+
+    #[test]
+    fn missing_root_has_no_sensors() {
+        let root = tempfile::tempdir().unwrap();
+        let sensors = read_sensors(&root.path().join("absent"));
+        assert!(sensors.temperatures.is_empty());
+        assert!(sensors.fans.is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Something that can tell how long the user has been away from the keyboard
+/// and mouse. Returns `None` when it can't tell (e.g. not supported here)
+pub trait IdleSource: Send {
+    fn name(&self) -> &'static str;
+    fn idle_time(&mut self) -> Option<Duration>;
+}
+
+/// Uses the first source that gives an answer, remembering which one worked
+pub struct ChainedIdleSource {
+    sources: Vec<Box<dyn IdleSource>>,
+    active: Option<usize>,
+}
+
+impl ChainedIdleSource {
+    pub fn new(sources: Vec<Box<dyn IdleSource>>) -> Self {
+        Self { sources, active: None }
+    }
+}
+
+impl IdleSource for ChainedIdleSource {
+    fn name(&self) -> &'static str {
+        self.active.map(|i| self.sources[i].name()).unwrap_or("none")
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        if let Some(i) = self.active {
+            if let Some(idle) = self.sources[i].idle_time() {
+                return Some(idle);
+            }
+        }
+        for (i, source) in self.sources.iter_mut().enumerate() {
+            if let Some(idle) = source.idle_time() {
+                self.active = Some(i);
+                return Some(idle);
+            }
+        }
+        self.active = None;
+        None
+    }
+}
+
+/// X11 screensaver idle time through the `xprintidle` tool
+#[cfg(target_os = "linux")]
+pub struct XPrintIdleSource;
+
+#[cfg(target_os = "linux")]
+impl IdleSource for XPrintIdleSource {
+    fn name(&self) -> &'static str {
+        "X11 (xprintidle)"
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        let output = std::process::Command::new("xprintidle").output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let millis = String::from_utf8(output.stdout).ok()?.trim().parse().ok()?;
+        Some(Duration::from_millis(millis))
+    }
+}
+
+/// GNOME (X11 and Wayland) idle time from Mutter's IdleMonitor over D-Bus
+#[cfg(target_os = "linux")]
+pub struct MutterIdleSource;
+
+#[cfg(target_os = "linux")]
+impl IdleSource for MutterIdleSource {
+    fn name(&self) -> &'static str {
+        "GNOME IdleMonitor"
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        let output = std::process::Command::new("gdbus")
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.gnome.Mutter.IdleMonitor",
+                "--object-path",
+                "/org/gnome/Mutter/IdleMonitor/Core",
+                "--method",
+                "org.gnome.Mutter.IdleMonitor.GetIdletime",
+            ])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        // Reply looks like "(uint64 12345,)"
+        let text = String::from_utf8(output.stdout).ok()?;
+        let millis = text
+            .trim()
+            .trim_start_matches("(uint64 ")
+            .trim_end_matches(",)")
+            .parse()
+            .ok()?;
+        Some(Duration::from_millis(millis))
+    }
+}
+
+/// Heuristic for when no desktop idle API is available: watches the
+/// interrupt counters of input devices in `/proc/interrupts` and counts the
+/// time since they last moved
+#[cfg(target_os = "linux")]
+pub struct InterruptsIdleSource {
+    path: std::path::PathBuf,
+    last_count: Option<u64>,
+    last_activity: Instant,
+}
+
+#[cfg(target_os = "linux")]
+impl InterruptsIdleSource {
+    /// Interrupt line names that belong to keyboards, touchpads and mice
+    const INPUT_DEVICES: [&'static str; 3] = ["i8042", "hid", "PIXA"];
+
+    pub fn new() -> Self {
+        Self::with_path("/proc/interrupts")
+    }
+
+    /// Read counters from another file, e.g. a fake `/proc/interrupts`
+    pub fn with_path(path: impl Into<std::path::PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            last_count: None,
+            last_activity: Instant::now(),
+        }
+    }
+
+    fn input_interrupt_count(&self) -> Option<u64> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        let mut total = None;
+        for line in content.lines() {
+            if !Self::INPUT_DEVICES.iter().any(|device| line.contains(device)) {
+                continue;
+            }
+            // "  1:   123   456   IR-IO-APIC    1-edge      i8042"
+            let count: u64 = line
+                .split_whitespace()
+                .skip(1)
+                .map_while(|field| field.parse::<u64>().ok())
+                .sum();
+            *total.get_or_insert(0) += count;
+        }
+        total
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl IdleSource for InterruptsIdleSource {
+    fn name(&self) -> &'static str {
+        "input interrupts"
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        let count = self.input_interrupt_count()?;
+        if self.last_count != Some(count) {
+            self.last_count = Some(count);
+            self.last_activity = Instant::now();
+        }
+        Some(self.last_activity.elapsed())
+    }
+}
+
+/// Windows idle time from `GetLastInputInfo`
+#[cfg(target_os = "windows")]
+pub struct WindowsIdleSource;
+
+#[cfg(target_os = "windows")]
+impl IdleSource for WindowsIdleSource {
+    fn name(&self) -> &'static str {
+        "Windows last input"
+    }
+
+    fn idle_time(&mut self) -> Option<Duration> {
+        #[repr(C)]
+        struct LastInputInfo {
+            cb_size: u32,
+            dw_time: u32,
+        }
+
+        #[link(name = "user32")]
+        extern "system" {
+            fn GetLastInputInfo(plii: *mut LastInputInfo) -> i32;
+        }
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetTickCount() -> u32;
+        }
+
+        let mut info = LastInputInfo {
+            cb_size: std::mem::size_of::<LastInputInfo>() as u32,
+            dw_time: 0,
+        };
+        // SAFETY: `info` is a properly sized LASTINPUTINFO that outlives the call
+        let ok = unsafe { GetLastInputInfo(&mut info) } != 0;
+        if !ok {
+            return None;
+        }
+        // SAFETY: GetTickCount has no preconditions
+        let now = unsafe { GetTickCount() };
+        Some(Duration::from_millis(now.wrapping_sub(info.dw_time) as u64))
+    }
+}
+
+/// The best idle source for this platform
+pub fn default_idle_source() -> Box<dyn IdleSource> {
+    #[cfg(target_os = "linux")]
+    let sources: Vec<Box<dyn IdleSource>> = vec![
+        Box::new(XPrintIdleSource),
+        Box::new(MutterIdleSource),
+        Box::new(InterruptsIdleSource::new()),
+    ];
+    #[cfg(target_os = "windows")]
+    let sources: Vec<Box<dyn IdleSource>> = vec![Box::new(WindowsIdleSource)];
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    let sources: Vec<Box<dyn IdleSource>> = Vec::new();
+
+    Box::new(ChainedIdleSource::new(sources))
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct IdleSettings {
+    pub enabled: bool,
+    pub dim_after_mins: u64,
+    pub sleep_after_mins: u64,
+    pub dim_brightness: u8,
+}
+
+impl Default for IdleSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dim_after_mins: 5,
+            sleep_after_mins: 15,
+            dim_brightness: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IdleState {
+    Active,
+    Dimmed,
+    Asleep,
+}
+
+impl IdleState {
+    /// State for how long the user has been idle under the given settings
+    pub fn for_idle_time(idle: Duration, settings: &IdleSettings) -> Self {
+        if !settings.enabled {
+            return IdleState::Active;
+        }
+        if idle >= Duration::from_secs(settings.sleep_after_mins * 60) {
+            IdleState::Asleep
+        } else if idle >= Duration::from_secs(settings.dim_after_mins * 60) {
+            IdleState::Dimmed
+        } else {
+            IdleState::Active
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Answers with scripted idle times, then with `None`
+    struct FakeIdleSource {
+        name: &'static str,
+        answers: VecDeque<Option<Duration>>,
+    }
+
+    impl FakeIdleSource {
+        fn new(name: &'static str, answers: &[Option<u64>]) -> Box<Self> {
+            Box::new(Self {
+                name,
+                answers: answers.iter().map(|secs| secs.map(Duration::from_secs)).collect(),
+            })
+        }
+    }
+
+    impl IdleSource for FakeIdleSource {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn idle_time(&mut self) -> Option<Duration> {
+            self.answers.pop_front().flatten()
+        }
+    }
+
+    fn enabled() -> IdleSettings {
+        IdleSettings {
+            enabled: true,
+            dim_after_mins: 5,
+            sleep_after_mins: 15,
+            dim_brightness: 20,
+        }
+    }
+
+    #[test]
+    fn thresholds_pick_the_state() {
+        let settings = enabled();
+        let state = |mins: u64| IdleState::for_idle_time(Duration::from_secs(mins * 60), &settings);
+        assert_eq!(state(0), IdleState::Active);
+        assert_eq!(IdleState::for_idle_time(Duration::from_secs(299), &settings), IdleState::Active);
+        assert_eq!(state(5), IdleState::Dimmed);
+        assert_eq!(state(14), IdleState::Dimmed);
+        assert_eq!(state(15), IdleState::Asleep);
+    }
+
+    #[test]
+    fn disabled_settings_stay_active() {
+        let settings = IdleSettings::default();
+        assert_eq!(IdleState::for_idle_time(Duration::from_secs(24 * 3600), &settings), IdleState::Active);
+    }
+
+    #[test]
+    fn states_follow_the_source_through_idle_and_back() {
+        let settings = enabled();
+        let mut source = FakeIdleSource::new("fake", &[Some(10), Some(400), Some(1000), Some(2)]);
+        let states: Vec<IdleState> = (0..4)
+            .map(|_| IdleState::for_idle_time(source.idle_time().unwrap_or_default(), &settings))
+            .collect();
+        assert_eq!(
+            states,
+            [IdleState::Active, IdleState::Dimmed, IdleState::Asleep, IdleState::Active]
+        );
+    }
+
+    #[test]
+    fn unknown_idle_time_counts_as_active() {
+        let settings = enabled();
+        let mut source = FakeIdleSource::new("fake", &[None]);
+        let idle = source.idle_time();
+        assert_eq!(idle, None);
+        assert_eq!(IdleState::for_idle_time(idle.unwrap_or_default(), &settings), IdleState::Active);
+    }
+
+    #[test]
+    fn chain_falls_back_and_remembers_the_working_source() {
+        let mut chain = ChainedIdleSource::new(vec![
+            FakeIdleSource::new("first", &[None, Some(7)]),
+            FakeIdleSource::new("second", &[Some(3), Some(4)]),
+        ]);
+        assert_eq!(chain.name(), "none");
+
+        assert_eq!(chain.idle_time(), Some(Duration::from_secs(3)));
+        assert_eq!(chain.name(), "second");
+        // Asks the remembered source first
+        assert_eq!(chain.idle_time(), Some(Duration::from_secs(4)));
+        // Once it stops answering, the chain starts over from the top
+        assert_eq!(chain.idle_time(), Some(Duration::from_secs(7)));
+        assert_eq!(chain.name(), "first");
+        assert_eq!(chain.idle_time(), None);
+        assert_eq!(chain.name(), "none");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn interrupts_source_resets_on_input_activity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("interrupts");
+        let write = |keyboard: u64| {
+            let content = format!(
+                "           CPU0       CPU1\n  0:         20          0   IO-APIC    2-edge      timer\n  1:  {:>9}  {:>9}   IR-IO-APIC    1-edge      i8042\n",
+                keyboard, 1
+            );
+            std::fs::write(&path, content).unwrap();
+        };
+
+        write(100);
+        let mut source = InterruptsIdleSource::with_path(&path);
+        assert!(source.idle_time().is_some());
+
+        // Pretend the last key press was ten minutes ago, unless the
+        // monotonic clock started more recently than that
+        if let Some(earlier) = source.last_activity.checked_sub(Duration::from_secs(600)) {
+            source.last_activity = earlier;
+            assert!(source.idle_time().unwrap() >= Duration::from_secs(600), "no input since");
+        }
+
+        write(101);
+        assert!(source.idle_time().unwrap() < Duration::from_secs(1), "a key press ends the idle time");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn interrupts_source_needs_an_input_device() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("interrupts");
+        std::fs::write(&path, "  0:         20   IO-APIC    2-edge      timer\n").unwrap();
+        assert_eq!(InterruptsIdleSource::with_path(&path).idle_time(), None);
+    }
+}
@@ -3,24 +3,158 @@ mod commands;
 mod stats;
 mod presets;
 mod utils;
+mod alerts;
+mod settings;
+mod timer;
+mod control;
+mod schedule;
+mod playlist;
+mod transition;
+mod idle;
+mod notifications;
+mod api;
+mod mqtt;
+mod stream;
+mod audio;
+mod script;
+mod registry;
+mod params;
+mod builtins;
+mod animations;
+mod bundle;
+mod render;
+mod sampler;
+#[cfg(target_os = "linux")]
+mod power;
+#[cfg(target_os = "linux")]
+mod hwmon;
 
 use device::Device;
 use commands::*;
-use stats::Stats;
-use presets::{PresetManager, MATRIX_WIDTH, MATRIX_HEIGHT, image_data_to_command};
+use stats::{format_bytes, format_rate, DiskIoRate, DiskSpace, FanReading, NetworkRate, TemperatureReading};
+use presets::{ConflictPolicy, CustomPreset, PresetManager, MATRIX_WIDTH, MATRIX_HEIGHT, image_data_to_command};
 use utils::*;
+use alerts::{AlertAction, AlertEngine, AlertRule, AlertTarget, Comparison, Metric, MetricSnapshot};
+use settings::Settings;
+use timer::{PomodoroPhase, Timer, TimerCommand, TimerMode};
+use control::ControlCommand;
+use schedule::{ScheduleAction, ScheduleRule, DAY_LABELS};
+use playlist::{Playlist, PlaylistCursor, PlaylistEntry};
+use transition::{TransitionKind, TransitionSettings};
+use idle::IdleState;
+use api::{ApiCall, ApiRequest, ApiResponse, ApiServer, Side};
+use mqtt::{MqttClient, MqttEvent};
+use stream::StreamHub;
+use audio::{AudioSourceKind, AudioVisualizer};
+use script::{ScriptManager, SCRIPT_DIR, SCRIPT_EXTENSION};
+use registry::{PresetCategory, PresetEntry, PresetRegistry};
+use render::{RenderEvent, RenderInputs, RenderPlan, RenderThread};
+use sampler::{Sample, Sampler};
+use notifications::{AppFilterMode, Notification, NotificationBanner, NotificationStyle};
+#[cfg(target_os = "linux")]
+use power::{PowerEvent, ResumeRetry, SleepInhibitor};
+use std::sync::mpsc::Receiver;
 use std::{sync::Arc, sync::Mutex};
-use std::time::Instant;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{Local, NaiveDateTime, TimeZone};
 
 fn main() -> Result<(), eframe::Error> {
+    // Any arguments form a control command, e.g. `led_controller timer countdown 25m`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Preset import and export work on the files without starting the GUI
+    if bundle::is_file_command(&args) {
+        match bundle::run_file_command(&args) {
+            Ok(message) => {
+                println!("{}", message);
+                // A running instance picks up imported presets
+                let _ = control::send_to_running("presets reload");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    let startup_command = if args.is_empty() {
+        None
+    } else {
+        let line = args.join(" ");
+        let command = match ControlCommand::parse(&line) {
+            Ok(command) => command,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        };
+        // Hand the command to an already running instance if there is one
+        if let Ok(reply) = control::send_to_running(&line) {
+            println!("{}", reply);
+            return Ok(());
+        }
+        Some(command)
+    };
+
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "Framework LED Controller",
         options,
-        Box::new(|_cc| Box::<MyApp>::default()),
+        Box::new(move |cc| {
+            let mut app = MyApp::new(&cc.egui_ctx);
+            app.start_control_socket(cc.egui_ctx.clone());
+            app.start_power_monitor(cc.egui_ctx.clone());
+            if app.settings.api.enabled {
+                app.start_api_server(cc.egui_ctx.clone());
+            }
+            if app.settings.mqtt.enabled {
+                app.start_mqtt_client(cc.egui_ctx.clone());
+            }
+            if app.settings.notifications.enabled {
+                app.start_notification_monitor(cc.egui_ctx.clone());
+            }
+            if let Some(command) = startup_command {
+                app.handle_control_command(command);
+            }
+            let app = Arc::new(Mutex::new(app));
+            spawn_clock(app.clone(), cc.egui_ctx.clone());
+            Box::new(AppWindow(app))
+        }),
     )
 }
 
+/// How often metrics are sampled and the schedule, playlists, alerts and
+/// idle state are re-evaluated
+const TICK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Drive the app's periodic work from its own thread, so it keeps going
+/// while no side is connected and while the window is hidden or busy. The
+/// sample is taken without holding the app, then applied to it
+fn spawn_clock(app: Arc<Mutex<MyApp>>, ctx: egui::Context) {
+    thread::spawn(move || {
+        let mut sampler = Sampler::new();
+        loop {
+            let started = Instant::now();
+            let interface = app.lock().unwrap_or_else(|e| e.into_inner()).net_interface.clone();
+            let sample = sampler.sample(interface.as_deref());
+            app.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .update_metrics(sample, &Local::now().naive_local());
+            ctx.request_repaint();
+            thread::sleep(TICK_INTERVAL.saturating_sub(started.elapsed()));
+        }
+    });
+}
+
+/// The window eframe draws; the clock thread shares the app behind it
+struct AppWindow(Arc<Mutex<MyApp>>);
+
+impl eframe::App for AppWindow {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).update(ctx);
+    }
+}
+
 struct MyApp {
     left_port: String,
     right_port: String,
@@ -28,30 +162,135 @@ struct MyApp {
     right_connected: bool,
     left_device: Option<Arc<Mutex<Device>>>,
     right_device: Option<Arc<Mutex<Device>>>,
-    stats: Option<Arc<Mutex<Stats>>>,
     cpu_percent: u8,
     ram_percent: u8,
+    net_rx_percent: u8,
+    net_tx_percent: u8,
+    net_rate: NetworkRate,
+    net_interface: Option<String>,
+    net_interfaces: Vec<String>,
+    disk_space: Vec<DiskSpace>,
+    disk_io_rate: DiskIoRate,
+    disk_io_history: Vec<(u8, u8)>,
+    temperatures: Vec<TemperatureReading>,
+    fans: Vec<FanReading>,
+    temp_sensor: Option<String>,
+    fan_peak_rpm: u32,
+    batteries: Vec<BatteryStatus>,
+    presets: PresetRegistry,
     left_preset: String,
     right_preset: String,
     left_brightness: u8,
     right_brightness: u8,
     available_ports: Vec<String>,
-    last_update: Instant,
     idle_frame: u8,
     status_message: String,
     // Image editor fields
     editor_image: Vec<u8>,
     editor_brightness: u8,
     editor_preset_name: String,
-    preset_manager: PresetManager,
+    // Shared with the render threads; edits copy it if they still hold it
+    preset_manager: Arc<PresetManager>,
+    /// Why custom_presets.json couldn't be loaded, until the user recovers
+    preset_load_error: Option<String>,
     selected_custom_preset: Option<String>,
     show_editor: bool,
+    // Preset gallery: filters and the selected preset's details being edited
+    gallery_search: String,
+    gallery_favorites_only: bool,
+    gallery_tag: Option<String>,
+    detail_description: String,
+    detail_tags: String,
+    show_gallery: bool,
+    // Preset library import/export
+    library_path: String,
+    import_policy: ConflictPolicy,
+    show_library: bool,
+    // Alert rule fields
+    settings: Settings,
+    /// Why settings.json couldn't be loaded, until the user picks the defaults
+    settings_load_error: Option<String>,
+    alert_engine: AlertEngine,
+    left_alert: Option<usize>,
+    right_alert: Option<usize>,
+    // Last alert command sent to each side, so it's only resent on change
+    left_alert_shown: Option<Vec<u8>>,
+    right_alert_shown: Option<Vec<u8>>,
+    show_alerts: bool,
+    // Timer fields
+    timer: Timer,
+    show_timer: bool,
+    control_rx: Option<Receiver<ControlCommand>>,
+    // Schedule fields
+    left_schedule: ScheduleAction,
+    right_schedule: ScheduleAction,
+    left_asleep: bool,
+    right_asleep: bool,
+    show_schedule: bool,
+    // Playlist playback position per side
+    left_playlist_cursor: PlaylistCursor,
+    right_playlist_cursor: PlaylistCursor,
+    // Render threads, what they should show and what they are showing
+    left_render: RenderThread,
+    right_render: RenderThread,
+    left_plan: Option<RenderPlan>,
+    right_plan: Option<RenderPlan>,
+    left_showing: Option<String>,
+    right_showing: Option<String>,
+    // Timer the render threads were last sent
+    rendered_timer: Option<Timer>,
+    // Idle dimming fields
+    // Name of the idle source that last answered
+    idle_source: &'static str,
+    idle_time: Option<Duration>,
+    idle_state: IdleState,
+    show_idle: bool,
+    // Suspend and lid state
+    suspended: bool,
+    lid_closed: bool,
+    // Audio visualizer
+    audio: Option<AudioVisualizer>,
+    audio_error: Option<String>,
+    show_audio: bool,
+    // Script presets
+    scripts: ScriptManager,
+    show_scripts: bool,
+    // HTTP API
+    api_server: Option<ApiServer>,
+    stream_hub: Arc<StreamHub>,
+    left_api_frame: Option<Vec<u8>>,
+    right_api_frame: Option<Vec<u8>>,
+    show_api: bool,
+    // MQTT
+    mqtt_client: Option<MqttClient>,
+    mqtt_status: String,
+    show_mqtt: bool,
+    // Desktop notifications
+    notification_rx: Option<Receiver<Notification>>,
+    last_notification: Option<Notification>,
+    show_notifications: bool,
+    #[cfg(target_os = "linux")]
+    power_rx: Option<Receiver<PowerEvent>>,
+    #[cfg(target_os = "linux")]
+    sleep_inhibitor: SleepInhibitor,
+    // Sides (left, right) still to reopen after resume, and when to try next
+    #[cfg(target_os = "linux")]
+    resume_reconnect: Option<((bool, bool), ResumeRetry)>,
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
+impl MyApp {
+    /// The app with its render threads running; their events wake `ctx`
+    fn new(ctx: &egui::Context) -> Self {
         let available_ports = get_available_ports();
-        let preset_manager = PresetManager::load_from_file();
+        let (preset_manager, preset_load_error) = match PresetManager::load_from_file() {
+            Ok(manager) => (manager, None),
+            Err(e) => (PresetManager::damaged(presets::PRESET_FILE), Some(e)),
+        };
+        let (settings, settings_load_error) = match Settings::load_from_file() {
+            Ok(settings) => (settings, None),
+            Err(e) => (Settings::damaged(), Some(e)),
+        };
+        let scripts = ScriptManager::load();
         Self {
             left_port: available_ports.get(0).cloned().unwrap_or_default(),
             right_port: available_ports.get(1).cloned().unwrap_or_default(),
@@ -59,29 +298,118 @@ impl Default for MyApp {
             right_connected: false,
             left_device: None,
             right_device: None,
-            stats: None,
             cpu_percent: 0,
             ram_percent: 0,
+            net_rx_percent: 0,
+            net_tx_percent: 0,
+            net_rate: NetworkRate::default(),
+            net_interface: None,
+            net_interfaces: Vec::new(),
+            disk_space: Vec::new(),
+            disk_io_rate: DiskIoRate::default(),
+            disk_io_history: Vec::new(),
+            temperatures: Vec::new(),
+            fans: Vec::new(),
+            temp_sensor: None,
+            fan_peak_rpm: 0,
+            batteries: Vec::new(),
+            presets: PresetRegistry::new(),
             left_preset: "idle".to_string(),
             right_preset: "idle".to_string(),
             left_brightness: 120,
             right_brightness: 120,
             available_ports,
-            last_update: Instant::now(),
             idle_frame: 0,
-            status_message: "Ready to connect".to_string(),
+            status_message: match &settings_load_error {
+                Some(e) => format!("Settings could not be loaded: {}", e),
+                None => "Ready to connect".to_string(),
+            },
             editor_image: vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT],
             editor_brightness: 255,
             editor_preset_name: String::new(),
-            preset_manager,
+            preset_manager: Arc::new(preset_manager),
+            preset_load_error,
             selected_custom_preset: None,
             show_editor: false,
+            gallery_search: String::new(),
+            gallery_favorites_only: false,
+            gallery_tag: None,
+            detail_description: String::new(),
+            detail_tags: String::new(),
+            show_gallery: false,
+            library_path: "presets.json".to_string(),
+            import_policy: ConflictPolicy::Rename,
+            show_library: false,
+            settings,
+            settings_load_error,
+            alert_engine: AlertEngine::new(),
+            left_alert: None,
+            right_alert: None,
+            left_alert_shown: None,
+            right_alert_shown: None,
+            show_alerts: false,
+            timer: Timer::new(),
+            show_timer: false,
+            control_rx: None,
+            left_schedule: ScheduleAction::Keep,
+            right_schedule: ScheduleAction::Keep,
+            left_asleep: false,
+            right_asleep: false,
+            show_schedule: false,
+            left_playlist_cursor: PlaylistCursor::new(),
+            right_playlist_cursor: PlaylistCursor::new(),
+            left_render: RenderThread::spawn(Side::Left, scripts.clone(), {
+                let ctx = ctx.clone();
+                move || ctx.request_repaint()
+            }),
+            right_render: RenderThread::spawn(Side::Right, scripts.clone(), {
+                let ctx = ctx.clone();
+                move || ctx.request_repaint()
+            }),
+            left_plan: None,
+            right_plan: None,
+            left_showing: None,
+            right_showing: None,
+            rendered_timer: None,
+            idle_source: "none",
+            idle_time: None,
+            idle_state: IdleState::Active,
+            show_idle: false,
+            suspended: false,
+            lid_closed: false,
+            audio: None,
+            audio_error: None,
+            show_audio: false,
+            scripts,
+            show_scripts: false,
+            api_server: None,
+            stream_hub: StreamHub::new(),
+            left_api_frame: None,
+            right_api_frame: None,
+            show_api: false,
+            mqtt_client: None,
+            mqtt_status: "Not connected".to_string(),
+            show_mqtt: false,
+            notification_rx: None,
+            last_notification: None,
+            show_notifications: false,
+            #[cfg(target_os = "linux")]
+            power_rx: None,
+            #[cfg(target_os = "linux")]
+            sleep_inhibitor: SleepInhibitor::new(),
+            #[cfg(target_os = "linux")]
+            resume_reconnect: None,
         }
     }
 }
 
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+impl MyApp {
+    fn update(&mut self, ctx: &egui::Context) {
+        self.process_events();
+        if self.timer.is_running() {
+            ctx.request_repaint_after(Duration::from_millis(250));
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Framework LED Controller");
             
@@ -103,6 +431,12 @@ impl eframe::App for MyApp {
             });
             
             ui.label(&self.status_message);
+            if self.preset_load_error.is_some() {
+                self.show_preset_recovery(ui);
+            }
+            if self.settings_load_error.is_some() {
+                self.show_settings_recovery(ui);
+            }
             ui.separator();
             
             // Main scrollable area with left and right columns
@@ -147,33 +481,52 @@ impl eframe::App for MyApp {
                                 ui.label(format!("Level: {}", self.left_brightness));
                                 
                                 ui.label("Preset:");
-                                egui::ComboBox::from_id_source("left_preset")
-                                    .selected_text(&self.left_preset)
-                                    .show_ui(ui, |ui| {
-                                        ui.selectable_value(&mut self.left_preset, "idle".to_string(), "Idle Animation");
-                                        ui.separator();
-                                        ui.label("📊 System Metrics");
-                                        ui.selectable_value(&mut self.left_preset, "cpu".to_string(), "  CPU Usage");
-                                        ui.selectable_value(&mut self.left_preset, "ram".to_string(), "  RAM Usage");
-                                        ui.separator();
-                                        ui.label("⏰ Display");
-                                        ui.selectable_value(&mut self.left_preset, "clock".to_string(), "  Clock");
-                                        ui.selectable_value(&mut self.left_preset, "battery".to_string(), "  Battery");
-                                        ui.separator();
-                                        ui.label("🎨 Patterns");
-                                        ui.selectable_value(&mut self.left_preset, "gradient".to_string(), "  Gradient");
-                                        ui.selectable_value(&mut self.left_preset, "double_gradient".to_string(), "  Double Gradient");
-                                        ui.selectable_value(&mut self.left_preset, "zigzag".to_string(), "  ZigZag");
-                                        ui.selectable_value(&mut self.left_preset, "lotus_h".to_string(), "  LOTUS Horiz");
-                                        ui.selectable_value(&mut self.left_preset, "lotus_v".to_string(), "  LOTUS Vert");
-                                        ui.selectable_value(&mut self.left_preset, "full_brightness".to_string(), "  Full Bright");
-                                        ui.selectable_value(&mut self.left_preset, "panic".to_string(), "  ⚠️ PANIC");
-                                        ui.separator();
-                                        ui.label("🖼️ Custom Presets");
-                                        for preset_name in self.preset_manager.list_presets() {
-                                            ui.selectable_value(&mut self.left_preset, preset_name.clone(), format!("  {}", preset_name));
-                                        }
-                                    });
+                                let entries = self.presets.entries(&self.preset_manager, &self.scripts);
+                                preset_picker(ui, "left_preset", &mut self.left_preset, &entries);
+
+                                if self.left_schedule != ScheduleAction::Keep {
+                                    ui.label(format!("📅 Scheduled: {}", self.left_schedule.label()));
+                                }
+
+                                if let Some(playing) = self.left_playlist_cursor.peek(&self.settings.left_playlist) {
+                                    ui.label(format!("▶ Playlist: {}", playing.preset));
+                                }
+                                ui.collapsing("Playlist", |ui| {
+                                    let options = self.preset_options();
+                                    if playlist_editor(ui, "left_playlist", &mut self.settings.left_playlist, &options) {
+                                        self.left_playlist_cursor.restart();
+                                    }
+                                    if ui.button("Save Playlist").clicked() {
+                                        self.status_message = match self.settings.save_to_file() {
+                                            Ok(_) => "Playlist saved".to_string(),
+                                            Err(e) => format!("Error saving playlist: {}", e),
+                                        };
+                                    }
+                                });
+                                ui.collapsing("Transition", |ui| {
+                                    transition_editor(ui, "left_transition", &mut self.settings.left_transition);
+                                    if ui.button("Save Transition").clicked() {
+                                        self.status_message = match self.settings.save_to_file() {
+                                            Ok(_) => "Transition saved".to_string(),
+                                            Err(e) => format!("Error saving transition: {}", e),
+                                        };
+                                    }
+                                });
+
+                                ui.push_id("left_preset_settings", |ui| {
+                                    self.presets.settings_ui(
+                                        &self.left_preset,
+                                        &self.preset_manager,
+                                        &mut self.settings.left_preset_params,
+                                        ui,
+                                    )
+                                });
+                                if ui.button("Save Preset Options").clicked() {
+                                    self.status_message = match self.settings.save_to_file() {
+                                        Ok(_) => "Preset options saved".to_string(),
+                                        Err(e) => format!("Error saving preset options: {}", e),
+                                    };
+                                }
                             }
                         });
                     });
@@ -219,33 +572,52 @@ impl eframe::App for MyApp {
                                 ui.label(format!("Level: {}", self.right_brightness));
                                 
                                 ui.label("Preset:");
-                                egui::ComboBox::from_id_source("right_preset")
-                                    .selected_text(&self.right_preset)
-                                    .show_ui(ui, |ui| {
-                                        ui.selectable_value(&mut self.right_preset, "idle".to_string(), "Idle Animation");
-                                        ui.separator();
-                                        ui.label("📊 System Metrics");
-                                        ui.selectable_value(&mut self.right_preset, "cpu".to_string(), "  CPU Usage");
-                                        ui.selectable_value(&mut self.right_preset, "ram".to_string(), "  RAM Usage");
-                                        ui.separator();
-                                        ui.label("⏰ Display");
-                                        ui.selectable_value(&mut self.right_preset, "clock".to_string(), "  Clock");
-                                        ui.selectable_value(&mut self.right_preset, "battery".to_string(), "  Battery");
-                                        ui.separator();
-                                        ui.label("🎨 Patterns");
-                                        ui.selectable_value(&mut self.right_preset, "gradient".to_string(), "  Gradient");
-                                        ui.selectable_value(&mut self.right_preset, "double_gradient".to_string(), "  Double Gradient");
-                                        ui.selectable_value(&mut self.right_preset, "zigzag".to_string(), "  ZigZag");
-                                        ui.selectable_value(&mut self.right_preset, "lotus_h".to_string(), "  LOTUS Horiz");
-                                        ui.selectable_value(&mut self.right_preset, "lotus_v".to_string(), "  LOTUS Vert");
-                                        ui.selectable_value(&mut self.right_preset, "full_brightness".to_string(), "  Full Bright");
-                                        ui.selectable_value(&mut self.right_preset, "panic".to_string(), "  ⚠️ PANIC");
-                                        ui.separator();
-                                        ui.label("🖼️ Custom Presets");
-                                        for preset_name in self.preset_manager.list_presets() {
-                                            ui.selectable_value(&mut self.right_preset, preset_name.clone(), format!("  {}", preset_name));
-                                        }
-                                    });
+                                let entries = self.presets.entries(&self.preset_manager, &self.scripts);
+                                preset_picker(ui, "right_preset", &mut self.right_preset, &entries);
+
+                                if self.right_schedule != ScheduleAction::Keep {
+                                    ui.label(format!("📅 Scheduled: {}", self.right_schedule.label()));
+                                }
+
+                                if let Some(playing) = self.right_playlist_cursor.peek(&self.settings.right_playlist) {
+                                    ui.label(format!("▶ Playlist: {}", playing.preset));
+                                }
+                                ui.collapsing("Playlist", |ui| {
+                                    let options = self.preset_options();
+                                    if playlist_editor(ui, "right_playlist", &mut self.settings.right_playlist, &options) {
+                                        self.right_playlist_cursor.restart();
+                                    }
+                                    if ui.button("Save Playlist").clicked() {
+                                        self.status_message = match self.settings.save_to_file() {
+                                            Ok(_) => "Playlist saved".to_string(),
+                                            Err(e) => format!("Error saving playlist: {}", e),
+                                        };
+                                    }
+                                });
+                                ui.collapsing("Transition", |ui| {
+                                    transition_editor(ui, "right_transition", &mut self.settings.right_transition);
+                                    if ui.button("Save Transition").clicked() {
+                                        self.status_message = match self.settings.save_to_file() {
+                                            Ok(_) => "Transition saved".to_string(),
+                                            Err(e) => format!("Error saving transition: {}", e),
+                                        };
+                                    }
+                                });
+
+                                ui.push_id("right_preset_settings", |ui| {
+                                    self.presets.settings_ui(
+                                        &self.right_preset,
+                                        &self.preset_manager,
+                                        &mut self.settings.right_preset_params,
+                                        ui,
+                                    )
+                                });
+                                if ui.button("Save Preset Options").clicked() {
+                                    self.status_message = match self.settings.save_to_file() {
+                                        Ok(_) => "Preset options saved".to_string(),
+                                        Err(e) => format!("Error saving preset options: {}", e),
+                                    };
+                                }
                             }
                         });
                     });
@@ -320,19 +692,19 @@ impl eframe::App for MyApp {
                         ui.text_edit_singleline(&mut self.editor_preset_name);
                     });
 
-                    if ui.button("Save Preset").clicked() {
-                        if !self.editor_preset_name.is_empty() {
-                            match self.preset_manager.save_preset(
-                                self.editor_preset_name.clone(),
-                                self.editor_image.clone(),
-                            ) {
-                                Ok(_) => {
-                                    self.status_message = format!("Preset '{}' saved!", self.editor_preset_name);
-                                    self.editor_preset_name.clear();
-                                }
-                                Err(e) => {
-                                    self.status_message = format!("Error saving preset: {}", e);
-                                }
+                    // Saving over a library that failed to load would lose it
+                    let save = ui.add_enabled(self.preset_load_error.is_none(), egui::Button::new("Save Preset"));
+                    if save.clicked() && !self.editor_preset_name.is_empty() {
+                        match Arc::make_mut(&mut self.preset_manager).save_preset(
+                            self.editor_preset_name.clone(),
+                            self.editor_image.clone(),
+                        ) {
+                            Ok(_) => {
+                                self.status_message = format!("Preset '{}' saved!", self.editor_preset_name);
+                                self.editor_preset_name.clear();
+                            }
+                            Err(e) => {
+                                self.status_message = format!("Error saving preset: {}", e);
                             }
                         }
                     }
@@ -358,11 +730,16 @@ impl eframe::App for MyApp {
                         });
 
                     // Delete preset
-                    if let Some(preset_name) = &self.selected_custom_preset {
-                        if ui.button("Delete Preset").clicked() {
-                            let _ = self.preset_manager.delete_preset(preset_name);
-                            self.status_message = format!("Deleted preset '{}'", preset_name);
-                            self.selected_custom_preset = None;
+                    if let Some(preset_name) = self.selected_custom_preset.clone() {
+                        let delete = ui.add_enabled(self.preset_load_error.is_none(), egui::Button::new("Delete Preset"));
+                        if delete.clicked() {
+                            match Arc::make_mut(&mut self.preset_manager).delete_preset(&preset_name) {
+                                Ok(()) => {
+                                    self.status_message = format!("Deleted preset '{}'", preset_name);
+                                    self.selected_custom_preset = None;
+                                }
+                                Err(e) => self.status_message = format!("Error deleting preset: {}", e),
+                            }
                         }
                     }
 
@@ -387,6 +764,127 @@ impl eframe::App for MyApp {
                 }
             });
 
+            ui.group(|ui| {
+                ui.heading("🗂️ Preset Gallery");
+                if ui.button("Toggle Gallery").clicked() {
+                    self.show_gallery = !self.show_gallery;
+                }
+
+                if self.show_gallery {
+                    self.show_preset_gallery(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("📦 Preset Library");
+                if ui.button("Toggle Library").clicked() {
+                    self.show_library = !self.show_library;
+                }
+
+                if self.show_library {
+                    self.show_library_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("⏱️ Timer");
+                if ui.button("Toggle Timer").clicked() {
+                    self.show_timer = !self.show_timer;
+                }
+
+                if self.show_timer {
+                    self.show_timer_controls(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("📅 Schedule");
+                if ui.button("Toggle Schedule").clicked() {
+                    self.show_schedule = !self.show_schedule;
+                }
+
+                if self.show_schedule {
+                    self.show_schedule_rules(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("💤 Idle Dim & Sleep");
+                if ui.button("Toggle Idle Settings").clicked() {
+                    self.show_idle = !self.show_idle;
+                }
+
+                if self.show_idle {
+                    self.show_idle_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("🎵 Audio Visualizer");
+                if ui.button("Toggle Audio Visualizer").clicked() {
+                    self.show_audio = !self.show_audio;
+                }
+
+                if self.show_audio {
+                    self.show_audio_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("📜 Script Presets");
+                if ui.button("Toggle Scripts").clicked() {
+                    self.show_scripts = !self.show_scripts;
+                }
+
+                if self.show_scripts {
+                    self.show_script_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("🌐 HTTP API");
+                if ui.button("Toggle HTTP API").clicked() {
+                    self.show_api = !self.show_api;
+                }
+
+                if self.show_api {
+                    self.show_api_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("🏠 MQTT");
+                if ui.button("Toggle MQTT").clicked() {
+                    self.show_mqtt = !self.show_mqtt;
+                }
+
+                if self.show_mqtt {
+                    self.show_mqtt_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("🔔 Notifications");
+                if ui.button("Toggle Notifications").clicked() {
+                    self.show_notifications = !self.show_notifications;
+                }
+
+                if self.show_notifications {
+                    self.show_notification_settings(ui);
+                }
+            });
+
+            ui.group(|ui| {
+                ui.heading("🚨 Alert Rules");
+                if ui.button("Toggle Alert Rules").clicked() {
+                    self.show_alerts = !self.show_alerts;
+                }
+
+                if self.show_alerts {
+                    self.show_alert_rules(ui);
+                }
+            });
+
             ui.separator();
 
             if self.left_connected || self.right_connected {
@@ -407,18 +905,114 @@ impl eframe::App for MyApp {
                                 .text("RAM"),
                         );
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("Interface:");
+                        egui::ComboBox::from_id_source("net_interface")
+                            .selected_text(self.net_interface.clone().unwrap_or_else(|| "All".to_string()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.net_interface, None, "All");
+                                for interface in &self.net_interfaces {
+                                    ui.selectable_value(&mut self.net_interface, Some(interface.clone()), interface);
+                                }
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Upload: {}", format_rate(self.net_rate.tx_bytes_per_sec)));
+                        ui.add(
+                            egui::ProgressBar::new(self.net_tx_percent as f32 / 100.0)
+                                .text("UP"),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Download: {}", format_rate(self.net_rate.rx_bytes_per_sec)));
+                        ui.add(
+                            egui::ProgressBar::new(self.net_rx_percent as f32 / 100.0)
+                                .text("DOWN"),
+                        );
+                    });
+                    for disk in &self.disk_space {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "{} ({} free of {}): {}%",
+                                disk.mount_point,
+                                format_bytes(disk.available_bytes),
+                                format_bytes(disk.total_bytes),
+                                disk.usage_percent
+                            ));
+                            let bar = egui::ProgressBar::new(disk.usage_percent as f32 / 100.0);
+                            if disk.usage_percent >= DISK_WARNING_PERCENT {
+                                ui.add(bar.fill(egui::Color32::RED));
+                            } else {
+                                ui.add(bar);
+                            }
+                        });
+                    }
+                    ui.label(format!(
+                        "Disk I/O: read {} / write {}",
+                        format_rate(self.disk_io_rate.read_bytes_per_sec),
+                        format_rate(self.disk_io_rate.write_bytes_per_sec)
+                    ));
+                    ui.horizontal(|ui| {
+                        ui.label("Temperature sensor:");
+                        egui::ComboBox::from_id_source("temp_sensor")
+                            .selected_text(self.temp_sensor.clone().unwrap_or_else(|| "Hottest".to_string()))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.temp_sensor, None, "Hottest");
+                                for reading in &self.temperatures {
+                                    let text = match reading.critical {
+                                        Some(critical) => format!("{} (crit {:.0}°C)", reading.label, critical),
+                                        None => reading.label.clone(),
+                                    };
+                                    ui.selectable_value(&mut self.temp_sensor, Some(reading.label.clone()), text);
+                                }
+                            });
+                        ui.label(format!("{:.1}°C", self.selected_temperature()));
+                    });
+                    let temperature = &mut self.settings.temperature;
+                    ui.horizontal(|ui| {
+                        ui.label("Warning °C:");
+                        ui.add(egui::Slider::new(&mut temperature.warning, 30.0..=110.0));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Critical °C:");
+                        ui.add(egui::Slider::new(&mut temperature.critical, 30.0..=110.0));
+                    });
+                    if temperature.critical < temperature.warning {
+                        temperature.critical = temperature.warning;
+                    }
+                    if ui.button("Save Thresholds").clicked() {
+                        self.status_message = match self.settings.save_to_file() {
+                            Ok(_) => "Temperature thresholds saved".to_string(),
+                            Err(e) => format!("Error saving temperature thresholds: {}", e),
+                        };
+                    }
+                    for fan in &self.fans {
+                        ui.label(format!("{}: {} RPM", fan.label, fan.rpm));
+                    }
+                    if self.batteries.is_empty() {
+                        ui.label("Battery: none detected");
+                    }
+                    for (i, battery) in self.batteries.iter().enumerate() {
+                        let state = match battery.state {
+                            ChargeState::Charging => "charging",
+                            ChargeState::Discharging => "discharging",
+                            ChargeState::Full => "full",
+                            ChargeState::Unknown => "unknown",
+                        };
+                        let mut text = format!("Battery {}: {:.0}% ({})", i + 1, battery.percent, state);
+                        if let Some(time) = battery.time_remaining {
+                            let minutes = time.as_secs() / 60;
+                            let direction = if battery.state == ChargeState::Charging { "to full" } else { "left" };
+                            text.push_str(&format!(", {}h {:02}m {}", minutes / 60, minutes % 60, direction));
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label(text);
+                            ui.add(egui::ProgressBar::new(battery.percent / 100.0));
+                        });
+                    }
                 });
 
                 ui.separator();
-
-                // Update metrics
-                if self.last_update.elapsed().as_millis() > 500 {
-                    self.update_metrics();
-                    self.last_update = Instant::now();
-                }
-
-                // Request repaint frequently
-                ctx.request_repaint();
             }
         });
     }
@@ -428,17 +1022,12 @@ impl MyApp {
     fn connect_left(&mut self) {
         match Device::connect(&self.left_port) {
             Ok(mut dev) => {
-                dev.send(brightness(self.left_brightness));
+                dev.send(brightness(self.effective_brightness(self.left_brightness)));
                 self.left_device = Some(Arc::new(Mutex::new(dev)));
+                self.left_render.set_device(self.left_device.clone());
                 self.left_connected = true;
+                self.left_asleep = false;
                 self.status_message = format!("Left connected to {}", self.left_port);
-
-                // Initialize stats if this is the first connection
-                if self.stats.is_none() {
-                    let mut stats = Stats::new();
-                    stats.refresh();
-                    self.stats = Some(Arc::new(Mutex::new(stats)));
-                }
             }
             Err(e) => {
                 self.status_message = format!("Failed to connect left: {}", e);
@@ -449,17 +1038,12 @@ impl MyApp {
     fn connect_right(&mut self) {
         match Device::connect(&self.right_port) {
             Ok(mut dev) => {
-                dev.send(brightness(self.right_brightness));
+                dev.send(brightness(self.effective_brightness(self.right_brightness)));
                 self.right_device = Some(Arc::new(Mutex::new(dev)));
+                self.right_render.set_device(self.right_device.clone());
                 self.right_connected = true;
+                self.right_asleep = false;
                 self.status_message = format!("Right connected to {}", self.right_port);
-
-                // Initialize stats if this is the first connection
-                if self.stats.is_none() {
-                    let mut stats = Stats::new();
-                    stats.refresh();
-                    self.stats = Some(Arc::new(Mutex::new(stats)));
-                }
             }
             Err(e) => {
                 self.status_message = format!("Failed to connect right: {}", e);
@@ -469,180 +1053,1632 @@ impl MyApp {
 
     fn disconnect_left(&mut self) {
         self.left_device = None;
+        self.left_render.set_device(None);
+        self.left_render.reset();
+        self.left_alert_shown = None;
         self.left_connected = false;
-        if !self.right_connected {
-            self.stats = None;
-        }
         self.status_message = "Left matrix disconnected".to_string();
     }
 
     fn disconnect_right(&mut self) {
         self.right_device = None;
+        self.right_render.set_device(None);
+        self.right_render.reset();
+        self.right_alert_shown = None;
         self.right_connected = false;
-        if !self.left_connected {
-            self.stats = None;
-        }
         self.status_message = "Right matrix disconnected".to_string();
     }
 
-    fn update_metrics(&mut self) {
+    /// Work through what the background services sent; the GUI does this
+    /// every frame and the clock on every tick, so nothing waits on the window
+    fn process_events(&mut self) {
+        self.process_control_commands();
+        self.process_power_events();
+        self.process_notifications();
+        self.process_api_calls();
+        self.process_mqtt_events();
+        self.stream_hub.set_devices(self.left_device.clone(), self.right_device.clone());
+        self.process_render_events();
+        self.ensure_audio();
+        self.sync_render_timer();
+        self.sync_render_plans();
+    }
+
+    /// Take in a tick's sample and work out what each side shows at `now`
+    fn update_metrics(&mut self, sample: Sample, now: &NaiveDateTime) {
+        self.process_events();
+        if self.timer.tick(Instant::now()) {
+            self.status_message = "Timer finished".to_string();
+        }
+
         // Increment animation frame
         self.idle_frame = self.idle_frame.wrapping_add(1);
-        
-        if let Some(stats_arc) = &self.stats {
-            if let Ok(mut stats) = stats_arc.lock() {
-                self.cpu_percent = stats.cpu_usage();
-                self.ram_percent = stats.ram_usage();
-
-                // Send commands to left device based on left preset
-                if let Some(left_dev) = &self.left_device {
-                    if let Ok(mut dev) = left_dev.lock() {
-                        let command = match self.left_preset.as_str() {
-                            "cpu" => pattern_percentage(self.cpu_percent),
-                            "ram" => pattern_percentage(self.ram_percent),
-                            "idle" => {
-                                let pattern = (self.idle_frame / 4) % 3;
-                                vec![MAGIC1, MAGIC2, 0x14, pattern]
-                            }
-                            "clock" => {
-                                let image_data = render_clock_display();
-                                image_data_to_command(&image_data)
-                            }
-                            "battery" => {
-                                let image_data = render_battery_display();
-                                image_data_to_command(&image_data)
-                            }
-                            "gradient" => pattern_gradient(),
-                            "double_gradient" => pattern_double_gradient(),
-                            "zigzag" => pattern_zigzag(),
-                            "lotus_h" => pattern_lotus_horizontal(),
-                            "lotus_v" => pattern_lotus_vertical(),
-                            "full_brightness" => pattern_full_brightness(),
-                            "panic" => pattern_panic(),
-                            _ => {
-                                // Check if it's a custom preset
-                                if let Some(image_data) = self.preset_manager.get_preset(&self.left_preset) {
-                                    image_data_to_command(&image_data)
-                                } else {
-                                    Vec::new()
-                                }
-                            }
-                        };
-                        
-                        if !command.is_empty() {
-                            dev.send(command);
-                        }
-                    }
-                }
 
-                // Send commands to right device based on right preset
-                if let Some(right_dev) = &self.right_device {
-                    if let Ok(mut dev) = right_dev.lock() {
-                        let command = match self.right_preset.as_str() {
-                            "cpu" => pattern_percentage(self.cpu_percent),
-                            "ram" => pattern_percentage(self.ram_percent),
-                            "idle" => {
-                                let pattern = (self.idle_frame / 4) % 3;
-                                vec![MAGIC1, MAGIC2, 0x14, pattern]
-                            }
-                            "clock" => {
-                                let image_data = render_clock_display();
-                                image_data_to_command(&image_data)
-                            }
-                            "battery" => {
-                                let image_data = render_battery_display();
-                                image_data_to_command(&image_data)
-                            }
-                            "gradient" => pattern_gradient(),
-                            "double_gradient" => pattern_double_gradient(),
-                            "zigzag" => pattern_zigzag(),
-                            "lotus_h" => pattern_lotus_horizontal(),
-                            "lotus_v" => pattern_lotus_vertical(),
-                            "full_brightness" => pattern_full_brightness(),
-                            "panic" => pattern_panic(),
-                            _ => {
-                                // Check if it's a custom preset
-                                if let Some(image_data) = self.preset_manager.get_preset(&self.right_preset) {
-                                    image_data_to_command(&image_data)
-                                } else {
-                                    Vec::new()
-                                }
-                            }
-                        };
-                        
-                        if !command.is_empty() {
-                            dev.send(command);
-                        }
-                    }
-                }
-            }
+        self.cpu_percent = sample.cpu_percent;
+        self.ram_percent = sample.ram_percent;
+        self.net_rx_percent = sample.net_rx_percent;
+        self.net_tx_percent = sample.net_tx_percent;
+        self.net_rate = sample.net_rate;
+        self.net_interfaces = sample.net_interfaces;
+        self.disk_space = sample.disk_space;
+        self.disk_io_rate = sample.disk_io_rate;
+        self.disk_io_history = sample.disk_io_history;
+        self.temperatures = sample.temperatures;
+        self.fans = sample.fans;
+        let max_rpm = self.fans.iter().map(|f| f.rpm).max().unwrap_or(0);
+        self.fan_peak_rpm = self.fan_peak_rpm.max(max_rpm);
+        self.batteries = sample.batteries;
+        self.lid_closed = sample.lid_closed;
+
+        self.evaluate_alerts();
+
+        self.idle_time = sample.idle_time;
+        self.idle_source = sample.idle_source;
+        let idle_state = IdleState::for_idle_time(self.idle_time.unwrap_or_default(), &self.settings.idle);
+        if idle_state != self.idle_state {
+            self.idle_state = idle_state;
+            self.send_left_brightness();
+            self.send_right_brightness();
         }
-    }
 
-    fn send_left_brightness(&mut self) {
+        let (left_schedule, right_schedule) = schedule::active_actions(&self.settings.schedules, now);
+        self.left_schedule = left_schedule;
+        self.right_schedule = right_schedule;
+
+        // The render threads draw each side's preset; a live stream owns the
+        // side, otherwise an active alert temporarily overrides it
+        let (left_preset, transition) = Self::select_preset(
+            &self.left_schedule,
+            &mut self.left_playlist_cursor,
+            &self.settings.left_playlist,
+            &self.left_preset,
+            self.settings.left_transition,
+        );
+        self.left_plan = Some(RenderPlan { preset: left_preset, transition });
+        let (right_preset, transition) = Self::select_preset(
+            &self.right_schedule,
+            &mut self.right_playlist_cursor,
+            &self.settings.right_playlist,
+            &self.right_preset,
+            self.settings.right_transition,
+        );
+        self.right_plan = Some(RenderPlan { preset: right_preset, transition });
+
         if let Some(left_dev) = &self.left_device {
             if let Ok(mut dev) = left_dev.lock() {
-                dev.send(brightness(self.left_brightness));
+                let asleep = self.left_schedule == ScheduleAction::Sleep || self.matrices_should_sleep();
+                if asleep != self.left_asleep {
+                    dev.send(sleep(asleep));
+                    self.left_asleep = asleep;
+                }
+
+                let alert = match self.left_alert.and_then(|i| self.settings.alert_rules.get(i)) {
+                    Some(rule) if !self.stream_hub.is_streaming(Side::Left) && !self.left_asleep => {
+                        Some(self.alert_command(&rule.action)).filter(|command| !command.is_empty())
+                    }
+                    _ => None,
+                };
+                if alert != self.left_alert_shown {
+                    if let Some(command) = &alert {
+                        dev.send(command.clone());
+                    }
+                    self.left_alert_shown = alert;
+                }
             }
         }
-    }
 
-    fn send_right_brightness(&mut self) {
         if let Some(right_dev) = &self.right_device {
             if let Ok(mut dev) = right_dev.lock() {
-                dev.send(brightness(self.right_brightness));
+                let asleep = self.right_schedule == ScheduleAction::Sleep || self.matrices_should_sleep();
+                if asleep != self.right_asleep {
+                    dev.send(sleep(asleep));
+                    self.right_asleep = asleep;
+                }
+
+                let alert = match self.right_alert.and_then(|i| self.settings.alert_rules.get(i)) {
+                    Some(rule) if !self.stream_hub.is_streaming(Side::Right) && !self.right_asleep => {
+                        Some(self.alert_command(&rule.action)).filter(|command| !command.is_empty())
+                    }
+                    _ => None,
+                };
+                if alert != self.right_alert_shown {
+                    if let Some(command) = &alert {
+                        dev.send(command.clone());
+                    }
+                    self.right_alert_shown = alert;
+                }
             }
         }
-    }
-}
-
-fn get_available_ports() -> Vec<String> {
-    serialport::available_ports()
-        .map(|ports| {
-            ports
-                .iter()
-                .filter_map(|p| match p {
-                    serialport::SerialPortInfo {
-                        port_name,
-                        port_type: _,
-                    } => Some(port_name.clone()),
-                })
-                .collect()
-        })
-        .unwrap_or_default()
-}
 
-// Command generators
-fn pattern_percentage(value: u8) -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x00, value]
-}
+        if let Some(client) = &self.mqtt_client {
+            client.set_state(self.mqtt_state());
+        }
+        self.send_render_inputs();
+        self.sync_render_plans();
+    }
 
-fn pattern_gradient() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x01]
-}
+    fn start_control_socket(&mut self, ctx: egui::Context) {
+        match control::spawn_listener(move || ctx.request_repaint()) {
+            Ok(rx) => self.control_rx = Some(rx),
+            Err(e) => self.status_message = format!("Control socket unavailable: {}", e),
+        }
+    }
 
-fn pattern_double_gradient() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x02]
-}
+    fn process_control_commands(&mut self) {
+        let Some(rx) = &self.control_rx else {
+            return;
+        };
+        let commands: Vec<ControlCommand> = rx.try_iter().collect();
+        for command in commands {
+            self.handle_control_command(command);
+        }
+    }
 
-fn pattern_lotus_horizontal() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x03]
-}
+    fn start_api_server(&mut self, ctx: egui::Context) {
+        // Drop any running server first; that waits until its port is free
+        self.api_server = None;
+        let api = &self.settings.api;
+        match api::spawn_server(api.port, self.stream_hub.clone(), api.stream, move || ctx.request_repaint()) {
+            Ok(server) => self.api_server = Some(server),
+            Err(e) => self.status_message = format!("HTTP API unavailable: {}", e),
+        }
+    }
 
-fn pattern_zigzag() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x04]
-}
+    fn process_api_calls(&mut self) {
+        let Some(server) = &self.api_server else {
+            return;
+        };
+        let calls: Vec<ApiCall> = server.rx.try_iter().collect();
+        for call in calls {
+            let response = self.handle_api_request(call.request.clone());
+            call.respond(response);
+        }
+    }
 
-fn pattern_full_brightness() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x05]
-}
+    fn handle_api_request(&mut self, request: ApiRequest) -> ApiResponse {
+        match request {
+            ApiRequest::ListDevices => ApiResponse::ok(serde_json::json!([
+                {
+                    "side": "left",
+                    "connected": self.left_connected,
+                    "port": self.left_port,
+                    "brightness": self.left_brightness,
+                    "preset": self.left_preset,
+                },
+                {
+                    "side": "right",
+                    "connected": self.right_connected,
+                    "port": self.right_port,
+                    "brightness": self.right_brightness,
+                    "preset": self.right_preset,
+                },
+            ])),
+            ApiRequest::SetBrightness(side, level) => {
+                match side {
+                    Side::Left => {
+                        self.left_brightness = level;
+                        self.send_left_brightness();
+                    }
+                    Side::Right => {
+                        self.right_brightness = level;
+                        self.send_right_brightness();
+                    }
+                }
+                ApiResponse::ok(serde_json::json!({ "brightness": level }))
+            }
+            ApiRequest::SetPreset(side, preset) => {
+                let known = self.preset_options().iter().any(|(id, _)| *id == preset);
+                if !known {
+                    return ApiResponse::error(404, format!("no preset '{}'", preset));
+                }
+                match side {
+                    Side::Left => self.left_preset = preset.clone(),
+                    Side::Right => self.right_preset = preset.clone(),
+                }
+                // The side's schedule and playlist still come first; say so
+                // rather than pretend the preset is on display
+                match self.preset_override(side) {
+                    Some((source, shown)) => ApiResponse::ok(serde_json::json!({
+                        "preset": preset,
+                        "overridden_by": source,
+                        "showing": shown,
+                    })),
+                    None => ApiResponse::ok(serde_json::json!({ "preset": preset })),
+                }
+            }
+            ApiRequest::PushFrame(side, frame) => {
+                let (connected, api_frame, preset) = match side {
+                    Side::Left => (self.left_connected, &mut self.left_api_frame, &mut self.left_preset),
+                    Side::Right => (self.right_connected, &mut self.right_api_frame, &mut self.right_preset),
+                };
+                if !connected {
+                    return ApiResponse::error(409, "that side is not connected");
+                }
+                *api_frame = Some(frame);
+                *preset = "api_frame".to_string();
+                self.send_render_inputs();
+                ApiResponse::ok(serde_json::json!({ "preset": "api_frame" }))
+            }
+            ApiRequest::ListPresets => {
+                ApiResponse::ok(serde_json::json!(self.preset_manager.list_presets()))
+            }
+            ApiRequest::GetPreset(name) => match self.preset_manager.get_preset(&name) {
+                Some(frame) => ApiResponse::ok(serde_json::json!({ "name": name, "frame": frame })),
+                None => ApiResponse::error(404, format!("no preset '{}'", name)),
+            },
+            ApiRequest::SavePreset(..) | ApiRequest::DeletePreset(_) if self.preset_load_error.is_some() => {
+                ApiResponse::error(409, "custom presets could not be loaded; recover them in the app first")
+            }
+            ApiRequest::SavePreset(name, frame) => match Arc::make_mut(&mut self.preset_manager).save_preset(name.clone(), frame) {
+                Ok(()) => ApiResponse::ok(serde_json::json!({ "name": name })),
+                Err(e) => ApiResponse::error(400, e),
+            },
+            ApiRequest::DeletePreset(name) => {
+                if self.preset_manager.get_preset(&name).is_none() {
+                    return ApiResponse::error(404, format!("no preset '{}'", name));
+                }
+                match Arc::make_mut(&mut self.preset_manager).delete_preset(&name) {
+                    Ok(()) => ApiResponse::ok(serde_json::json!({ "name": name })),
+                    Err(e) => ApiResponse::error(500, format!("failed to save presets: {}", e)),
+                }
+            }
+        }
+    }
 
-fn pattern_panic() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x06]
+    fn show_api_settings(&mut self, ui: &mut egui::Ui) {
+        let api = &mut self.settings.api;
+        let running_port = self.api_server.as_ref().map(|server| server.port);
+        let mut restart = false;
+        ui.horizontal(|ui| {
+            restart |= ui.checkbox(&mut api.enabled, "Serve REST API on localhost").changed();
+            ui.label("Port:");
+            // Rebind once the value is settled, not on every step of a drag
+            let port = ui.add(egui::DragValue::new(&mut api.port).clamp_range(1024..=65535));
+            let settled = port.drag_stopped() || port.lost_focus();
+            restart |= settled && running_port.is_some_and(|running| running != api.port);
+        });
+        ui.add_enabled_ui(api.enabled, |ui| {
+            if ui.checkbox(&mut api.stream, "Accept WebSocket frame streams").changed() {
+                if let Some(server) = &self.api_server {
+                    server.set_streaming(api.stream);
+                }
+            }
+        });
+        if restart {
+            if self.settings.api.enabled {
+                self.start_api_server(ui.ctx().clone());
+            } else {
+                self.api_server = None;
+            }
+        }
+        if let Some(server) = &self.api_server {
+            ui.label(format!("Listening on http://127.0.0.1:{}/api", server.port));
+            if self.settings.api.stream {
+                ui.label(format!("Stream test page: http://127.0.0.1:{}/stream", server.port));
+            }
+        }
+
+        if ui.button("Save API Settings").clicked() {
+            self.status_message = match self.settings.save_to_file() {
+                Ok(_) => "API settings saved".to_string(),
+                Err(e) => format!("Error saving API settings: {}", e),
+            };
+        }
+    }
+
+    fn start_mqtt_client(&mut self, ctx: egui::Context) {
+        let settings = &self.settings.mqtt;
+        self.mqtt_status = format!("Connecting to {}:{}", settings.host, settings.port);
+        let discovery = if settings.discovery {
+            let presets: Vec<String> = self.preset_options().into_iter().map(|(id, _)| id).collect();
+            mqtt::discovery_messages(settings, &presets)
+        } else {
+            Vec::new()
+        };
+        let client = mqtt::spawn_client(settings.clone(), discovery, move || ctx.request_repaint());
+        client.set_state(self.mqtt_state());
+        self.mqtt_client = Some(client);
+    }
+
+    /// Apply commands from the broker the same way as HTTP API requests;
+    /// the client thread keeps the published state current by itself
+    fn process_mqtt_events(&mut self) {
+        let Some(client) = &self.mqtt_client else {
+            return;
+        };
+        let events: Vec<MqttEvent> = client.rx.try_iter().collect();
+        for event in events {
+            match event {
+                MqttEvent::Connected => {
+                    let settings = &self.settings.mqtt;
+                    self.mqtt_status = format!("Connected to {}:{}", settings.host, settings.port);
+                }
+                MqttEvent::Disconnected(reason) => {
+                    self.mqtt_status = format!("Disconnected ({}), retrying", reason);
+                }
+                MqttEvent::Command(request) => {
+                    let response = self.handle_api_request(request);
+                    if response.status != 200 {
+                        self.status_message = format!("MQTT command failed: {}", response.body["error"]);
+                    }
+                    if let Some(client) = &self.mqtt_client {
+                        client.publish_state(self.mqtt_state());
+                    }
+                }
+                MqttEvent::Rejected(reason) => self.status_message = format!("MQTT message ignored: {}", reason),
+            }
+        }
+    }
+
+    /// Each side's state and the latest metrics, as retained MQTT messages
+    fn mqtt_state(&self) -> Vec<(String, String)> {
+        let settings = &self.settings.mqtt;
+        let mut messages = Vec::new();
+        for (side, connected, brightness, preset) in [
+            ("left", self.left_connected, self.left_brightness, &self.left_preset),
+            ("right", self.right_connected, self.right_brightness, &self.right_preset),
+        ] {
+            let state = serde_json::json!({ "connected": connected, "brightness": brightness, "preset": preset });
+            messages.push((settings.topic(&format!("{}/state", side)), state.to_string()));
+        }
+
+        let metrics = serde_json::json!({
+            "cpu": self.cpu_percent,
+            "ram": self.ram_percent,
+            "battery": average_battery_percentage(&self.batteries),
+            "temperature": if self.temperatures.is_empty() { None } else { Some(self.selected_temperature()) },
+        });
+        messages.push((settings.topic("metrics"), metrics.to_string()));
+        messages
+    }
+
+    fn show_mqtt_settings(&mut self, ui: &mut egui::Ui) {
+        let mqtt = &mut self.settings.mqtt;
+        let toggled = ui.checkbox(&mut mqtt.enabled, "Connect to MQTT broker").changed();
+        egui::Grid::new("mqtt_settings").num_columns(2).show(ui, |ui| {
+            ui.label("Broker:");
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut mqtt.host);
+                ui.add(egui::DragValue::new(&mut mqtt.port));
+            });
+            ui.end_row();
+            ui.label("User name:");
+            ui.text_edit_singleline(&mut mqtt.username);
+            ui.end_row();
+            ui.label("Password:");
+            ui.add(egui::TextEdit::singleline(&mut mqtt.password).password(true));
+            ui.end_row();
+            ui.label("Client id:");
+            ui.text_edit_singleline(&mut mqtt.client_id);
+            ui.end_row();
+            ui.label("Base topic:");
+            ui.text_edit_singleline(&mut mqtt.base_topic);
+            ui.end_row();
+            ui.checkbox(&mut mqtt.discovery, "HA discovery prefix:");
+            ui.text_edit_singleline(&mut mqtt.discovery_prefix);
+            ui.end_row();
+            ui.label("Publish every:");
+            ui.add(egui::DragValue::new(&mut mqtt.publish_interval_secs).clamp_range(1..=3600).suffix("s"));
+            ui.end_row();
+        });
+
+        let reconnect = ui.horizontal(|ui| {
+            let reconnect = ui.add_enabled(self.settings.mqtt.enabled, egui::Button::new("Reconnect")).clicked();
+            if ui.button("Save MQTT Settings").clicked() {
+                self.status_message = match self.settings.save_to_file() {
+                    Ok(_) => "MQTT settings saved".to_string(),
+                    Err(e) => format!("Error saving MQTT settings: {}", e),
+                };
+            }
+            reconnect
+        });
+        if toggled || reconnect.inner {
+            // Dropping the old client disconnects it
+            self.mqtt_client = None;
+            self.mqtt_status = "Not connected".to_string();
+            if self.settings.mqtt.enabled {
+                self.start_mqtt_client(ui.ctx().clone());
+            }
+        }
+        ui.label(&self.mqtt_status);
+    }
+
+    fn start_notification_monitor(&mut self, ctx: egui::Context) {
+        match notifications::spawn_notification_monitor(notifications::notify_monitor(None), move || ctx.request_repaint()) {
+            Ok(rx) => self.notification_rx = Some(rx),
+            Err(e) => self.status_message = format!("Notification monitor unavailable: {}", e),
+        }
+    }
+
+    /// Hand newly arrived notifications to the configured sides' render
+    /// threads, which show them over the preset until they're done
+    fn process_notifications(&mut self) {
+        let Some(rx) = &self.notification_rx else {
+            return;
+        };
+        let arrived: Vec<Notification> = rx.try_iter().collect();
+        let settings = &self.settings.notifications;
+        let now = Instant::now();
+        for notification in arrived {
+            if settings.allows(&notification.app_name) {
+                if settings.target.includes_left() && self.left_connected {
+                    self.left_render.set_banner(Some(NotificationBanner::new(&notification, settings, now)));
+                }
+                if settings.target.includes_right() && self.right_connected {
+                    self.right_render.set_banner(Some(NotificationBanner::new(&notification, settings, now)));
+                }
+            }
+            self.last_notification = Some(notification);
+        }
+    }
+
+    fn show_notification_settings(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.settings.notifications;
+        let enabled = ui.checkbox(&mut settings.enabled, "Show desktop notifications");
+        if enabled.changed() && settings.enabled && self.notification_rx.is_none() {
+            self.start_notification_monitor(ui.ctx().clone());
+        }
+        let settings = &mut self.settings.notifications;
+        if ui.checkbox(&mut settings.do_not_disturb, "Do not disturb").changed() && settings.do_not_disturb {
+            self.left_render.set_banner(None);
+            self.right_render.set_banner(None);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Show on");
+            egui::ComboBox::from_id_source("notification_target")
+                .selected_text(settings.target.label())
+                .show_ui(ui, |ui| {
+                    for target in AlertTarget::ALL {
+                        ui.selectable_value(&mut settings.target, target, target.label());
+                    }
+                });
+            egui::ComboBox::from_id_source("notification_style")
+                .selected_text(settings.style.label())
+                .show_ui(ui, |ui| {
+                    for style in NotificationStyle::ALL {
+                        ui.selectable_value(&mut settings.style, style, style.label());
+                    }
+                });
+            if settings.style == NotificationStyle::Icon {
+                ui.add(egui::DragValue::new(&mut settings.icon_secs).clamp_range(1..=60).suffix("s"));
+            }
+        });
+
+        egui::ComboBox::from_id_source("notification_filter")
+            .selected_text(settings.filter_mode.label())
+            .show_ui(ui, |ui| {
+                for mode in AppFilterMode::ALL {
+                    ui.selectable_value(&mut settings.filter_mode, mode, mode.label());
+                }
+            });
+        if settings.filter_mode != AppFilterMode::AllApps {
+            let mut remove = None;
+            for (i, app) in settings.apps.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(app);
+                    if ui.button("🗑").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                settings.apps.remove(i);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Add App").clicked() {
+                    settings.apps.push(String::new());
+                }
+                if let Some(last) = &self.last_notification {
+                    let known = settings.apps.iter().any(|app| app.eq_ignore_ascii_case(&last.app_name));
+                    if !known && ui.button(format!("Add \"{}\"", last.app_name)).clicked() {
+                        settings.apps.push(last.app_name.clone());
+                    }
+                }
+            });
+        }
+
+        if let Some(last) = &self.last_notification {
+            ui.label(format!("Last: [{}, {}] {} {}", last.app_name, last.urgency.label(), last.summary, last.body));
+        }
+
+        if ui.button("Save Notification Settings").clicked() {
+            self.status_message = match self.settings.save_to_file() {
+                Ok(_) => "Notification settings saved".to_string(),
+                Err(e) => format!("Error saving notification settings: {}", e),
+            };
+        }
+    }
+
+    /// Whether both sides should be asleep regardless of their preset:
+    /// user idle, lid closed or the system suspending
+    fn matrices_should_sleep(&self) -> bool {
+        self.idle_state == IdleState::Asleep
+            || self.suspended
+            || (self.lid_closed && self.settings.power.sleep_when_lid_closed)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn start_power_monitor(&mut self, ctx: egui::Context) {
+        match power::spawn_sleep_monitor(power::logind_monitor(), move || ctx.request_repaint()) {
+            Ok(rx) => {
+                self.power_rx = Some(rx);
+                self.sync_sleep_inhibitor();
+            }
+            Err(e) => self.status_message = format!("Suspend monitor unavailable: {}", e),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn start_power_monitor(&mut self, _ctx: egui::Context) {}
+
+    /// Hold the delay lock only while suspend is watched and the matrices
+    /// should be put to sleep first; best effort, without the lock suspend
+    /// just doesn't wait for us
+    #[cfg(target_os = "linux")]
+    fn sync_sleep_inhibitor(&mut self) {
+        if self.power_rx.is_some() && self.settings.power.sleep_on_suspend {
+            let _ = self.sleep_inhibitor.acquire();
+        } else {
+            self.sleep_inhibitor.release();
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn sync_sleep_inhibitor(&mut self) {}
+
+    #[cfg(target_os = "linux")]
+    fn process_power_events(&mut self) {
+        let Some(rx) = &self.power_rx else {
+            return;
+        };
+        let events: Vec<PowerEvent> = rx.try_iter().collect();
+        for event in events {
+            match event {
+                PowerEvent::Suspending => {
+                    if self.settings.power.sleep_on_suspend {
+                        for dev in [&self.left_device, &self.right_device].into_iter().flatten() {
+                            if let Ok(mut dev) = dev.lock() {
+                                dev.send(sleep(true));
+                            }
+                        }
+                        self.suspended = true;
+                    }
+                    self.sleep_inhibitor.release();
+                }
+                PowerEvent::Resumed => {
+                    self.suspended = false;
+                    self.start_resume_reconnect();
+                    self.sync_sleep_inhibitor();
+                }
+            }
+        }
+        self.retry_resume_reconnect();
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn process_power_events(&mut self) {}
+
+    /// Serial handles go stale across suspend, so drop every connected side
+    /// and reopen it, retrying while USB comes back
+    #[cfg(target_os = "linux")]
+    fn start_resume_reconnect(&mut self) {
+        let sides = (self.left_connected, self.right_connected);
+        if sides.0 {
+            self.disconnect_left();
+        }
+        if sides.1 {
+            self.disconnect_right();
+        }
+        if sides.0 || sides.1 {
+            self.resume_reconnect = Some((sides, ResumeRetry::new(Instant::now())));
+        }
+    }
+
+    /// Try reopening the sides that were connected before suspend once the
+    /// next attempt is due; connecting restores brightness, and the presets
+    /// carry on as before
+    #[cfg(target_os = "linux")]
+    fn retry_resume_reconnect(&mut self) {
+        let now = Instant::now();
+        let Some(((left, right), retry)) = &mut self.resume_reconnect else {
+            return;
+        };
+        if !retry.is_due(now) {
+            return;
+        }
+        let (left, right) = (*left, *right);
+
+        if left && !self.left_connected {
+            self.connect_left();
+        }
+        if right && !self.right_connected {
+            self.connect_right();
+        }
+        let pending = (left && !self.left_connected, right && !self.right_connected);
+
+        if pending == (false, false) {
+            self.resume_reconnect = None;
+            for dev in [&self.left_device, &self.right_device].into_iter().flatten() {
+                if let Ok(mut dev) = dev.lock() {
+                    dev.send(sleep(false));
+                }
+            }
+            self.status_message = "Resumed from suspend, matrices reconnected".to_string();
+        } else if let Some((sides, retry)) = &mut self.resume_reconnect {
+            if retry.failed(now) {
+                *sides = pending;
+            } else {
+                self.resume_reconnect = None;
+                self.status_message = "Could not reconnect the matrices after resume".to_string();
+            }
+        }
+    }
+
+    fn handle_control_command(&mut self, command: ControlCommand) {
+        match command {
+            ControlCommand::Timer(timer_command) => {
+                timer_command.apply(&mut self.timer, Instant::now());
+                self.status_message = format!("Timer: {:?}", timer_command);
+            }
+            ControlCommand::ReloadPresets => match PresetManager::load_from_file() {
+                Ok(manager) => {
+                    self.preset_manager = Arc::new(manager);
+                    self.preset_load_error = None;
+                    self.status_message = "Custom presets reloaded".to_string();
+                }
+                Err(e) => self.status_message = format!("Could not reload custom presets: {}", e),
+            },
+        }
+    }
+
+    fn show_timer_controls(&mut self, ui: &mut egui::Ui) {
+        let now = Instant::now();
+
+        ui.horizontal(|ui| {
+            ui.label("Mode:");
+            let mut mode = self.timer.mode;
+            egui::ComboBox::from_id_source("timer_mode")
+                .selected_text(mode.label())
+                .show_ui(ui, |ui| {
+                    for option in TimerMode::ALL {
+                        ui.selectable_value(&mut mode, option, option.label());
+                    }
+                });
+            if mode != self.timer.mode {
+                self.timer.set_mode(mode);
+            }
+        });
+
+        // Lengths are edited in whole minutes
+        let minutes_field = |ui: &mut egui::Ui, label: &str, length: &mut Duration| {
+            let mut minutes = length.as_secs() / 60;
+            ui.horizontal(|ui| {
+                ui.label(label);
+                if ui.add(egui::DragValue::new(&mut minutes).clamp_range(1..=999).suffix(" min")).changed() {
+                    *length = Duration::from_secs(minutes * 60);
+                }
+            });
+        };
+        match self.timer.mode {
+            TimerMode::Countdown => minutes_field(ui, "Length:", &mut self.timer.countdown),
+            TimerMode::Stopwatch => {}
+            TimerMode::Pomodoro => {
+                minutes_field(ui, "Work:", &mut self.timer.work);
+                minutes_field(ui, "Break:", &mut self.timer.short_break);
+            }
+        }
+
+        let time = self.timer.display_time(now).as_secs();
+        let mut text = format!("{:02}:{:02}:{:02}", time / 3600, (time / 60) % 60, time % 60);
+        if self.timer.mode == TimerMode::Pomodoro {
+            text.push_str(match self.timer.phase() {
+                PomodoroPhase::Work => " (work)",
+                PomodoroPhase::Break => " (break)",
+            });
+        }
+        ui.label(text);
+
+        ui.horizontal(|ui| {
+            let mut command = None;
+            if self.timer.is_running() {
+                if ui.button("Pause").clicked() {
+                    command = Some(TimerCommand::Pause);
+                }
+            } else if ui.button("Start").clicked() {
+                command = Some(TimerCommand::Start);
+            }
+            if ui.button("Reset").clicked() {
+                command = Some(TimerCommand::Reset);
+            }
+            if let Some(command) = command {
+                command.apply(&mut self.timer, now);
+            }
+        });
+    }
+
+    /// Every preset a side can show as (id, label): built-ins, custom presets, then scripts
+    fn preset_options(&self) -> Vec<(String, String)> {
+        self.presets
+            .entries(&self.preset_manager, &self.scripts)
+            .into_iter()
+            .map(|entry| (entry.id, entry.name))
+            .collect()
+    }
+
+    /// Thumbnails of the custom presets with search and filters, and the
+    /// details of the selected one
+    fn show_preset_gallery(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.gallery_search);
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.gallery_favorites_only, "★ Favorites only");
+            egui::ComboBox::from_id_source("gallery_tag")
+                .selected_text(self.gallery_tag.clone().unwrap_or_else(|| "All tags".to_string()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.gallery_tag, None, "All tags");
+                    for tag in self.preset_manager.tags() {
+                        ui.selectable_value(&mut self.gallery_tag, Some(tag.clone()), tag);
+                    }
+                });
+        });
+
+        let shown: Vec<CustomPreset> = self
+            .preset_manager
+            .ordered()
+            .into_iter()
+            .filter(|preset| preset.matches(&self.gallery_search))
+            .filter(|preset| !self.gallery_favorites_only || preset.favorite)
+            .filter(|preset| match &self.gallery_tag {
+                Some(tag) => preset.tags.contains(tag),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        if shown.is_empty() {
+            ui.label(if self.preset_manager.presets.is_empty() {
+                "No custom presets yet; draw one in the Image Editor"
+            } else {
+                "No presets match"
+            });
+        }
+
+        let mut clicked = None;
+        let mut starred = None;
+        ui.horizontal_wrapped(|ui| {
+            for preset in &shown {
+                ui.vertical(|ui| {
+                    let selected = self.selected_custom_preset.as_deref() == Some(preset.name.as_str());
+                    if preset_thumbnail(ui, &preset.image_data, selected).on_hover_text(&preset.description).clicked() {
+                        clicked = Some(preset.name.clone());
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.small_button(if preset.favorite { "★" } else { "☆" }).clicked() {
+                            starred = Some((preset.name.clone(), !preset.favorite));
+                        }
+                        ui.label(&preset.name);
+                    });
+                });
+            }
+        });
+        if let Some((name, favorite)) = starred {
+            if let Err(e) = Arc::make_mut(&mut self.preset_manager).set_favorite(&name, favorite) {
+                self.status_message = e;
+            }
+        }
+        if let Some(name) = clicked {
+            if let Some(preset) = self.preset_manager.presets.get(&name) {
+                self.detail_description = preset.description.clone();
+                self.detail_tags = preset.tags.join(", ");
+            }
+            self.selected_custom_preset = Some(name);
+        }
+
+        let Some(preset) = self
+            .selected_custom_preset
+            .as_ref()
+            .and_then(|name| self.preset_manager.presets.get(name))
+            .cloned()
+        else {
+            return;
+        };
+        ui.separator();
+        ui.strong(&preset.name);
+        ui.label(format!("Created {}, modified {}", format_timestamp(preset.created), format_timestamp(preset.modified)));
+        ui.horizontal(|ui| {
+            ui.label("Description:");
+            ui.text_edit_singleline(&mut self.detail_description);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Tags:");
+            ui.text_edit_singleline(&mut self.detail_tags).on_hover_text("Separated by commas");
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Save Details").clicked() {
+                let tags = self
+                    .detail_tags
+                    .split(',')
+                    .map(|tag| tag.trim().to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+                self.status_message = match Arc::make_mut(&mut self.preset_manager).set_details(
+                    &preset.name,
+                    self.detail_description.trim().to_string(),
+                    tags,
+                    preset.favorite,
+                ) {
+                    Ok(()) => format!("Saved details of '{}'", preset.name),
+                    Err(e) => e,
+                };
+            }
+            if ui.button("⬆ Earlier").clicked() {
+                if let Err(e) = Arc::make_mut(&mut self.preset_manager).move_preset(&preset.name, -1) {
+                    self.status_message = e;
+                }
+            }
+            if ui.button("⬇ Later").clicked() {
+                if let Err(e) = Arc::make_mut(&mut self.preset_manager).move_preset(&preset.name, 1) {
+                    self.status_message = e;
+                }
+            }
+            if ui.button("Open in Editor").clicked() {
+                self.editor_image = preset.image_data.clone();
+                self.editor_preset_name = preset.name.clone();
+                self.show_editor = true;
+            }
+        });
+    }
+
+    /// Offer to start over after settings.json failed to load; until then
+    /// the defaults are used but never saved
+    fn show_settings_recovery(&mut self, ui: &mut egui::Ui) {
+        let Some(error) = &self.settings_load_error else {
+            return;
+        };
+        ui.colored_label(egui::Color32::RED, format!("⚠ Settings could not be loaded: {}", error));
+        if ui.button("Use Default Settings").clicked() {
+            self.status_message = match self.settings.set_aside_damaged() {
+                Ok(()) => {
+                    self.settings_load_error = None;
+                    format!(
+                        "Using default settings; the damaged file was kept as {}",
+                        Settings::damaged_path().display()
+                    )
+                }
+                Err(e) => format!("Error moving the damaged file: {}", e),
+            };
+        }
+    }
+
+    /// Offer to recover after custom_presets.json failed to load
+    fn show_preset_recovery(&mut self, ui: &mut egui::Ui) {
+        let Some(error) = &self.preset_load_error else {
+            return;
+        };
+        ui.colored_label(egui::Color32::RED, format!("⚠ Custom presets could not be loaded: {}", error));
+        ui.horizontal(|ui| {
+            if ui.button("Restore from Backup").clicked() {
+                self.status_message = match self.preset_manager.load_backup() {
+                    Ok((mut manager, path)) => {
+                        let saved = Arc::make_mut(&mut self.preset_manager)
+                            .set_aside_damaged()
+                            .map_err(|e| e.to_string())
+                            .and_then(|_| manager.save_to_file().map_err(|e| e.to_string()));
+                        match saved {
+                            Ok(()) => {
+                                let count = manager.presets.len();
+                                let damaged = manager.damaged_path();
+                                self.preset_manager = Arc::new(manager);
+                                self.preset_load_error = None;
+                                format!(
+                                    "Restored {} preset(s) from {}; the damaged file was kept as {}",
+                                    count,
+                                    path.display(),
+                                    damaged.display()
+                                )
+                            }
+                            Err(e) => format!("Error restoring presets: {}", e),
+                        }
+                    }
+                    Err(e) => format!("Could not restore presets: {}", e),
+                };
+            }
+            if ui.button("Start Empty").clicked() {
+                self.status_message = match Arc::make_mut(&mut self.preset_manager).set_aside_damaged() {
+                    Ok(()) => {
+                        self.preset_load_error = None;
+                        format!(
+                            "Started with no custom presets; the damaged file was kept as {}",
+                            self.preset_manager.damaged_path().display()
+                        )
+                    }
+                    Err(e) => format!("Error moving the damaged file: {}", e),
+                };
+            }
+        });
+    }
+
+    fn show_library_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut self.library_path);
+        });
+        ui.label(format!(
+            "Exports a JSON bundle, a binary one for .{} files, or a 9x34 PNG of one preset for .png files",
+            bundle::BINARY_EXTENSION
+        ));
+
+        let path = std::path::PathBuf::from(&self.library_path);
+        ui.horizontal(|ui| {
+            if ui.button("Export All").clicked() {
+                self.status_message = match bundle::export_file(&self.preset_manager, &[], &path) {
+                    Ok(count) => format!("Exported {} preset(s) to {}", count, path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                };
+            }
+            let selected = self.selected_custom_preset.clone();
+            let label = match &selected {
+                Some(name) => format!("Export '{}'", name),
+                None => "Export Selected".to_string(),
+            };
+            if ui.add_enabled(selected.is_some(), egui::Button::new(label)).clicked() {
+                let names: Vec<String> = selected.into_iter().collect();
+                self.status_message = match bundle::export_file(&self.preset_manager, &names, &path) {
+                    Ok(_) => format!("Exported '{}' to {}", names[0], path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                };
+            }
+        });
+        if self.selected_custom_preset.is_none() {
+            ui.label("Pick a preset under Load Preset in the Image Editor to export just that one");
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("On a name clash:");
+            egui::ComboBox::from_id_source("import_policy")
+                .selected_text(self.import_policy.label())
+                .show_ui(ui, |ui| {
+                    for policy in ConflictPolicy::ALL {
+                        ui.selectable_value(&mut self.import_policy, policy, policy.label());
+                    }
+                });
+            // Importing into a library that failed to load would lose it
+            let import = ui.add_enabled(self.preset_load_error.is_none(), egui::Button::new("Import"));
+            if import.clicked() {
+                self.status_message = match bundle::import_file(Arc::make_mut(&mut self.preset_manager), &path, self.import_policy) {
+                    Ok(summary) => format!("Imported from {}: {}", path.display(), summary),
+                    Err(e) => format!("Import failed: {}", e),
+                };
+            }
+        });
+    }
+
+    fn show_idle_settings(&mut self, ui: &mut egui::Ui) {
+        let idle = &mut self.settings.idle;
+        ui.checkbox(&mut idle.enabled, "Dim and sleep when idle");
+        ui.horizontal(|ui| {
+            ui.label("Dim after:");
+            ui.add(egui::DragValue::new(&mut idle.dim_after_mins).clamp_range(1..=240).suffix(" min"));
+            ui.label("Sleep after:");
+            ui.add(egui::DragValue::new(&mut idle.sleep_after_mins).clamp_range(1..=480).suffix(" min"));
+        });
+        if idle.sleep_after_mins < idle.dim_after_mins {
+            idle.sleep_after_mins = idle.dim_after_mins;
+        }
+        ui.label("Dimmed brightness:");
+        ui.add(egui::Slider::new(&mut idle.dim_brightness, 0..=255));
+
+        if ui.checkbox(&mut self.settings.power.sleep_on_suspend, "Sleep before system suspend").changed() {
+            self.sync_sleep_inhibitor();
+        }
+        ui.checkbox(&mut self.settings.power.sleep_when_lid_closed, "Sleep while the lid is closed");
+
+        let idle_text = match self.idle_time {
+            Some(time) => format!("{}s", time.as_secs()),
+            None => "unknown".to_string(),
+        };
+        ui.label(format!("Idle: {} (source: {}), state: {:?}", idle_text, self.idle_source, self.idle_state));
+
+        if ui.button("Save Idle Settings").clicked() {
+            self.status_message = match self.settings.save_to_file() {
+                Ok(_) => "Idle settings saved".to_string(),
+                Err(e) => format!("Error saving idle settings: {}", e),
+            };
+        }
+    }
+
+    fn show_schedule_rules(&mut self, ui: &mut egui::Ui) {
+        let mut actions = vec![ScheduleAction::Keep, ScheduleAction::Sleep];
+        actions.extend(self.preset_options().into_iter().map(|(id, _)| ScheduleAction::Preset(id)));
+
+        let mut remove = None;
+        for (i, rule) in self.settings.schedules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut rule.enabled, "");
+                ui.text_edit_singleline(&mut rule.name);
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+            });
+            ui.horizontal(|ui| {
+                for (day, label) in DAY_LABELS.iter().enumerate() {
+                    ui.checkbox(&mut rule.days[day], *label);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("From");
+                time_of_day_field(ui, &mut rule.start_minute);
+                ui.label("to");
+                time_of_day_field(ui, &mut rule.end_minute);
+            });
+            ui.horizontal(|ui| {
+                for (label, action) in [("Left:", &mut rule.left), ("Right:", &mut rule.right)] {
+                    ui.label(label);
+                    egui::ComboBox::from_id_source((label, "schedule_action", i))
+                        .selected_text(action.label())
+                        .show_ui(ui, |ui| {
+                            for option in &actions {
+                                ui.selectable_value(action, option.clone(), option.label());
+                            }
+                        });
+                }
+            });
+            ui.separator();
+        }
+
+        if let Some(i) = remove {
+            self.settings.schedules.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Schedule").clicked() {
+                self.settings.schedules.push(ScheduleRule::default());
+            }
+            if ui.button("Save Schedule").clicked() {
+                self.status_message = match self.settings.save_to_file() {
+                    Ok(_) => "Schedule saved".to_string(),
+                    Err(e) => format!("Error saving schedule: {}", e),
+                };
+            }
+        });
+    }
+
+    /// Evaluate the alert rules against the latest metrics and pick which
+    /// rule (if any) overrides each side's preset
+    fn evaluate_alerts(&mut self) {
+        let rules = &self.settings.alert_rules;
+        let snapshot = MetricSnapshot {
+            cpu: Some(self.cpu_percent as f32),
+            ram: Some(self.ram_percent as f32),
+            battery: average_battery_percentage(&self.batteries),
+            disk: self.disk_space.iter().map(|d| d.usage_percent as f32).reduce(f32::max),
+            temperature: if self.temperatures.is_empty() { None } else { Some(self.selected_temperature()) },
+            net_down: Some(self.net_rx_percent as f32),
+            net_up: Some(self.net_tx_percent as f32),
+        };
+
+        let firing = self.alert_engine.evaluate(rules, &snapshot, Instant::now());
+        let left_alert = firing.iter().copied().find(|&i| rules[i].target.includes_left());
+        let right_alert = firing.iter().copied().find(|&i| rules[i].target.includes_right());
+
+        for (old, new) in [(self.left_alert, left_alert), (self.right_alert, right_alert)] {
+            if new != old {
+                if let Some(i) = new {
+                    self.status_message = format!("Alert: {}", rules[i].name);
+                }
+            }
+        }
+        self.left_alert = left_alert;
+        self.right_alert = right_alert;
+    }
+
+    /// Device command shown on a side while an alert is firing
+    fn alert_command(&self, action: &AlertAction) -> Vec<u8> {
+        match action {
+            AlertAction::Panic => pattern_panic(),
+            AlertAction::Flash => image_data_to_command(&render_alert_display(self.idle_frame)),
+            AlertAction::CustomPreset(name) => self
+                .preset_manager
+                .get_preset(name)
+                .map(|image_data| image_data_to_command(&image_data))
+                .unwrap_or_else(pattern_panic),
+        }
+    }
+
+    fn show_alert_rules(&mut self, ui: &mut egui::Ui) {
+        let preset_list = self.preset_manager.list_presets();
+        let mut remove = None;
+        let mut changed = false;
+
+        for (i, rule) in self.settings.alert_rules.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                changed |= ui.checkbox(&mut rule.enabled, "").changed();
+                changed |= ui.text_edit_singleline(&mut rule.name).changed();
+                if ui.button("🗑").clicked() {
+                    remove = Some(i);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("If");
+                egui::ComboBox::from_id_source(("alert_metric", i))
+                    .selected_text(rule.metric.label())
+                    .show_ui(ui, |ui| {
+                        for metric in Metric::ALL {
+                            changed |= ui.selectable_value(&mut rule.metric, metric, metric.label()).changed();
+                        }
+                    });
+                egui::ComboBox::from_id_source(("alert_comparison", i))
+                    .selected_text(rule.comparison.label())
+                    .width(40.0)
+                    .show_ui(ui, |ui| {
+                        for comparison in [Comparison::Above, Comparison::Below] {
+                            changed |= ui.selectable_value(&mut rule.comparison, comparison, comparison.label()).changed();
+                        }
+                    });
+                changed |= ui.add(egui::DragValue::new(&mut rule.threshold).clamp_range(0.0..=200.0)).changed();
+                ui.label("for");
+                changed |= ui.add(egui::DragValue::new(&mut rule.duration_secs).suffix("s")).changed();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Show on");
+                egui::ComboBox::from_id_source(("alert_target", i))
+                    .selected_text(rule.target.label())
+                    .show_ui(ui, |ui| {
+                        for target in AlertTarget::ALL {
+                            changed |= ui.selectable_value(&mut rule.target, target, target.label()).changed();
+                        }
+                    });
+                egui::ComboBox::from_id_source(("alert_action", i))
+                    .selected_text(rule.action.label())
+                    .show_ui(ui, |ui| {
+                        changed |= ui.selectable_value(&mut rule.action, AlertAction::Panic, AlertAction::Panic.label()).changed();
+                        changed |= ui.selectable_value(&mut rule.action, AlertAction::Flash, AlertAction::Flash.label()).changed();
+                        for preset in &preset_list {
+                            let action = AlertAction::CustomPreset(preset.clone());
+                            changed |= ui.selectable_value(&mut rule.action, action, format!("Preset: {}", preset)).changed();
+                        }
+                    });
+            });
+            ui.separator();
+        }
+
+        if let Some(i) = remove {
+            self.settings.alert_rules.remove(i);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Rule").clicked() {
+                self.settings.alert_rules.push(AlertRule::default());
+                changed = true;
+            }
+            if ui.button("Save Rules").clicked() {
+                self.status_message = match self.settings.save_to_file() {
+                    Ok(_) => "Alert rules saved".to_string(),
+                    Err(e) => format!("Error saving alert rules: {}", e),
+                };
+            }
+        });
+
+        if changed {
+            self.alert_engine.reset();
+            self.left_alert = None;
+            self.right_alert = None;
+        }
+    }
+
+    /// Snapshot of what a side's presets draw from, for its render thread
+    fn render_inputs(&self, side: Side) -> RenderInputs {
+        let (api_frame, params) = match side {
+            Side::Left => (&self.left_api_frame, &self.settings.left_preset_params),
+            Side::Right => (&self.right_api_frame, &self.settings.right_preset_params),
+        };
+        RenderInputs {
+            cpu_percent: self.cpu_percent,
+            ram_percent: self.ram_percent,
+            net_rx_percent: self.net_rx_percent,
+            net_tx_percent: self.net_tx_percent,
+            disks: self.disk_space.clone(),
+            disk_io_history: self.disk_io_history.clone(),
+            temperature: self.selected_temperature(),
+            temp_warning: self.settings.temperature.warning,
+            temp_critical: self.settings.temperature.critical,
+            fan_percent: self.fan_percent(),
+            batteries: self.batteries.clone(),
+            idle_frame: self.idle_frame,
+            api_frame: api_frame.clone(),
+            audio: self.audio.as_ref().map(AudioVisualizer::feed),
+            custom: Arc::clone(&self.preset_manager),
+            params: params.clone(),
+        }
+    }
+
+    fn send_render_inputs(&self) {
+        self.left_render.update(self.render_inputs(Side::Left));
+        self.right_render.update(self.render_inputs(Side::Right));
+    }
+
+    /// Send the timer to the render threads when it changed since last time
+    fn sync_render_timer(&mut self) {
+        if self.rendered_timer.as_ref() == Some(&self.timer) {
+            return;
+        }
+        self.left_render.set_timer(self.timer.clone());
+        self.right_render.set_timer(self.timer.clone());
+        self.rendered_timer = Some(self.timer.clone());
+    }
+
+    /// Tell each render thread what to draw: the side's preset while the
+    /// side is free, nothing while something else owns the display
+    fn sync_render_plans(&self) {
+        let left = self.left_plan.clone().filter(|_| self.side_is_free(Side::Left));
+        let right = self.right_plan.clone().filter(|_| self.side_is_free(Side::Right));
+        self.left_render.show(left);
+        self.right_render.show(right);
+    }
+
+    fn process_render_events(&mut self) {
+        for side in [Side::Left, Side::Right] {
+            let (render, showing) = match side {
+                Side::Left => (&self.left_render, &mut self.left_showing),
+                Side::Right => (&self.right_render, &mut self.right_showing),
+            };
+            for event in render.rx.try_iter() {
+                match event {
+                    RenderEvent::Showing(preset) => *showing = preset,
+                    RenderEvent::Message(message) => self.status_message = message,
+                }
+            }
+        }
+    }
+
+    /// Preset a side should show now: the schedule can swap one in, otherwise
+    /// an enabled playlist picks it. Also returns the transition to use
+    fn select_preset(
+        schedule: &ScheduleAction,
+        cursor: &mut PlaylistCursor,
+        playlist: &Playlist,
+        manual: &str,
+        transition: TransitionSettings,
+    ) -> (String, TransitionSettings) {
+        if let ScheduleAction::Preset(name) = schedule {
+            return (name.clone(), transition);
+        }
+        match cursor.current(playlist, Instant::now()) {
+            Some(entry) => {
+                let kind = entry.transition.unwrap_or(transition.kind);
+                (entry.preset.clone(), TransitionSettings { kind, ..transition })
+            }
+            None => (manual.to_string(), transition),
+        }
+    }
+
+    /// What takes precedence over a side's chosen preset, if anything: an
+    /// active schedule rule or an enabled playlist, with the preset it shows
+    fn preset_override(&self, side: Side) -> Option<(&'static str, String)> {
+        let (schedule, cursor, playlist) = match side {
+            Side::Left => (&self.left_schedule, &self.left_playlist_cursor, &self.settings.left_playlist),
+            Side::Right => (&self.right_schedule, &self.right_playlist_cursor, &self.settings.right_playlist),
+        };
+        if let ScheduleAction::Preset(name) = schedule {
+            return Some(("schedule", name.clone()));
+        }
+        cursor.peek(playlist).map(|entry| ("playlist", entry.preset.clone()))
+    }
+
+    /// Whether a side's render thread owns the display, i.e. no stream,
+    /// alert or sleep has taken over. The thread shows notification banners
+    /// over the preset itself
+    fn side_is_free(&self, side: Side) -> bool {
+        let (alert, asleep) = match side {
+            Side::Left => (self.left_alert, self.left_asleep),
+            Side::Right => (self.right_alert, self.right_asleep),
+        };
+        alert.is_none() && !asleep && !self.stream_hub.is_streaming(side)
+    }
+
+    /// Whether either side is showing the audio visualizer
+    fn audio_in_use(&self) -> bool {
+        [&self.left_showing, &self.right_showing]
+            .iter()
+            .any(|showing| showing.as_deref() == Some("audio"))
+    }
+
+    /// Start the audio visualizer the first time it's needed; a source that
+    /// fails to open isn't retried until the settings are applied again
+    fn ensure_audio(&mut self) {
+        if self.audio.is_some() || self.audio_error.is_some() || !self.audio_in_use() {
+            return;
+        }
+        let bands = if self.settings.audio.spanned { 2 * MATRIX_WIDTH } else { MATRIX_WIDTH };
+        match audio::open_source(&self.settings.audio) {
+            Ok(source) => {
+                self.audio = Some(AudioVisualizer::start(source, bands));
+                self.send_render_inputs();
+            }
+            Err(e) => {
+                self.status_message = format!("Audio source unavailable: {}", e);
+                self.audio_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn show_audio_settings(&mut self, ui: &mut egui::Ui) {
+        let audio = &mut self.settings.audio;
+        egui::ComboBox::from_id_source("audio_source")
+            .selected_text(audio.source.label())
+            .show_ui(ui, |ui| {
+                for kind in AudioSourceKind::ALL {
+                    ui.selectable_value(&mut audio.source, kind, kind.label());
+                }
+            });
+        match audio.source {
+            AudioSourceKind::WavFile => {
+                ui.horizontal(|ui| {
+                    ui.label("File:");
+                    ui.text_edit_singleline(&mut audio.wav_path);
+                });
+            }
+            AudioSourceKind::Stdin => {
+                ui.horizontal(|ui| {
+                    ui.label("16-bit mono at");
+                    ui.add(egui::DragValue::new(&mut audio.stdin_rate).clamp_range(8000..=192_000).suffix(" Hz"));
+                });
+            }
+            AudioSourceKind::Monitor => {}
+        }
+        ui.checkbox(&mut audio.spanned, "Span 18 bands across both sides");
+
+        let status = match (&self.audio, &self.audio_error) {
+            (Some(audio), _) => audio.status(),
+            (None, Some(error)) => format!("Error: {}", error),
+            (None, None) => "Starts when a side shows the Audio Spectrum preset".to_string(),
+        };
+        ui.label(status);
+
+        ui.horizontal(|ui| {
+            if ui.button("Apply").clicked() {
+                // Reopened with the new settings on the next update
+                self.audio = None;
+                self.audio_error = None;
+            }
+            if ui.button("Save Audio Settings").clicked() {
+                self.status_message = match self.settings.save_to_file() {
+                    Ok(_) => "Audio settings saved".to_string(),
+                    Err(e) => format!("Error saving audio settings: {}", e),
+                };
+            }
+        });
+    }
+
+    fn show_script_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Scripts are loaded from ./{}/*.{}", SCRIPT_DIR, SCRIPT_EXTENSION));
+        let names: Vec<&String> = self.scripts.names().collect();
+        if names.is_empty() {
+            ui.label("No scripts found");
+        } else {
+            ui.label(names.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(", "));
+        }
+        if ui.button("Reload Scripts").clicked() {
+            let errors = self.scripts.reload();
+            self.left_render.set_scripts(self.scripts.clone());
+            self.right_render.set_scripts(self.scripts.clone());
+            self.status_message = match errors.first() {
+                Some(error) => format!("Loaded {} script(s); {}", self.scripts.names().count(), error),
+                None => format!("Loaded {} script(s)", self.scripts.names().count()),
+            };
+        }
+        ui.collapsing("Script reference", |ui| {
+            ui.label("Runs once per frame on a 9x34 framebuffer that is kept between frames.");
+            ui.label("Drawing: clear() fill(b) set(x, y, b) get(x, y) line(x0, y0, x1, y1, b) rect(x, y, w, h, b)");
+            ui.label("Metrics: cpu() ram() net_down() net_up() disk() temperature() battery() charging()");
+            ui.label("Time: time() frame() hour() minute() second() weekday()");
+            ui.label("Maths: sin cos abs floor ceil round sqrt min max pow clamp random()");
+            ui.label("Other: len(a) push(a, v) print(...)  Constants: WIDTH HEIGHT");
+            ui.label("state.name keeps its value between frames and starts as 0");
+        });
+    }
+
+    /// Temperature of the chosen sensor, or the hottest one if none is chosen
+    fn selected_temperature(&self) -> f32 {
+        let reading = match &self.temp_sensor {
+            Some(label) => self.temperatures.iter().find(|r| &r.label == label),
+            None => self.temperatures.first(),
+        };
+        reading.map(|r| r.celsius).unwrap_or(0.0)
+    }
+
+    /// Busiest fan as a percentage of its top speed, or of the highest speed
+    /// seen so far when the driver doesn't report one
+    fn fan_percent(&self) -> u8 {
+        self.fans
+            .iter()
+            .filter_map(|fan| {
+                let max = fan.max_rpm.unwrap_or(self.fan_peak_rpm);
+                (max > 0).then(|| (fan.rpm as f32 / max as f32 * 100.0).clamp(0.0, 100.0) as u8)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Brightness to send for a side's setting, lowered while the user is idle
+    fn effective_brightness(&self, level: u8) -> u8 {
+        match self.idle_state {
+            IdleState::Active => level,
+            IdleState::Dimmed | IdleState::Asleep => level.min(self.settings.idle.dim_brightness),
+        }
+    }
+
+    fn send_left_brightness(&mut self) {
+        if let Some(left_dev) = &self.left_device {
+            if let Ok(mut dev) = left_dev.lock() {
+                dev.send(brightness(self.effective_brightness(self.left_brightness)));
+            }
+        }
+    }
+
+    fn send_right_brightness(&mut self) {
+        if let Some(right_dev) = &self.right_device {
+            if let Ok(mut dev) = right_dev.lock() {
+                dev.send(brightness(self.effective_brightness(self.right_brightness)));
+            }
+        }
+    }
 }
 
-fn pattern_lotus_vertical() -> Vec<u8> {
-    vec![MAGIC1, MAGIC2, 0x01, 0x07]
+/// A custom preset drawn small, 3 points per LED; outlined when selected
+fn preset_thumbnail(ui: &mut egui::Ui, image_data: &[u8], selected: bool) -> egui::Response {
+    let pixel_size = 3.0;
+    let (response, painter) = ui.allocate_painter(
+        egui::Vec2::new(pixel_size * MATRIX_WIDTH as f32, pixel_size * MATRIX_HEIGHT as f32),
+        egui::Sense::click(),
+    );
+    for (idx, &brightness) in image_data.iter().enumerate().take(MATRIX_WIDTH * MATRIX_HEIGHT) {
+        let (x, y) = (idx % MATRIX_WIDTH, idx / MATRIX_WIDTH);
+        let rect = egui::Rect::from_min_size(
+            response.rect.min + egui::Vec2::new(x as f32 * pixel_size, y as f32 * pixel_size),
+            egui::Vec2::splat(pixel_size),
+        );
+        painter.rect_filled(rect, 0.0, egui::Color32::from_gray(brightness));
+    }
+    let stroke = if selected {
+        egui::Stroke::new(2.0, egui::Color32::LIGHT_BLUE)
+    } else {
+        egui::Stroke::new(1.0, egui::Color32::GRAY)
+    };
+    painter.rect_stroke(response.rect, 0.0, stroke);
+    response
+}
+
+/// A Unix timestamp as local date and time, or "unknown" for 0
+fn format_timestamp(secs: u64) -> String {
+    match Local.timestamp_opt(secs as i64, 0).single() {
+        Some(time) if secs > 0 => time.format("%Y-%m-%d %H:%M").to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Combo box listing every preset under its category heading
+fn preset_picker(ui: &mut egui::Ui, id: &str, selected: &mut String, entries: &[PresetEntry]) {
+    let selected_text = entries
+        .iter()
+        .find(|entry| entry.id == *selected)
+        .map_or(selected.clone(), |entry| entry.name.clone());
+    egui::ComboBox::from_id_source(id).selected_text(selected_text).show_ui(ui, |ui| {
+        let mut category = PresetCategory::General;
+        for entry in entries {
+            if entry.category == PresetCategory::General {
+                ui.selectable_value(selected, entry.id.clone(), &entry.name);
+                continue;
+            }
+            if entry.category != category {
+                category = entry.category;
+                ui.separator();
+                ui.label(category.label());
+            }
+            ui.selectable_value(selected, entry.id.clone(), format!("  {}", entry.name));
+        }
+    });
+}
+
+/// Editor for a side's playlist; returns true when the entries changed
+fn playlist_editor(ui: &mut egui::Ui, id: &str, playlist: &mut Playlist, options: &[(String, String)]) -> bool {
+    let mut changed = ui.checkbox(&mut playlist.enabled, "Rotate through playlist").changed();
+    let mut remove = None;
+    let mut move_up = None;
+
+    for (i, entry) in playlist.entries.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            let selected = options
+                .iter()
+                .find(|(preset, _)| *preset == entry.preset)
+                .map(|(_, label)| label.clone())
+                .unwrap_or_else(|| entry.preset.clone());
+            egui::ComboBox::from_id_source((id, "entry", i))
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (preset, label) in options {
+                        changed |= ui.selectable_value(&mut entry.preset, preset.clone(), label).changed();
+                    }
+                });
+            changed |= ui
+                .add(egui::DragValue::new(&mut entry.dwell_secs).clamp_range(1..=3600).suffix("s"))
+                .changed();
+            let transition_text = entry.transition.map(|k| k.label()).unwrap_or("Default");
+            egui::ComboBox::from_id_source((id, "entry_transition", i))
+                .selected_text(transition_text)
+                .show_ui(ui, |ui| {
+                    changed |= ui.selectable_value(&mut entry.transition, None, "Default").changed();
+                    for kind in TransitionKind::ALL {
+                        changed |= ui.selectable_value(&mut entry.transition, Some(kind), kind.label()).changed();
+                    }
+                });
+            if i > 0 && ui.button("⬆").clicked() {
+                move_up = Some(i);
+            }
+            if ui.button("🗑").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+
+    if let Some(i) = move_up {
+        playlist.entries.swap(i - 1, i);
+        changed = true;
+    }
+    if let Some(i) = remove {
+        playlist.entries.remove(i);
+        changed = true;
+    }
+    if ui.button("Add Entry").clicked() {
+        playlist.entries.push(PlaylistEntry::default());
+        changed = true;
+    }
+    changed
+}
+
+/// Editor for a side's default transition kind and duration
+fn transition_editor(ui: &mut egui::Ui, id: &str, settings: &mut TransitionSettings) {
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source((id, "kind"))
+            .selected_text(settings.kind.label())
+            .show_ui(ui, |ui| {
+                for kind in TransitionKind::ALL {
+                    ui.selectable_value(&mut settings.kind, kind, kind.label());
+                }
+            });
+        ui.add(egui::DragValue::new(&mut settings.duration_ms).clamp_range(0..=5000).suffix(" ms"));
+    });
+}
+
+/// Hour and minute editors for a time of day stored as minutes since midnight
+fn time_of_day_field(ui: &mut egui::Ui, minute_of_day: &mut u16) {
+    let mut hour = *minute_of_day / 60;
+    let mut minute = *minute_of_day % 60;
+    let hour_changed = ui.add(egui::DragValue::new(&mut hour).clamp_range(0..=23)).changed();
+    ui.label(":");
+    let minute_changed = ui.add(egui::DragValue::new(&mut minute).clamp_range(0..=59)).changed();
+    if hour_changed || minute_changed {
+        *minute_of_day = hour * 60 + minute;
+    }
+}
+
+fn get_available_ports() -> Vec<String> {
+    serialport::available_ports()
+        .map(|ports| {
+            ports
+                .iter()
+                .filter_map(|p| match p {
+                    serialport::SerialPortInfo {
+                        port_name,
+                        port_type: _,
+                    } => Some(port_name.clone()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 fn set_animate(enabled: bool) -> Vec<u8> {
@@ -0,0 +1,715 @@
+// src/mqtt.rs
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::{decode_frame, ApiRequest, Side};
+
+/// Seconds the broker waits without traffic before dropping us
+const KEEP_ALIVE_SECS: u16 = 30;
+
+/// How long to wait before trying a lost broker again
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How often the client thread checks whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest packet accepted from the broker
+const MAX_PACKET_BYTES: usize = 1024 * 1024;
+
+// MQTT 3.1.1 packet types, already shifted into the fixed header's high nibble
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const SUBSCRIBE: u8 = 0x82;
+const PINGREQ: u8 = 0xC0;
+const DISCONNECT: u8 = 0xE0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct MqttSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub client_id: String,
+    /// Prefix of every topic, e.g. `led/left/brightness`
+    pub base_topic: String,
+    /// Announce entities to Home Assistant under `discovery_prefix`
+    pub discovery: bool,
+    pub discovery_prefix: String,
+    pub publish_interval_secs: u64,
+}
+
+impl Default for MqttSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 1883,
+            username: String::new(),
+            password: String::new(),
+            client_id: "led_controller".to_string(),
+            base_topic: "led".to_string(),
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+            publish_interval_secs: 10,
+        }
+    }
+}
+
+impl MqttSettings {
+    pub fn topic(&self, suffix: &str) -> String {
+        format!("{}/{}", self.base_topic.trim_end_matches('/'), suffix)
+    }
+}
+
+/// Something that happened on the MQTT connection
+#[derive(Debug)]
+pub enum MqttEvent {
+    Connected,
+    Disconnected(String),
+    Command(ApiRequest),
+    /// A message on a command topic that couldn't be understood
+    Rejected(String),
+}
+
+enum Outgoing {
+    Publish { topic: String, payload: Vec<u8>, retain: bool },
+    ConnectionLost,
+}
+
+/// Retained messages the client thread keeps published by itself
+#[derive(Default)]
+struct Retained {
+    /// Sent once after every (re)connect
+    discovery: Vec<(String, String)>,
+    /// Sent after every (re)connect and every publish interval
+    state: Vec<(String, String)>,
+}
+
+/// A background MQTT connection that reconnects by itself; it disconnects
+/// when dropped
+pub struct MqttClient {
+    pub rx: Receiver<MqttEvent>,
+    tx: Sender<Outgoing>,
+    retained: Arc<Mutex<Retained>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl MqttClient {
+    /// Queue a message; dropped silently while disconnected
+    pub fn publish(&self, topic: String, payload: impl Into<Vec<u8>>, retain: bool) {
+        let payload = payload.into();
+        let _ = self.tx.send(Outgoing::Publish { topic, payload, retain });
+    }
+
+    /// Replace the state messages the client thread republishes on its own
+    /// every publish interval, whether or not the GUI is running
+    pub fn set_state(&self, messages: Vec<(String, String)>) {
+        self.retained.lock().unwrap_or_else(|e| e.into_inner()).state = messages;
+    }
+
+    /// Replace the state messages and publish them right away, e.g. after a
+    /// command changed them
+    pub fn publish_state(&self, messages: Vec<(String, String)>) {
+        for (topic, payload) in &messages {
+            self.publish(topic.clone(), payload.clone(), true);
+        }
+        self.set_state(messages);
+    }
+}
+
+impl Drop for MqttClient {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Connect to the broker in `settings` on a background thread, subscribe to
+/// `<base>/+/brightness`, `<base>/+/preset` and `<base>/+/frame`, and pass
+/// commands received there to the app; `notify` is called after every event
+/// so the GUI can wake up. The `discovery` messages are published, retained,
+/// after every connect
+pub fn spawn_client<F>(settings: MqttSettings, discovery: Vec<(String, String)>, notify: F) -> MqttClient
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let (event_tx, event_rx) = mpsc::channel();
+    let (out_tx, out_rx) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let retained = Arc::new(Mutex::new(Retained {
+        discovery,
+        state: Vec::new(),
+    }));
+
+    let stopped = stop.clone();
+    let lost_tx = out_tx.clone();
+    let notify = Arc::new(notify);
+    let session_retained = retained.clone();
+    thread::spawn(move || {
+        while !stopped.load(Ordering::Relaxed) {
+            let reason = match connect(&settings) {
+                Ok(stream) => {
+                    let _ = event_tx.send(MqttEvent::Connected);
+                    notify();
+                    let session = Session {
+                        settings: &settings,
+                        out_rx: &out_rx,
+                        lost_tx: &lost_tx,
+                        event_tx: &event_tx,
+                        retained: &session_retained,
+                        stopped: &stopped,
+                    };
+                    session.run(stream, &notify)
+                }
+                Err(e) => e.to_string(),
+            };
+            if stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            if event_tx.send(MqttEvent::Disconnected(reason)).is_err() {
+                break;
+            }
+            notify();
+
+            let retry_at = Instant::now() + RECONNECT_DELAY;
+            while Instant::now() < retry_at && !stopped.load(Ordering::Relaxed) {
+                thread::sleep(POLL_INTERVAL);
+            }
+            // Forget what was queued for the old connection
+            while out_rx.try_recv().is_ok() {}
+        }
+    });
+
+    MqttClient {
+        rx: event_rx,
+        tx: out_tx,
+        retained,
+        stop,
+    }
+}
+
+/// Open the connection, log in and subscribe to the command topics
+fn connect(settings: &MqttSettings) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect((settings.host.as_str(), settings.port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    write_packet(&mut stream, CONNECT, &connect_body(settings))?;
+
+    let (header, reply) = read_packet(&mut stream)?;
+    if header & 0xF0 != CONNACK || reply.len() < 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected CONNACK"));
+    }
+    if reply[1] != 0 {
+        let reason = match reply[1] {
+            1 => "unacceptable protocol version",
+            2 => "client id rejected",
+            3 => "server unavailable",
+            4 => "bad user name or password",
+            5 => "not authorized",
+            _ => "connection refused",
+        };
+        return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason));
+    }
+
+    write_packet(&mut stream, SUBSCRIBE, &subscribe_body(settings))?;
+
+    publish_packet(&mut stream, &settings.topic("status"), b"online", true)?;
+    // Reads now happen on their own thread, which blocks until data arrives
+    stream.set_read_timeout(None)?;
+    Ok(stream)
+}
+
+/// Variable header and payload of the CONNECT packet: protocol name, level
+/// 4 (3.1.1), flags, keep alive, then client id, will and credentials.
+/// Clean session, with a retained last will of "offline" on the status topic
+fn connect_body(settings: &MqttSettings) -> Vec<u8> {
+    let mut flags = 0x02 | 0x04 | 0x20;
+    if !settings.username.is_empty() {
+        flags |= 0x80;
+        if !settings.password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    put_str(&mut body, &settings.client_id);
+    put_str(&mut body, &settings.topic("status"));
+    put_bytes(&mut body, b"offline");
+    if !settings.username.is_empty() {
+        put_str(&mut body, &settings.username);
+        if !settings.password.is_empty() {
+            put_str(&mut body, &settings.password);
+        }
+    }
+    body
+}
+
+/// SUBSCRIBE to every side's command topics at QoS 0
+fn subscribe_body(settings: &MqttSettings) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&1u16.to_be_bytes()); // packet id
+    for command in ["brightness", "preset", "frame"] {
+        put_str(&mut body, &settings.topic(&format!("+/{}", command)));
+        body.push(0); // QoS 0
+    }
+    body
+}
+
+/// Whether the connection has been quiet long enough to need a PINGREQ;
+/// pinging at half the keep alive leaves the broker plenty of slack
+fn ping_due(last_sent: Instant, now: Instant) -> bool {
+    now.duration_since(last_sent) >= Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2)
+}
+
+/// Pump one connection until it drops
+struct Session<'a> {
+    settings: &'a MqttSettings,
+    out_rx: &'a Receiver<Outgoing>,
+    lost_tx: &'a Sender<Outgoing>,
+    event_tx: &'a Sender<MqttEvent>,
+    retained: &'a Mutex<Retained>,
+    stopped: &'a AtomicBool,
+}
+
+impl Session<'_> {
+    /// Returns why the connection ended
+    fn run<F: Fn() + Send + Sync + 'static>(&self, mut stream: TcpStream, notify: &Arc<F>) -> String {
+        let Ok(mut reader) = stream.try_clone() else {
+            return "could not clone the connection".to_string();
+        };
+        let (lost_tx, event_tx, notify, settings) =
+            (self.lost_tx.clone(), self.event_tx.clone(), notify.clone(), self.settings.clone());
+        thread::spawn(move || {
+            loop {
+                match read_packet(&mut reader) {
+                    Ok((header, body)) if header & 0xF0 == PUBLISH => {
+                        if let Some(event) = parse_publish(&settings, header, &body) {
+                            if event_tx.send(event).is_err() {
+                                break;
+                            }
+                            notify();
+                        }
+                    }
+                    // SUBACK and PINGRESP need no answer
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+            let _ = lost_tx.send(Outgoing::ConnectionLost);
+        });
+
+        let interval = Duration::from_secs(self.settings.publish_interval_secs.max(1));
+        if let Err(e) = self.publish_retained(&mut stream, true) {
+            return e.to_string();
+        }
+        let mut last_state = Instant::now();
+        let mut last_sent = Instant::now();
+        loop {
+            if self.stopped.load(Ordering::Relaxed) {
+                let _ = publish_packet(&mut stream, &self.settings.topic("status"), b"offline", true);
+                let _ = write_packet(&mut stream, DISCONNECT, &[]);
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                return "stopped".to_string();
+            }
+            let result = match self.out_rx.recv_timeout(POLL_INTERVAL) {
+                Ok(Outgoing::Publish { topic, payload, retain }) => publish_packet(&mut stream, &topic, &payload, retain),
+                Ok(Outgoing::ConnectionLost) => return "connection lost".to_string(),
+                Err(RecvTimeoutError::Timeout) if last_state.elapsed() >= interval => {
+                    last_state = Instant::now();
+                    self.publish_retained(&mut stream, false)
+                }
+                Err(RecvTimeoutError::Timeout) if ping_due(last_sent, Instant::now()) => {
+                    write_packet(&mut stream, PINGREQ, &[])
+                }
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return "stopped".to_string(),
+            };
+            if let Err(e) = result {
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                return e.to_string();
+            }
+            last_sent = Instant::now();
+        }
+    }
+
+    /// Publish the latest state, after the discovery messages on connect
+    fn publish_retained(&self, stream: &mut TcpStream, with_discovery: bool) -> io::Result<()> {
+        let messages: Vec<(String, String)> = {
+            let retained = self.retained.lock().unwrap_or_else(|e| e.into_inner());
+            let discovery = retained.discovery.iter().filter(|_| with_discovery);
+            discovery.chain(&retained.state).cloned().collect()
+        };
+        for (topic, payload) in messages {
+            publish_packet(stream, &topic, payload.as_bytes(), true)?;
+        }
+        Ok(())
+    }
+}
+
+/// Turn a message on `<base>/<side>/<command>` into an event
+fn parse_publish(settings: &MqttSettings, header: u8, body: &[u8]) -> Option<MqttEvent> {
+    let topic_len = u16::from_be_bytes([*body.first()?, *body.get(1)?]) as usize;
+    let topic = std::str::from_utf8(body.get(2..2 + topic_len)?).ok()?;
+    // QoS 1 and 2 messages carry a packet id before the payload
+    let qos = (header >> 1) & 0x03;
+    let payload = body.get(2 + topic_len + if qos > 0 { 2 } else { 0 }..)?;
+
+    let rest = topic.strip_prefix(settings.base_topic.trim_end_matches('/'))?.strip_prefix('/')?;
+    let (side, command) = rest.split_once('/')?;
+    let Some(side) = Side::parse(side) else {
+        return Some(MqttEvent::Rejected(format!("{}: unknown side", topic)));
+    };
+    let text = String::from_utf8_lossy(payload).trim().to_string();
+    let request = match command {
+        "brightness" => match text.parse::<u8>() {
+            Ok(level) => ApiRequest::SetBrightness(side, level),
+            Err(_) => return Some(MqttEvent::Rejected(format!("{}: brightness must be 0-255", topic))),
+        },
+        "preset" => ApiRequest::SetPreset(side, text),
+        "frame" => match decode_frame(None, payload) {
+            Ok(frame) => ApiRequest::PushFrame(side, frame),
+            Err(e) => return Some(MqttEvent::Rejected(format!("{}: {}", topic, e))),
+        },
+        _ => return None,
+    };
+    Some(MqttEvent::Command(request))
+}
+
+/// Home Assistant discovery messages (topic, retained JSON payload) for a
+/// brightness number and preset select per side plus the metric sensors.
+/// The select always offers `api_frame`, which frames pushed over MQTT or
+/// HTTP switch a side to, so Home Assistant accepts that state
+pub fn discovery_messages(settings: &MqttSettings, presets: &[String]) -> Vec<(String, String)> {
+    let mut options = presets.to_vec();
+    if !options.iter().any(|preset| preset == "api_frame") {
+        options.push("api_frame".to_string());
+    }
+    let device = json!({
+        "identifiers": [settings.client_id],
+        "name": "Framework LED Matrix",
+        "manufacturer": "Framework",
+    });
+    let availability = settings.topic("status");
+    let object_id = |name: &str| format!("{}_{}", settings.client_id, name);
+    let config_topic = |component: &str, name: &str| {
+        format!("{}/{}/{}/config", settings.discovery_prefix, component, object_id(name))
+    };
+
+    let mut messages = Vec::new();
+    for side in ["left", "right"] {
+        let state_topic = settings.topic(&format!("{}/state", side));
+        let number = json!({
+            "name": format!("{} brightness", side),
+            "unique_id": object_id(&format!("{}_brightness", side)),
+            "command_topic": settings.topic(&format!("{}/brightness", side)),
+            "state_topic": state_topic,
+            "value_template": "{{ value_json.brightness }}",
+            "min": 0,
+            "max": 255,
+            "availability_topic": availability,
+            "device": device,
+        });
+        messages.push((config_topic("number", &format!("{}_brightness", side)), number.to_string()));
+
+        let select = json!({
+            "name": format!("{} preset", side),
+            "unique_id": object_id(&format!("{}_preset", side)),
+            "command_topic": settings.topic(&format!("{}/preset", side)),
+            "state_topic": state_topic,
+            "value_template": "{{ value_json.preset }}",
+            "options": options,
+            "availability_topic": availability,
+            "device": device,
+        });
+        messages.push((config_topic("select", &format!("{}_preset", side)), select.to_string()));
+    }
+
+    for (metric, name, unit) in [
+        ("cpu", "CPU usage", "%"),
+        ("ram", "RAM usage", "%"),
+        ("battery", "Battery", "%"),
+        ("temperature", "Temperature", "°C"),
+    ] {
+        let sensor = json!({
+            "name": name,
+            "unique_id": object_id(metric),
+            "state_topic": settings.topic("metrics"),
+            "value_template": format!("{{{{ value_json.{} }}}}", metric),
+            "unit_of_measurement": unit,
+            "availability_topic": availability,
+            "device": device,
+        });
+        messages.push((config_topic("sensor", metric), sensor.to_string()));
+    }
+    messages
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_bytes(buf, s.as_bytes());
+}
+
+fn publish_packet(stream: &mut impl Write, topic: &str, payload: &[u8], retain: bool) -> io::Result<()> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    put_str(&mut body, topic);
+    body.extend_from_slice(payload);
+    write_packet(stream, PUBLISH | retain as u8, &body)
+}
+
+fn write_packet(stream: &mut impl Write, header: u8, body: &[u8]) -> io::Result<()> {
+    let mut packet = vec![header];
+    put_remaining_length(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    stream.write_all(&packet)
+}
+
+/// Remaining length: 7 bits per byte, high bit set when more follow
+fn put_remaining_length(buf: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+fn read_packet(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte)?;
+    let header = byte[0];
+
+    let mut len = 0usize;
+    for shift in 0..4 {
+        stream.read_exact(&mut byte)?;
+        len |= ((byte[0] & 0x7F) as usize) << (7 * shift);
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    if len > MAX_PACKET_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too large"));
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body)?;
+    Ok((header, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    fn remaining_length(len: usize) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_remaining_length(&mut buf, len);
+        buf
+    }
+
+    #[test]
+    fn remaining_length_uses_seven_bits_per_byte() {
+        assert_eq!(remaining_length(0), [0x00]);
+        assert_eq!(remaining_length(127), [0x7F]);
+        assert_eq!(remaining_length(128), [0x80, 0x01]);
+        assert_eq!(remaining_length(16_383), [0xFF, 0x7F]);
+        assert_eq!(remaining_length(16_384), [0x80, 0x80, 0x01]);
+        assert_eq!(remaining_length(2_097_151), [0xFF, 0xFF, 0x7F]);
+        assert_eq!(remaining_length(2_097_152), [0x80, 0x80, 0x80, 0x01]);
+        assert_eq!(remaining_length(268_435_455), [0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn packets_read_back_what_was_written() {
+        for len in [0, 5, 127, 128, 300, 20_000] {
+            let body: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut wire = Vec::new();
+            write_packet(&mut wire, PUBLISH, &body).unwrap();
+            assert_eq!(read_packet(&mut Cursor::new(wire)).unwrap(), (PUBLISH, body));
+        }
+    }
+
+    #[test]
+    fn oversized_and_truncated_packets_are_errors() {
+        let mut wire = vec![PUBLISH];
+        put_remaining_length(&mut wire, MAX_PACKET_BYTES + 1);
+        assert!(read_packet(&mut Cursor::new(wire)).is_err());
+
+        let mut wire = Vec::new();
+        write_packet(&mut wire, PUBLISH, b"0123456789").unwrap();
+        wire.truncate(6);
+        assert!(read_packet(&mut Cursor::new(wire)).is_err());
+    }
+
+    #[test]
+    fn connect_carries_will_keep_alive_and_credentials() {
+        let settings = MqttSettings {
+            client_id: "desk".to_string(),
+            username: "user".to_string(),
+            password: "pw".to_string(),
+            ..MqttSettings::default()
+        };
+        let mut expected = vec![0, 4, b'M', b'Q', b'T', b'T', 4, 0xE6, 0, 30];
+        expected.extend([0, 4, b'd', b'e', b's', b'k']);
+        expected.extend([0, 10]);
+        expected.extend(b"led/status");
+        expected.extend([0, 7]);
+        expected.extend(b"offline");
+        expected.extend([0, 4, b'u', b's', b'e', b'r', 0, 2, b'p', b'w']);
+        assert_eq!(connect_body(&settings), expected);
+
+        // Without a user name neither credential is sent
+        let anonymous = MqttSettings {
+            password: "ignored".to_string(),
+            ..MqttSettings::default()
+        };
+        let body = connect_body(&anonymous);
+        assert_eq!(body[7], 0x26);
+        assert!(body.ends_with(b"offline"));
+    }
+
+    #[test]
+    fn subscribe_asks_for_every_command_topic() {
+        let settings = MqttSettings {
+            base_topic: "home/matrix/".to_string(),
+            ..MqttSettings::default()
+        };
+        let mut expected = vec![0, 1];
+        for topic in ["home/matrix/+/brightness", "home/matrix/+/preset", "home/matrix/+/frame"] {
+            expected.extend((topic.len() as u16).to_be_bytes());
+            expected.extend(topic.as_bytes());
+            expected.push(0);
+        }
+        assert_eq!(subscribe_body(&settings), expected);
+    }
+
+    #[test]
+    fn publish_sets_the_retain_flag() {
+        let mut wire = Vec::new();
+        publish_packet(&mut wire, "led/metrics", b"{}", true).unwrap();
+        let (header, body) = read_packet(&mut Cursor::new(wire)).unwrap();
+        assert_eq!(header, PUBLISH | 1);
+        assert_eq!(&body[..2], [0, 11]);
+        assert_eq!(&body[2..13], b"led/metrics");
+        assert_eq!(&body[13..], b"{}");
+    }
+
+    #[test]
+    fn pings_at_half_the_keep_alive() {
+        let start = Instant::now();
+        let half = Duration::from_secs(KEEP_ALIVE_SECS as u64 / 2);
+        assert!(!ping_due(start, start));
+        assert!(!ping_due(start, start + half - Duration::from_millis(1)));
+        assert!(ping_due(start, start + half));
+    }
+
+    fn publish_body(topic: &str, payload: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        put_str(&mut body, topic);
+        body.extend_from_slice(payload);
+        body
+    }
+
+    #[test]
+    fn parses_commands_on_the_command_topics() {
+        let settings = MqttSettings::default();
+        let event = |topic: &str, payload: &[u8]| parse_publish(&settings, PUBLISH, &publish_body(topic, payload));
+
+        assert!(matches!(
+            event("led/left/brightness", b" 128 "),
+            Some(MqttEvent::Command(ApiRequest::SetBrightness(Side::Left, 128)))
+        ));
+        assert!(matches!(
+            event("led/right/preset", b"clock"),
+            Some(MqttEvent::Command(ApiRequest::SetPreset(Side::Right, preset))) if preset == "clock"
+        ));
+        assert!(matches!(event("led/left/brightness", b"300"), Some(MqttEvent::Rejected(_))));
+        assert!(matches!(event("led/middle/preset", b"clock"), Some(MqttEvent::Rejected(_))));
+        assert!(event("other/left/preset", b"clock").is_none());
+
+        // QoS 1 puts a packet id between topic and payload
+        let mut body = Vec::new();
+        put_str(&mut body, "led/left/brightness");
+        body.extend([0, 9]);
+        body.extend(b"42");
+        assert!(matches!(
+            parse_publish(&settings, PUBLISH | 0x02, &body),
+            Some(MqttEvent::Command(ApiRequest::SetBrightness(Side::Left, 42)))
+        ));
+    }
+
+    #[test]
+    fn discovery_select_offers_api_frame() {
+        let settings = MqttSettings::default();
+        let messages = discovery_messages(&settings, &["clock".to_string(), "cpu".to_string()]);
+        let select = messages
+            .iter()
+            .find(|(topic, _)| topic == "homeassistant/select/led_controller_left_preset/config")
+            .map(|(_, payload)| serde_json::from_str::<serde_json::Value>(payload).unwrap())
+            .unwrap();
+        assert_eq!(select["options"], json!(["clock", "cpu", "api_frame"]));
+
+        let messages = discovery_messages(&settings, &["api_frame".to_string()]);
+        assert!(messages.iter().all(|(_, payload)| payload.matches("api_frame").count() <= 1));
+    }
+
+    /// Plays the broker's side of a session: CONNECT, SUBSCRIBE, then the
+    /// retained messages the client publishes by itself
+    #[test]
+    fn client_publishes_discovery_and_state_without_the_gui() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let settings = MqttSettings {
+            port: listener.local_addr().unwrap().port(),
+            publish_interval_secs: 1,
+            ..MqttSettings::default()
+        };
+        let discovery = vec![("homeassistant/sensor/x/config".to_string(), "{}".to_string())];
+        let client = spawn_client(settings.clone(), discovery, || {});
+        client.set_state(vec![("led/metrics".to_string(), r#"{"cpu":1}"#.to_string())]);
+
+        let (mut broker, _) = listener.accept().unwrap();
+        broker.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (header, body) = read_packet(&mut broker).unwrap();
+        assert_eq!((header, body), (CONNECT, connect_body(&settings)));
+        write_packet(&mut broker, CONNACK, &[0, 0]).unwrap();
+        assert_eq!(read_packet(&mut broker).unwrap(), (SUBSCRIBE, subscribe_body(&settings)));
+
+        let mut next_publish = || {
+            let (header, body) = read_packet(&mut broker).unwrap();
+            assert_eq!(header, PUBLISH | 1, "retained publish");
+            let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+            let topic = String::from_utf8(body[2..2 + topic_len].to_vec()).unwrap();
+            (topic, String::from_utf8(body[2 + topic_len..].to_vec()).unwrap())
+        };
+        assert_eq!(next_publish(), ("led/status".to_string(), "online".to_string()));
+        assert_eq!(next_publish().0, "homeassistant/sensor/x/config");
+        assert_eq!(next_publish(), ("led/metrics".to_string(), r#"{"cpu":1}"#.to_string()));
+
+        // Republished on the interval with whatever the state is by then
+        client.set_state(vec![("led/metrics".to_string(), r#"{"cpu":2}"#.to_string())]);
+        assert_eq!(next_publish(), ("led/metrics".to_string(), r#"{"cpu":2}"#.to_string()));
+
+        assert!(matches!(client.rx.recv_timeout(Duration::from_secs(1)), Ok(MqttEvent::Connected)));
+        drop(client);
+    }
+}
@@ -0,0 +1,493 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::alerts::AlertTarget;
+use crate::utils::{render_notification_icon, render_vertical_text, MATRIX_HEIGHT, TEXT_LINE_HEIGHT};
+
+/// Match rule for the desktop's `Notify` calls
+const NOTIFY_MATCH: &str = "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+
+/// How fast scrolling text moves up the matrix
+const SCROLL_ROWS_PER_SEC: f32 = 12.0;
+
+/// Animation step of the blinking envelope icon
+const ICON_BLINK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A desktop notification as sent to `org.freedesktop.Notifications.Notify`
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub app_name: String,
+    pub summary: String,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+/// The `urgency` hint of a notification
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => Urgency::Low,
+            2 => Urgency::Critical,
+            _ => Urgency::Normal,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Urgency::Low => "low",
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        }
+    }
+}
+
+/// Picks `Notify` calls out of `dbus-monitor` output, one line at a time
+#[derive(Default)]
+pub struct NotifyCallParser {
+    in_call: bool,
+    /// Top level string arguments: app name, icon, summary, body
+    strings: Vec<String>,
+    /// A top level string that continues on the next line
+    open_string: Option<String>,
+    /// Key of the dict entry being read, for picking out hints
+    hint_key: Option<String>,
+    urgency: Urgency,
+}
+
+impl NotifyCallParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one line of output; returns a notification once the call's last
+    /// argument, the expiry timeout, has been seen
+    ///
+    /// A call looks like:
+    /// ```text
+    /// method call time=1700000000.1 sender=:1.42 -> destination=:1.10 serial=7 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+    ///    string "notify-send"
+    ///    uint32 0
+    ///    string ""
+    ///    string "Summary"
+    ///    string "Body that can
+    /// span lines"
+    ///    array [
+    ///       string "default"
+    ///       string "Open"
+    ///    ]
+    ///    array [
+    ///       dict entry(
+    ///          string "urgency"
+    ///          variant             byte 2
+    ///       )
+    ///    ]
+    ///    int32 -1
+    /// ```
+    pub fn feed(&mut self, line: &str) -> Option<Notification> {
+        if let Some(open) = &mut self.open_string {
+            // dbus-monitor prints newlines in strings as they are
+            open.push('\n');
+            match line.strip_suffix('"') {
+                Some(end) => {
+                    open.push_str(end);
+                    let value = self.open_string.take().unwrap_or_default();
+                    self.strings.push(value);
+                }
+                None => open.push_str(line),
+            }
+            return None;
+        }
+        if !line.starts_with(' ') {
+            // Message header: only Notify calls are interesting
+            self.in_call = line.starts_with("method call") && line.contains("member=Notify");
+            self.strings.clear();
+            self.hint_key = None;
+            self.urgency = Urgency::default();
+            return None;
+        }
+        if !self.in_call {
+            return None;
+        }
+
+        // Top level arguments are indented by exactly three spaces, the
+        // contents of the actions and hints arrays further
+        if let Some(rest) = line.strip_prefix("   ").filter(|rest| !rest.starts_with(' ')) {
+            if let Some(value) = rest.strip_prefix("string \"") {
+                match value.strip_suffix('"') {
+                    Some(value) => self.strings.push(value.to_string()),
+                    None => self.open_string = Some(value.to_string()),
+                }
+            } else if rest.starts_with("int32 ") && self.strings.len() >= 4 {
+                return Some(self.finish());
+            }
+            return None;
+        }
+
+        let nested = line.trim_start();
+        if let Some(key) = nested.strip_prefix("string \"") {
+            self.hint_key = Some(key.trim_end_matches('"').to_string());
+        } else if nested.starts_with("variant") && self.hint_key.as_deref() == Some("urgency") {
+            if let Some(byte) = nested.split_whitespace().last().and_then(|v| v.parse().ok()) {
+                self.urgency = Urgency::from_byte(byte);
+            }
+        }
+        None
+    }
+
+    fn finish(&mut self) -> Notification {
+        self.in_call = false;
+        let mut strings = std::mem::take(&mut self.strings).into_iter();
+        let app_name = strings.next().unwrap_or_default();
+        let _icon = strings.next();
+        Notification {
+            app_name,
+            summary: strings.next().unwrap_or_default(),
+            body: strings.next().unwrap_or_default(),
+            urgency: std::mem::take(&mut self.urgency),
+        }
+    }
+}
+
+/// `dbus-monitor` watching for `Notify` calls on the bus at `address`, or
+/// the session bus from `DBUS_SESSION_BUS_ADDRESS` for `None`, so a private
+/// bus (e.g. under `dbus-run-session`) works for testing
+pub fn notify_monitor(address: Option<&str>) -> Command {
+    let mut command = Command::new("dbus-monitor");
+    match address {
+        Some(address) => command.args(["--address", address]),
+        None => command.arg("--session"),
+    };
+    command.arg(NOTIFY_MATCH);
+    command
+}
+
+/// Run `monitor` (usually [`notify_monitor`]) and parse its output on a
+/// background thread; `notify` is called after every notification so the
+/// GUI can wake up
+pub fn spawn_notification_monitor<F>(mut monitor: Command, notify: F) -> std::io::Result<Receiver<Notification>>
+where
+    F: Fn() + Send + 'static,
+{
+    let mut child = monitor.stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut parser = NotifyCallParser::new();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(notification) = parser.feed(&line) {
+                if tx.send(notification).is_err() {
+                    break;
+                }
+                notify();
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+
+    Ok(rx)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum NotificationStyle {
+    /// Blink an envelope
+    Icon,
+    /// Scroll the summary up the matrix
+    Scroll,
+}
+
+impl NotificationStyle {
+    pub const ALL: [NotificationStyle; 2] = [NotificationStyle::Icon, NotificationStyle::Scroll];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NotificationStyle::Icon => "Flash icon",
+            NotificationStyle::Scroll => "Scroll summary",
+        }
+    }
+}
+
+/// How `NotificationSettings::apps` is applied
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum AppFilterMode {
+    AllApps,
+    OnlyListed,
+    AllExceptListed,
+}
+
+impl AppFilterMode {
+    pub const ALL: [AppFilterMode; 3] = [
+        AppFilterMode::AllApps,
+        AppFilterMode::OnlyListed,
+        AppFilterMode::AllExceptListed,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AppFilterMode::AllApps => "All apps",
+            AppFilterMode::OnlyListed => "Only listed apps",
+            AppFilterMode::AllExceptListed => "All except listed apps",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub do_not_disturb: bool,
+    pub target: AlertTarget,
+    pub style: NotificationStyle,
+    /// How long the icon blinks; scrolling text stays until it has passed
+    pub icon_secs: u64,
+    pub filter_mode: AppFilterMode,
+    /// Application names, compared case-insensitively
+    pub apps: Vec<String>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            do_not_disturb: false,
+            target: AlertTarget::Right,
+            style: NotificationStyle::Scroll,
+            icon_secs: 5,
+            filter_mode: AppFilterMode::AllApps,
+            apps: Vec::new(),
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Whether a notification from `app_name` should be shown
+    pub fn allows(&self, app_name: &str) -> bool {
+        if !self.enabled || self.do_not_disturb {
+            return false;
+        }
+        let listed = self.apps.iter().any(|app| app.trim().eq_ignore_ascii_case(app_name));
+        match self.filter_mode {
+            AppFilterMode::AllApps => true,
+            AppFilterMode::OnlyListed => listed,
+            AppFilterMode::AllExceptListed => !listed,
+        }
+    }
+}
+
+/// A notification being shown on one side
+pub struct NotificationBanner {
+    text: String,
+    style: NotificationStyle,
+    icon_duration: Duration,
+    started: Instant,
+}
+
+impl NotificationBanner {
+    pub fn new(notification: &Notification, settings: &NotificationSettings, now: Instant) -> Self {
+        let text = if notification.summary.trim().is_empty() {
+            &notification.app_name
+        } else {
+            &notification.summary
+        };
+        Self {
+            text: text.trim().to_string(),
+            style: settings.style,
+            icon_duration: Duration::from_secs(settings.icon_secs.max(1)),
+            started: now,
+        }
+    }
+
+    /// Frame to show at `now`, or `None` once the banner is done
+    pub fn frame(&self, now: Instant) -> Option<Vec<u8>> {
+        let elapsed = now.duration_since(self.started);
+        match self.style {
+            NotificationStyle::Icon => {
+                if elapsed >= self.icon_duration {
+                    return None;
+                }
+                let step = (elapsed.as_millis() / ICON_BLINK_INTERVAL.as_millis()) as u8;
+                Some(render_notification_icon(step))
+            }
+            NotificationStyle::Scroll => {
+                // Text enters at the bottom and leaves through the top
+                let scrolled = (elapsed.as_secs_f32() * SCROLL_ROWS_PER_SEC) as i32;
+                let offset = MATRIX_HEIGHT as i32 - scrolled;
+                let text_height = (self.text.chars().count() * TEXT_LINE_HEIGHT) as i32;
+                if offset + text_height <= 0 {
+                    return None;
+                }
+                Some(render_vertical_text(&self.text, offset, 255))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dbus-monitor` output around a `notify-send -u critical -A default=Open`
+    /// call with a two line body, between unrelated messages
+    const CAPTURED: &str = r#"signal time=1700000000.100000 sender=org.freedesktop.DBus -> destination=:1.91 serial=2 path=/org/freedesktop/DBus; interface=org.freedesktop.DBus; member=NameAcquired
+   string ":1.91"
+method call time=1700000001.200000 sender=:1.93 -> destination=:1.25 serial=7 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "notify-send"
+   uint32 0
+   string "dialog-warning"
+   string "Disk almost full"
+   string "Only 2 GB left on /home
+Empty the trash to free space"
+   array [
+      string "default"
+      string "Open"
+   ]
+   array [
+      dict entry(
+         string "urgency"
+         variant             byte 2
+      )
+      dict entry(
+         string "sender-pid"
+         variant             int64 4242
+      )
+   ]
+   int32 -1
+method return time=1700000001.210000 sender=:1.25 -> destination=:1.93 serial=31 reply_serial=7
+   uint32 12
+"#;
+
+    fn parse(output: &str) -> Vec<Notification> {
+        let mut parser = NotifyCallParser::new();
+        output.lines().filter_map(|line| parser.feed(line)).collect()
+    }
+
+    #[test]
+    fn parses_a_notify_call_with_hints_and_actions() {
+        let notifications = parse(CAPTURED);
+        assert_eq!(notifications.len(), 1);
+        let notification = &notifications[0];
+        assert_eq!(notification.app_name, "notify-send");
+        assert_eq!(notification.summary, "Disk almost full");
+        assert_eq!(notification.body, "Only 2 GB left on /home\nEmpty the trash to free space");
+        assert_eq!(notification.urgency, Urgency::Critical);
+    }
+
+    #[test]
+    fn urgency_defaults_to_normal_without_the_hint() {
+        let output = r#"method call time=1700000002.0 sender=:1.50 -> destination=:1.25 serial=3 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "Thunderbird"
+   uint32 0
+   string ""
+   string "New mail"
+   string ""
+   array [
+   ]
+   array [
+      dict entry(
+         string "desktop-entry"
+         variant             string "thunderbird"
+      )
+   ]
+   int32 5000
+"#;
+        let notifications = parse(output);
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].app_name, "Thunderbird");
+        assert_eq!(notifications[0].summary, "New mail");
+        assert_eq!(notifications[0].body, "");
+        assert_eq!(notifications[0].urgency, Urgency::Normal);
+    }
+
+    #[test]
+    fn parses_consecutive_calls_independently() {
+        let low = CAPTURED.replace("byte 2", "byte 0").replace("Disk almost full", "Backup done");
+        let notifications = parse(&format!("{}{}", low, CAPTURED));
+        assert_eq!(notifications.len(), 2);
+        assert_eq!(notifications[0].summary, "Backup done");
+        assert_eq!(notifications[0].urgency, Urgency::Low);
+        assert_eq!(notifications[1].summary, "Disk almost full");
+        assert_eq!(notifications[1].urgency, Urgency::Critical);
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let output = r#"method call time=1700000003.0 sender=:1.50 -> destination=:1.25 serial=4 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=CloseNotification
+   uint32 12
+signal time=1700000003.1 sender=:1.25 -> destination=(null destination) serial=40 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=NotificationClosed
+   uint32 12
+   uint32 3
+"#;
+        assert!(parse(output).is_empty());
+    }
+
+    #[test]
+    fn a_truncated_call_is_dropped_at_the_next_header() {
+        let truncated: String = CAPTURED.lines().take(9).map(|line| format!("{}\n", line)).collect();
+        let notifications = parse(&format!("{}{}", truncated, CAPTURED));
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].summary, "Disk almost full");
+    }
+
+    /// Sends a real `Notify` call over a private bus and reads it back
+    /// through `dbus-monitor`
+    #[test]
+    #[ignore = "needs dbus-daemon, dbus-monitor and dbus-send"]
+    fn monitors_a_private_bus() {
+        use std::io::Read;
+
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("dbus-daemon runs");
+        let mut address = String::new();
+        let mut stdout = daemon.stdout.take().unwrap();
+        let mut byte = [0];
+        while stdout.read(&mut byte).unwrap() == 1 && byte[0] != b'\n' {
+            address.push(byte[0] as char);
+        }
+
+        let rx = spawn_notification_monitor(notify_monitor(Some(&address)), || {}).unwrap();
+        thread::sleep(Duration::from_millis(500));
+        let status = Command::new("dbus-send")
+            .arg(format!("--address={}", address))
+            .args([
+                "--type=method_call",
+                "--dest=org.freedesktop.Notifications",
+                "/org/freedesktop/Notifications",
+                "org.freedesktop.Notifications.Notify",
+                "string:backup",
+                "uint32:0",
+                "string:",
+                "string:Backup finished",
+                "string:12 files\nin 3 folders",
+                "array:string:default,Open",
+                "dict:string:string:category,transfer.complete",
+                "int32:-1",
+            ])
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let notification = rx.recv_timeout(Duration::from_secs(5)).expect("notification arrives");
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+        assert_eq!(notification.app_name, "backup");
+        assert_eq!(notification.summary, "Backup finished");
+        assert_eq!(notification.body, "12 files\nin 3 folders");
+        assert_eq!(notification.urgency, Urgency::Normal);
+    }
+}
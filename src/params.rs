@@ -0,0 +1,235 @@
+// src/params.rs
+//! Typed options for presets. A preset describes its parameters once and
+//! gets egui controls and per-side persistence for free.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug)]
+pub enum ParamKind {
+    /// Number typed or dragged in, kept within the bounds
+    Number { min: f64, max: f64 },
+    /// Number picked with a slider
+    Range { min: f64, max: f64 },
+    /// One of a fixed set of options, stored by label
+    Choice(Vec<&'static str>),
+    /// LED brightness, 0-255
+    Level,
+    Toggle,
+    Text { max_len: usize },
+}
+
+/// A stored parameter value; JSON numbers, booleans and strings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParamValue {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+/// One parameter of a preset
+#[derive(Clone, Debug)]
+pub struct ParamSpec {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: ParamKind,
+    pub default: ParamValue,
+}
+
+impl ParamSpec {
+    pub fn number(key: &'static str, label: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self { key, label, kind: ParamKind::Number { min, max }, default: ParamValue::Number(default) }
+    }
+
+    pub fn range(key: &'static str, label: &'static str, min: f64, max: f64, default: f64) -> Self {
+        Self { key, label, kind: ParamKind::Range { min, max }, default: ParamValue::Number(default) }
+    }
+
+    pub fn choice(key: &'static str, label: &'static str, options: Vec<&'static str>, default: &str) -> Self {
+        Self { key, label, kind: ParamKind::Choice(options), default: ParamValue::Text(default.to_string()) }
+    }
+
+    pub fn level(key: &'static str, label: &'static str, default: u8) -> Self {
+        Self { key, label, kind: ParamKind::Level, default: ParamValue::Number(default as f64) }
+    }
+
+    pub fn toggle(key: &'static str, label: &'static str, default: bool) -> Self {
+        Self { key, label, kind: ParamKind::Toggle, default: ParamValue::Bool(default) }
+    }
+
+    pub fn text(key: &'static str, label: &'static str, max_len: usize, default: &str) -> Self {
+        Self { key, label, kind: ParamKind::Text { max_len }, default: ParamValue::Text(default.to_string()) }
+    }
+
+    /// A stored value coerced to this parameter's type and bounds; anything
+    /// missing or unusable (e.g. from an older version) becomes the default
+    fn sanitize(&self, value: Option<&ParamValue>) -> ParamValue {
+        match (&self.kind, value) {
+            (ParamKind::Number { min, max } | ParamKind::Range { min, max }, Some(ParamValue::Number(n))) => {
+                ParamValue::Number(n.clamp(*min, *max))
+            }
+            (ParamKind::Level, Some(ParamValue::Number(n))) => ParamValue::Number(n.clamp(0.0, 255.0).round()),
+            (ParamKind::Choice(options), Some(ParamValue::Text(s))) if options.contains(&s.as_str()) => {
+                ParamValue::Text(s.clone())
+            }
+            (ParamKind::Toggle, Some(ParamValue::Bool(b))) => ParamValue::Bool(*b),
+            (ParamKind::Text { max_len }, Some(ParamValue::Text(s))) => ParamValue::Text(s.chars().take(*max_len).collect()),
+            _ => self.default.clone(),
+        }
+    }
+}
+
+/// Parameter values for one preset on one side, by key
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PresetParams(BTreeMap<String, ParamValue>);
+
+impl PresetParams {
+    /// Stored values checked against a schema, with defaults for the rest
+    pub fn resolve(schema: &[ParamSpec], stored: Option<&PresetParams>) -> Self {
+        Self(
+            schema
+                .iter()
+                .map(|spec| (spec.key.to_string(), spec.sanitize(stored.and_then(|p| p.0.get(spec.key)))))
+                .collect(),
+        )
+    }
+
+    pub fn number(&self, key: &str) -> f64 {
+        match self.0.get(key) {
+            Some(ParamValue::Number(n)) => *n,
+            _ => 0.0,
+        }
+    }
+
+    pub fn level(&self, key: &str) -> u8 {
+        self.number(key).clamp(0.0, 255.0) as u8
+    }
+
+    pub fn flag(&self, key: &str) -> bool {
+        matches!(self.0.get(key), Some(ParamValue::Bool(true)))
+    }
+
+    /// Text and choice values
+    pub fn text(&self, key: &str) -> &str {
+        match self.0.get(key) {
+            Some(ParamValue::Text(s)) => s,
+            _ => "",
+        }
+    }
+}
+
+/// Controls for every parameter in a schema; returns true when a value changed
+pub fn params_editor(ui: &mut egui::Ui, schema: &[ParamSpec], params: &mut PresetParams) -> bool {
+    let mut changed = false;
+    for spec in schema {
+        let value = params.0.entry(spec.key.to_string()).or_insert_with(|| spec.default.clone());
+        ui.horizontal(|ui| match (&spec.kind, value) {
+            (ParamKind::Number { min, max }, ParamValue::Number(n)) => {
+                ui.label(spec.label);
+                changed |= ui.add(egui::DragValue::new(n).clamp_range(*min..=*max)).changed();
+            }
+            (ParamKind::Range { min, max }, ParamValue::Number(n)) => {
+                changed |= ui.add(egui::Slider::new(n, *min..=*max).text(spec.label)).changed();
+            }
+            (ParamKind::Level, ParamValue::Number(n)) => {
+                // Swatch showing roughly how bright the LEDs will be
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+                let grey = *n as u8;
+                ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(grey, grey, grey));
+                changed |= ui.add(egui::Slider::new(n, 0.0..=255.0).step_by(1.0).text(spec.label)).changed();
+            }
+            (ParamKind::Choice(options), ParamValue::Text(s)) => {
+                ui.label(spec.label);
+                egui::ComboBox::from_id_source(spec.key).selected_text(s.as_str()).show_ui(ui, |ui| {
+                    for option in options {
+                        changed |= ui.selectable_value(s, option.to_string(), *option).changed();
+                    }
+                });
+            }
+            (ParamKind::Toggle, ParamValue::Bool(b)) => {
+                changed |= ui.checkbox(b, spec.label).changed();
+            }
+            (ParamKind::Text { max_len }, ParamValue::Text(s)) => {
+                ui.label(spec.label);
+                changed |= ui.add(egui::TextEdit::singleline(s).char_limit(*max_len)).changed();
+            }
+            // Only reachable if a value skipped `resolve`
+            (_, value) => *value = spec.default.clone(),
+        });
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Vec<ParamSpec> {
+        vec![
+            ParamSpec::number("speed", "Speed", 1.0, 10.0, 5.0),
+            ParamSpec::range("scale", "Scale", 0.0, 1.0, 0.5),
+            ParamSpec::choice("style", "Style", vec!["bars", "dots"], "bars"),
+            ParamSpec::level("glow", "Glow", 120),
+            ParamSpec::toggle("invert", "Invert", false),
+            ParamSpec::text("message", "Message", 5, "hi"),
+        ]
+    }
+
+    fn stored(json: &str) -> PresetParams {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn missing_keys_get_defaults() {
+        for params in [PresetParams::resolve(&schema(), None), PresetParams::resolve(&schema(), Some(&stored("{}")))] {
+            assert_eq!(params.number("speed"), 5.0);
+            assert_eq!(params.number("scale"), 0.5);
+            assert_eq!(params.text("style"), "bars");
+            assert_eq!(params.level("glow"), 120);
+            assert!(!params.flag("invert"));
+            assert_eq!(params.text("message"), "hi");
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        let cases = [
+            (r#"{"speed": 50}"#, "speed", 10.0),
+            (r#"{"speed": -3}"#, "speed", 1.0),
+            (r#"{"scale": 2.5}"#, "scale", 1.0),
+            (r#"{"glow": 300}"#, "glow", 255.0),
+            (r#"{"glow": -1}"#, "glow", 0.0),
+            (r#"{"glow": 99.6}"#, "glow", 100.0),
+            (r#"{"speed": 7.5}"#, "speed", 7.5),
+        ];
+        for (json, key, expected) in cases {
+            let params = PresetParams::resolve(&schema(), Some(&stored(json)));
+            assert_eq!(params.number(key), expected, "{}", json);
+        }
+    }
+
+    #[test]
+    fn unusable_values_fall_back_to_defaults() {
+        let params = PresetParams::resolve(
+            &schema(),
+            Some(&stored(r#"{"style": "stripes", "speed": "fast", "invert": 1, "message": true}"#)),
+        );
+        assert_eq!(params.text("style"), "bars");
+        assert_eq!(params.number("speed"), 5.0);
+        assert!(!params.flag("invert"));
+        assert_eq!(params.text("message"), "hi");
+
+        let params = PresetParams::resolve(&schema(), Some(&stored(r#"{"style": "dots", "invert": true}"#)));
+        assert_eq!(params.text("style"), "dots");
+        assert!(params.flag("invert"));
+    }
+
+    #[test]
+    fn text_is_cut_and_unknown_keys_dropped() {
+        let params = PresetParams::resolve(&schema(), Some(&stored(r#"{"message": "hello world", "retired": 3}"#)));
+        assert_eq!(params.text("message"), "hello");
+        assert_eq!(params.0.len(), schema().len());
+        assert!(!params.0.contains_key("retired"));
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::transition::TransitionKind;
+
+/// One step of a playlist: a preset id (built-in or custom), how long to
+/// show it and optionally the transition into it instead of the side's default
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlaylistEntry {
+    pub preset: String,
+    pub dwell_secs: u64,
+    #[serde(default)]
+    pub transition: Option<TransitionKind>,
+}
+
+impl Default for PlaylistEntry {
+    fn default() -> Self {
+        Self {
+            preset: "clock".to_string(),
+            dwell_secs: 30,
+            transition: None,
+        }
+    }
+}
+
+/// An ordered list of presets a side cycles through
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Playlist {
+    pub enabled: bool,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// Position within a playlist; kept outside `Playlist` so the persisted
+/// configuration doesn't carry playback state
+#[derive(Default)]
+pub struct PlaylistCursor {
+    index: usize,
+    entered_at: Option<Instant>,
+}
+
+impl PlaylistCursor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start again from the first entry
+    pub fn restart(&mut self) {
+        self.index = 0;
+        self.entered_at = None;
+    }
+
+    /// The entry currently being shown, without advancing
+    pub fn peek<'a>(&self, playlist: &'a Playlist) -> Option<&'a PlaylistEntry> {
+        if !playlist.enabled {
+            return None;
+        }
+        playlist.entries.get(self.index)
+    }
+
+    /// The entry to show at `now`, advancing past entries whose dwell time
+    /// is up. Returns `None` when the playlist is disabled or empty
+    pub fn current<'a>(&mut self, playlist: &'a Playlist, now: Instant) -> Option<&'a PlaylistEntry> {
+        if !playlist.enabled || playlist.entries.is_empty() {
+            self.restart();
+            return None;
+        }
+        if self.index >= playlist.entries.len() {
+            self.restart();
+        }
+
+        let entered_at = *self.entered_at.get_or_insert(now);
+        let dwell = Duration::from_secs(playlist.entries[self.index].dwell_secs.max(1));
+        if now.duration_since(entered_at) >= dwell {
+            self.index = (self.index + 1) % playlist.entries.len();
+            self.entered_at = Some(now);
+        }
+
+        Some(&playlist.entries[self.index])
+    }
+}
@@ -0,0 +1,240 @@
+// src/power.rs
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LOGIND_NAME: &str = "org.freedesktop.login1";
+const LOGIND_PATH: &str = "/org/freedesktop/login1";
+
+/// Wait before the first reconnect attempt after resume, doubled after each failure
+const RESUME_RETRY_FIRST: Duration = Duration::from_millis(500);
+/// Longest wait between reconnect attempts
+const RESUME_RETRY_MAX: Duration = Duration::from_secs(8);
+/// How long after resume the matrices are looked for before giving up
+const RESUME_RETRY_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PowerEvent {
+    /// The system is about to suspend
+    Suspending,
+    /// The system has just resumed
+    Resumed,
+}
+
+/// `gdbus monitor` on the system bus, watching logind for `PrepareForSleep`
+pub fn logind_monitor() -> Command {
+    let mut command = Command::new("gdbus");
+    command
+        .args(["monitor", "--system"])
+        .args(["--dest", LOGIND_NAME, "--object-path", LOGIND_PATH]);
+    command
+}
+
+/// Run `monitor` (usually [`logind_monitor`]) and parse its output on a
+/// background thread; `notify` is called after every event so the GUI can wake up
+pub fn spawn_sleep_monitor<F>(mut monitor: Command, notify: F) -> std::io::Result<Receiver<PowerEvent>>
+where
+    F: Fn() + Send + 'static,
+{
+    let mut child = monitor.stdout(Stdio::piped()).stderr(Stdio::null()).spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(event) = parse_monitor_line(&line) {
+                if tx.send(event).is_err() {
+                    break;
+                }
+                notify();
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+
+    Ok(rx)
+}
+
+/// Parse a `gdbus monitor` line such as
+/// `/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (true,)`
+pub fn parse_monitor_line(line: &str) -> Option<PowerEvent> {
+    let (_, args) = line.split_once(".PrepareForSleep (")?;
+    match args.trim_end_matches(')').trim_end_matches(',').trim() {
+        "true" => Some(PowerEvent::Suspending),
+        "false" => Some(PowerEvent::Resumed),
+        _ => None,
+    }
+}
+
+/// A logind delay inhibitor lock, held by a `systemd-inhibit` child process,
+/// so suspend waits until the matrices have been put to sleep
+#[derive(Default)]
+pub struct SleepInhibitor {
+    child: Option<Child>,
+}
+
+impl SleepInhibitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the lock if it isn't held already
+    pub fn acquire(&mut self) -> std::io::Result<()> {
+        if self.child.is_some() {
+            return Ok(());
+        }
+        let child = Command::new("systemd-inhibit")
+            .args([
+                "--what=sleep",
+                "--mode=delay",
+                "--who=led_controller",
+                "--why=Turn off LED matrices before suspend",
+                "sleep",
+                "infinity",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        self.child = Some(child);
+        Ok(())
+    }
+
+    /// Let the pending suspend go ahead
+    pub fn release(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// When to try reopening the matrices after resume: USB devices can take a
+/// moment to come back, so attempts back off until a bounded window runs out
+pub struct ResumeRetry {
+    next_attempt: Instant,
+    delay: Duration,
+    deadline: Instant,
+}
+
+impl ResumeRetry {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            next_attempt: now,
+            delay: RESUME_RETRY_FIRST,
+            deadline: now + RESUME_RETRY_WINDOW,
+        }
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        now >= self.next_attempt
+    }
+
+    /// Schedule the next attempt after a failed one; false once the window
+    /// has run out and it's time to give up
+    pub fn failed(&mut self, now: Instant) -> bool {
+        if now >= self.deadline {
+            return false;
+        }
+        self.next_attempt = (now + self.delay).min(self.deadline);
+        self.delay = (self.delay * 2).min(RESUME_RETRY_MAX);
+        true
+    }
+}
+
+/// Whether the laptop lid is closed, from ACPI; `None` without a lid switch
+pub fn lid_closed() -> Option<bool> {
+    let entries = std::fs::read_dir("/proc/acpi/button/lid").ok()?;
+    for entry in entries.flatten() {
+        // "state:      closed"
+        if let Ok(state) = std::fs::read_to_string(entry.path().join("state")) {
+            return Some(state.contains("closed"));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_prepare_for_sleep_signals() {
+        assert_eq!(
+            parse_monitor_line("/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (true,)"),
+            Some(PowerEvent::Suspending)
+        );
+        assert_eq!(
+            parse_monitor_line("/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (false,)"),
+            Some(PowerEvent::Resumed)
+        );
+    }
+
+    #[test]
+    fn ignores_other_monitor_output() {
+        assert_eq!(parse_monitor_line("Monitoring signals on object /org/freedesktop/login1 owned by :1.4"), None);
+        assert_eq!(
+            parse_monitor_line("/org/freedesktop/login1: org.freedesktop.login1.Manager.SessionNew ('3', objectpath '/org/freedesktop/login1/session/_33')"),
+            None
+        );
+        assert_eq!(
+            parse_monitor_line("/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (maybe,)"),
+            None
+        );
+    }
+
+    #[test]
+    fn monitor_reports_suspend_then_resume() {
+        let mut monitor = Command::new("printf");
+        monitor.arg(concat!(
+            "Monitoring signals on object /org/freedesktop/login1\\n",
+            "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForShutdown (false,)\\n",
+            "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (true,)\\n",
+            "/org/freedesktop/login1: org.freedesktop.login1.Manager.PrepareForSleep (false,)\\n",
+        ));
+        let (notified, notifications) = mpsc::channel();
+        let rx = spawn_sleep_monitor(monitor, move || {
+            let _ = notified.send(());
+        })
+        .unwrap();
+
+        let events: Vec<PowerEvent> = rx.iter().collect();
+        assert_eq!(events, [PowerEvent::Suspending, PowerEvent::Resumed]);
+        assert_eq!(notifications.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn missing_monitor_is_an_error() {
+        let monitor = Command::new("/nonexistent/gdbus");
+        assert!(spawn_sleep_monitor(monitor, || {}).is_err());
+    }
+
+    #[test]
+    fn resume_retry_backs_off_within_the_window() {
+        let start = Instant::now();
+        let mut retry = ResumeRetry::new(start);
+        assert!(retry.is_due(start), "first attempt is immediate");
+
+        let mut now = start;
+        let mut waits = Vec::new();
+        while retry.failed(now) {
+            let wait = retry.next_attempt - now;
+            waits.push(wait);
+            now = retry.next_attempt;
+            assert!(retry.is_due(now));
+        }
+
+        assert_eq!(&waits[..5], [500, 1000, 2000, 4000, 8000].map(Duration::from_millis));
+        assert!(waits.iter().all(|&wait| wait <= RESUME_RETRY_MAX));
+        assert!(now >= start + RESUME_RETRY_WINDOW, "gives up only at the end of the window");
+        assert!(now - start < RESUME_RETRY_WINDOW + RESUME_RETRY_MAX);
+    }
+}
@@ -1,45 +1,236 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::builtins;
+use crate::script::SCRIPT_PREFIX;
 
 pub const MATRIX_WIDTH: usize = 9;
 pub const MATRIX_HEIGHT: usize = 34;
 pub const PRESET_FILE: &str = "custom_presets.json";
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Earlier versions of the preset file kept as custom_presets.json.1
+/// (newest) up to .3
+pub const PRESET_BACKUPS: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct CustomPreset {
     pub name: String,
     pub image_data: Vec<u8>, // 9*34 = 306 pixels, each u8 is brightness 0-255
+    /// Unix seconds; 0 for presets saved before timestamps were kept
+    #[serde(default)]
+    pub created: u64,
+    #[serde(default)]
+    pub modified: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub favorite: bool,
+    /// Place in the user's ordering; ties are broken by name
+    #[serde(default)]
+    pub position: u32,
+}
+
+impl CustomPreset {
+    /// Whether the preset's name, description or tags contain `query`,
+    /// ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || self.name.to_lowercase().contains(&query)
+            || self.description.to_lowercase().contains(&query)
+            || self.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+    }
+}
+
+/// Current time in Unix seconds
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// What to do when an imported preset has the name of an existing one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ConflictPolicy {
+    /// Keep the existing preset and drop the imported one
+    Skip,
+    Overwrite,
+    /// Import under a free name such as "smile (2)"
+    Rename,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 3] = [ConflictPolicy::Rename, ConflictPolicy::Skip, ConflictPolicy::Overwrite];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Skip => "Keep existing",
+            ConflictPolicy::Overwrite => "Overwrite",
+            ConflictPolicy::Rename => "Import as a copy",
+        }
+    }
+
+    /// Parse the name used on the command line
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(ConflictPolicy::Skip),
+            "overwrite" => Some(ConflictPolicy::Overwrite),
+            "rename" => Some(ConflictPolicy::Rename),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+/// Counts of what an import did
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub replaced: usize,
+    pub renamed: usize,
+    pub skipped: usize,
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} replaced, {} renamed, {} skipped",
+            self.added, self.replaced, self.renamed, self.skipped
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct PresetManager {
     pub presets: HashMap<String, CustomPreset>,
+    /// The file the library is saved to; backups and a damaged copy are
+    /// kept next to it
+    #[serde(skip)]
+    path: PathBuf,
+    /// Set while the file failed to load, so saving can't replace it
+    /// before it has been set aside
+    #[serde(skip)]
+    damaged: bool,
+    /// Whether this session has already shifted the backups, so they hold
+    /// earlier sessions rather than the last few edits
+    #[serde(skip)]
+    backed_up: bool,
+}
+
+impl Default for PresetManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PresetManager {
+    /// An empty library saved to custom_presets.json
     pub fn new() -> Self {
+        Self::at(PRESET_FILE)
+    }
+
+    /// An empty library saved to `path`
+    pub fn at(path: impl Into<PathBuf>) -> Self {
         Self {
             presets: HashMap::new(),
+            path: path.into(),
+            damaged: false,
+            backed_up: false,
+        }
+    }
+
+    /// An empty library standing in for a file at `path` that failed to
+    /// load; it refuses to save until `set_aside_damaged` moved the file
+    pub fn damaged(path: impl Into<PathBuf>) -> Self {
+        Self {
+            damaged: true,
+            ..Self::at(path)
         }
     }
 
-    pub fn load_from_file() -> Self {
-        if let Ok(content) = fs::read_to_string(PRESET_FILE) {
-            if let Ok(manager) = serde_json::from_str(&content) {
-                return manager;
+    pub fn load_from_file() -> Result<Self, String> {
+        Self::load(Path::new(PRESET_FILE))
+    }
+
+    /// Read the preset file; no file is an empty library, but a file that
+    /// can't be read or parsed is an error so it isn't overwritten unawares
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(|manager: Self| Self { path: path.to_path_buf(), ..manager })
+                .map_err(|e| format!("{} is damaged: {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::at(path)),
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// The newest backup that parses, with its path; it saves to this
+    /// library's file
+    pub fn load_backup(&self) -> Result<(Self, PathBuf), String> {
+        (1..=PRESET_BACKUPS)
+            .map(|n| self.backup_path(n))
+            .find_map(|backup| {
+                let content = fs::read_to_string(&backup).ok()?;
+                let manager: Self = serde_json::from_str(&content).ok()?;
+                Some((Self { path: self.path.clone(), ..manager }, backup))
+            })
+            .ok_or_else(|| "no readable backup found".to_string())
+    }
+
+    /// Where a preset file that failed to load is moved before it's replaced
+    pub fn damaged_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}.damaged", self.path.display()))
+    }
+
+    /// Move a preset file that failed to load out of the way, so saving
+    /// doesn't rotate it into the backups, and allow saving again
+    pub fn set_aside_damaged(&mut self) -> io::Result<()> {
+        match fs::rename(&self.path, self.damaged_path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => {
+                self.damaged = false;
+                Ok(())
             }
         }
-        Self::new()
     }
 
-    pub fn save_to_file(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Replace the preset file without ever leaving it half written. The
+    /// first change of a session keeps the previous version as the newest
+    /// backup
+    pub fn save_to_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_writable()?;
         let json = serde_json::to_string_pretty(self)?;
-        fs::write(PRESET_FILE, json)?;
+        if fs::read(&self.path).is_ok_and(|current| current == json.as_bytes()) {
+            return Ok(());
+        }
+        if !self.backed_up {
+            self.rotate_backups()?;
+            self.backed_up = true;
+        }
+        write_atomic(&self.path, json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Changes are refused up front while the file could not be loaded, so
+    /// the library in memory never differs from what is on disk
+    fn check_writable(&self) -> Result<(), String> {
+        if self.damaged {
+            return Err(format!(
+                "{} could not be loaded; restore it from a backup or start empty first",
+                self.path.display()
+            ));
+        }
         Ok(())
     }
 
     pub fn save_preset(&mut self, name: String, image_data: Vec<u8>) -> Result<(), String> {
+        self.check_writable()?;
+        check_name(&name)?;
         if image_data.len() != MATRIX_WIDTH * MATRIX_HEIGHT {
             return Err(format!(
                 "Invalid image data size. Expected {}, got {}",
@@ -48,34 +239,203 @@ impl PresetManager {
             ));
         }
 
-        self.presets.insert(
-            name.clone(),
-            CustomPreset {
-                name: name.clone(),
-                image_data,
-            },
-        );
+        // Saving over a preset keeps its details and place in the order
+        let now = now_secs();
+        let position = self.next_position();
+        let preset = self.presets.entry(name.clone()).or_insert_with(|| CustomPreset {
+            name,
+            created: now,
+            position,
+            ..CustomPreset::default()
+        });
+        preset.image_data = image_data;
+        preset.modified = now;
 
         self.save_to_file()
             .map_err(|e| format!("Failed to save preset: {}", e))?;
         Ok(())
     }
 
+    /// Replace a preset's description, tags and favorite flag
+    pub fn set_details(&mut self, name: &str, description: String, tags: Vec<String>, favorite: bool) -> Result<(), String> {
+        self.check_writable()?;
+        let preset = self.presets.get_mut(name).ok_or_else(|| format!("no preset '{}'", name))?;
+        preset.description = description;
+        preset.tags = tags;
+        preset.favorite = favorite;
+        preset.modified = now_secs();
+        self.save_to_file()
+            .map_err(|e| format!("Failed to save preset: {}", e))
+    }
+
+    pub fn set_favorite(&mut self, name: &str, favorite: bool) -> Result<(), String> {
+        self.check_writable()?;
+        let preset = self.presets.get_mut(name).ok_or_else(|| format!("no preset '{}'", name))?;
+        preset.favorite = favorite;
+        self.save_to_file()
+            .map_err(|e| format!("Failed to save preset: {}", e))
+    }
+
+    /// Move a preset earlier (negative `offset`) or later in the order
+    pub fn move_preset(&mut self, name: &str, offset: isize) -> Result<(), String> {
+        self.check_writable()?;
+        let mut names = self.list_presets();
+        let from = names.iter().position(|n| n == name).ok_or_else(|| format!("no preset '{}'", name))?;
+        let to = from.saturating_add_signed(offset).min(names.len() - 1);
+        let moved = names.remove(from);
+        names.insert(to, moved);
+        // Renumber everything so positions stay unique
+        for (position, name) in names.iter().enumerate() {
+            if let Some(preset) = self.presets.get_mut(name) {
+                preset.position = position as u32;
+            }
+        }
+        self.save_to_file()
+            .map_err(|e| format!("Failed to save preset order: {}", e))
+    }
+
+    /// Every tag in use, sorted
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self.presets.values().flat_map(|p| p.tags.iter().cloned()).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    /// Presets in the user's order
+    pub fn ordered(&self) -> Vec<&CustomPreset> {
+        let mut presets: Vec<&CustomPreset> = self.presets.values().collect();
+        presets.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        presets
+    }
+
+    /// Position for a preset added at the end
+    fn next_position(&self) -> u32 {
+        self.presets.values().map(|p| p.position + 1).max().unwrap_or(0)
+    }
+
     pub fn get_preset(&self, name: &str) -> Option<Vec<u8>> {
         self.presets.get(name).map(|p| p.image_data.clone())
     }
 
     pub fn delete_preset(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_writable()?;
         self.presets.remove(name);
         self.save_to_file()?;
         Ok(())
     }
 
+    /// Preset names in the user's order
     pub fn list_presets(&self) -> Vec<String> {
-        self.presets.keys().cloned().collect()
+        self.ordered().into_iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// Add presets from a bundle, settling name clashes with `policy`, and
+    /// save once at the end. Nothing is added if any preset is malformed
+    pub fn import(&mut self, presets: Vec<CustomPreset>, policy: ConflictPolicy) -> Result<ImportSummary, String> {
+        self.check_writable()?;
+        for preset in &presets {
+            if preset.name.trim().is_empty() {
+                return Err("a preset has no name".to_string());
+            }
+            check_name(&preset.name)?;
+            if preset.image_data.len() != MATRIX_WIDTH * MATRIX_HEIGHT {
+                return Err(format!(
+                    "preset '{}' has {} pixels, expected {}",
+                    preset.name,
+                    preset.image_data.len(),
+                    MATRIX_WIDTH * MATRIX_HEIGHT
+                ));
+            }
+        }
+
+        let mut summary = ImportSummary::default();
+        let now = now_secs();
+        for mut preset in presets {
+            if self.presets.contains_key(&preset.name) {
+                match policy {
+                    ConflictPolicy::Skip => {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    ConflictPolicy::Overwrite => summary.replaced += 1,
+                    ConflictPolicy::Rename => {
+                        preset.name = self.free_name(&preset.name);
+                        summary.renamed += 1;
+                    }
+                }
+            } else {
+                summary.added += 1;
+            }
+            // Imports go to the end of the order, or keep the place of the
+            // preset they replace
+            preset.position = match self.presets.get(&preset.name) {
+                Some(existing) => existing.position,
+                None => self.next_position(),
+            };
+            if preset.created == 0 {
+                preset.created = now;
+            }
+            preset.modified = preset.modified.max(preset.created);
+            self.presets.insert(preset.name.clone(), preset);
+        }
+
+        self.save_to_file()
+            .map_err(|e| format!("Failed to save presets: {}", e))?;
+        Ok(summary)
+    }
+
+    fn backup_path(&self, n: usize) -> PathBuf {
+        PathBuf::from(format!("{}.{}", self.path.display(), n))
+    }
+
+    /// Shift custom_presets.json.1 to .2 and so on, dropping the oldest, then
+    /// copy the current file to .1
+    fn rotate_backups(&self) -> io::Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        for n in (1..PRESET_BACKUPS).rev() {
+            let from = self.backup_path(n);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(n + 1))?;
+            }
+        }
+        fs::copy(&self.path, self.backup_path(1))?;
+        Ok(())
+    }
+
+    /// `name` with the first " (n)" suffix that isn't taken
+    fn free_name(&self, name: &str) -> String {
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| !self.presets.contains_key(candidate))
+            .expect("some suffix is free")
     }
 }
 
+/// Custom presets share ids with built-ins and scripts, and those win, so
+/// a name that collides with one would never be shown
+fn check_name(name: &str) -> Result<(), String> {
+    if name.starts_with(SCRIPT_PREFIX) {
+        Err(format!("preset names can't start with '{}'", SCRIPT_PREFIX))
+    } else if builtins::is_builtin_id(name) {
+        Err(format!("'{}' is the name of a built-in preset", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Write to a temporary file next to `path` and rename it into place, so a
+/// crash leaves either the old contents or the new ones
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temp = PathBuf::from(format!("{}.tmp", path.display()));
+    let mut file = File::create(&temp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
 /// Convert image data to device command bytes for greyscale display
 pub fn image_data_to_command(image_data: &[u8]) -> Vec<u8> {
     if image_data.len() != MATRIX_WIDTH * MATRIX_HEIGHT {
@@ -108,3 +468,125 @@ pub fn image_data_to_command(image_data: &[u8]) -> Vec<u8> {
     commands
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str) -> CustomPreset {
+        CustomPreset {
+            name: name.to_string(),
+            image_data: vec![0; MATRIX_WIDTH * MATRIX_HEIGHT],
+            ..CustomPreset::default()
+        }
+    }
+
+    #[test]
+    fn reserved_names_are_refused() {
+        let mut manager = PresetManager::new();
+        let frame = vec![0; MATRIX_WIDTH * MATRIX_HEIGHT];
+        assert_eq!(
+            manager.save_preset("cpu".to_string(), frame.clone()),
+            Err("'cpu' is the name of a built-in preset".to_string())
+        );
+        assert_eq!(
+            manager.save_preset("script:rain".to_string(), frame),
+            Err("preset names can't start with 'script:'".to_string())
+        );
+
+        let bundle = vec![preset("smile"), preset("clock")];
+        assert_eq!(
+            manager.import(bundle, ConflictPolicy::Rename).err(),
+            Some("'clock' is the name of a built-in preset".to_string())
+        );
+        assert!(manager.presets.is_empty(), "nothing from a refused bundle is added");
+    }
+
+    #[test]
+    fn similar_names_are_allowed() {
+        for name in ["CPU", "cpu usage", "my script:1", "clock (2)"] {
+            assert_eq!(check_name(name), Ok(()), "{}", name);
+        }
+    }
+
+    fn frame(brightness: u8) -> Vec<u8> {
+        vec![brightness; MATRIX_WIDTH * MATRIX_HEIGHT]
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn saves_atomically_and_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presets.json");
+        let mut manager = PresetManager::load(&path).unwrap();
+        assert!(manager.presets.is_empty(), "no file is an empty library");
+
+        manager.save_preset("smile".to_string(), frame(7)).unwrap();
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, ["presets.json"], "no temporary file or backup is left behind");
+
+        let loaded = PresetManager::load(&path).unwrap();
+        assert_eq!(loaded.get_preset("smile"), Some(frame(7)));
+        assert_eq!(loaded.path, path);
+    }
+
+    #[test]
+    fn backs_up_once_per_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presets.json");
+        let backup = |n: usize| dir.path().join(format!("presets.json.{}", n));
+
+        let mut sessions = Vec::new();
+        for session in 0..5u8 {
+            let mut manager = PresetManager::load(&path).unwrap();
+            // Unchanged saves don't touch anything
+            manager.save_to_file().unwrap();
+            for edit in 0..3 {
+                manager.save_preset(format!("s{}e{}", session, edit), frame(session)).unwrap();
+            }
+            sessions.push(read(&path));
+        }
+
+        // The backups are the libraries earlier sessions ended with
+        assert_eq!(read(&backup(1)), sessions[3]);
+        assert_eq!(read(&backup(2)), sessions[2]);
+        assert_eq!(read(&backup(3)), sessions[1]);
+        assert!(!backup(4).exists());
+        assert_eq!(PresetManager::load(&path).unwrap().presets.len(), 15);
+    }
+
+    #[test]
+    fn damaged_file_is_kept_until_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("presets.json");
+        for session in 0..3u8 {
+            let mut manager = PresetManager::load(&path).unwrap();
+            manager.save_preset(format!("v{}", session), frame(session)).unwrap();
+        }
+        fs::write(&path, "{ not json").unwrap();
+        fs::write(dir.path().join("presets.json.1"), "also broken").unwrap();
+
+        let error = PresetManager::load(&path).err().unwrap();
+        assert!(error.contains("is damaged"), "{}", error);
+
+        // Nothing saves over the damaged file
+        let mut standing_in = PresetManager::damaged(&path);
+        assert!(standing_in.save_preset("new".to_string(), frame(1)).is_err());
+        assert!(standing_in.delete_preset("v0").is_err());
+        assert!(standing_in.import(vec![preset("imported")], ConflictPolicy::Rename).is_err());
+        assert!(standing_in.list_presets().is_empty());
+        assert_eq!(read(&path), "{ not json");
+
+        // The newest backup that reads cleanly is offered
+        let (mut restored, from) = standing_in.load_backup().unwrap();
+        assert_eq!(from, dir.path().join("presets.json.2"));
+        assert_eq!(restored.list_presets(), ["v0"]);
+
+        standing_in.set_aside_damaged().unwrap();
+        assert_eq!(read(&standing_in.damaged_path()), "{ not json");
+        restored.save_to_file().unwrap();
+        assert_eq!(PresetManager::load(&path).unwrap().list_presets(), ["v0"]);
+    }
+}
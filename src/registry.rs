@@ -0,0 +1,258 @@
+// src/registry.rs
+//! Presets are trait objects kept in a registry. Each render thread owns
+//! one and ticks it; the GUI keeps another to list presets and edit their
+//! parameters. Built-ins are registered up front; custom images and
+//! scripts get an entry the first time they're shown.
+use std::collections::HashMap;
+
+use crate::api::Side;
+use crate::audio::SpectrumFeed;
+use crate::builtins::{self, CustomImagePreset, ScriptPreset};
+use crate::params::{params_editor, ParamSpec, PresetParams};
+use crate::presets::{image_data_to_command, PresetManager};
+use crate::script::ScriptManager;
+use crate::stats::DiskSpace;
+use crate::timer::Timer;
+use crate::utils::BatteryStatus;
+
+/// What a preset produces for one update
+pub enum PresetOutput {
+    /// A host-rendered brightness frame, one byte per pixel
+    Frame(Vec<u8>),
+    /// A raw firmware command, for patterns the firmware draws itself
+    Command(Vec<u8>),
+}
+
+impl PresetOutput {
+    pub fn frame(&self) -> Option<&[u8]> {
+        match self {
+            PresetOutput::Frame(frame) => Some(frame),
+            PresetOutput::Command(_) => None,
+        }
+    }
+
+    pub fn into_command(self) -> Vec<u8> {
+        match self {
+            PresetOutput::Frame(frame) => image_data_to_command(&frame),
+            PresetOutput::Command(command) => command,
+        }
+    }
+}
+
+/// Headings the preset pickers group presets under
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PresetCategory {
+    General,
+    Metrics,
+    Display,
+    Animations,
+    Patterns,
+    Custom,
+    Script,
+}
+
+impl PresetCategory {
+    pub fn label(self) -> &'static str {
+        match self {
+            PresetCategory::General => "General",
+            PresetCategory::Metrics => "📊 System Metrics",
+            PresetCategory::Display => "⏰ Display",
+            PresetCategory::Animations => "✨ Animations",
+            PresetCategory::Patterns => "🎨 Patterns",
+            PresetCategory::Custom => "🖼️ Custom Presets",
+            PresetCategory::Script => "📜 Scripts",
+        }
+    }
+}
+
+/// Everything a preset may read to draw one update
+#[derive(Clone)]
+pub struct PresetContext<'a> {
+    pub side: Side,
+    pub cpu_percent: u8,
+    pub ram_percent: u8,
+    pub net_rx_percent: u8,
+    pub net_tx_percent: u8,
+    pub disks: &'a [DiskSpace],
+    pub disk_io_history: &'a [(u8, u8)],
+    /// Chosen sensor in °C, 0 without one
+    pub temperature: f32,
+    pub temp_warning: f32,
+    pub temp_critical: f32,
+    pub fan_percent: u8,
+    pub batteries: &'a [BatteryStatus],
+    pub timer: &'a Timer,
+    /// Slow animation counter, advanced on each metrics update
+    pub idle_frame: u8,
+    /// Last frame pushed to this side over the API
+    pub api_frame: Option<&'a [u8]>,
+    pub audio: Option<&'a SpectrumFeed>,
+    pub scripts: &'a ScriptManager,
+    pub custom: &'a PresetManager,
+    /// This preset's parameters on this side, with defaults filled in
+    pub params: &'a PresetParams,
+}
+
+/// Frame rate of presets that don't say otherwise; they only change when
+/// the metrics do
+pub const DEFAULT_FPS: f64 = 2.0;
+
+/// Frame rate for presets that animate
+pub const ANIMATED_FPS: f64 = 30.0;
+
+/// Presets run on render threads, so they must be `Send`
+pub trait Preset: Send {
+    /// Stable id used in settings, playlists, schedules, the API and MQTT
+    fn id(&self) -> &str;
+
+    /// Name shown in the GUI
+    fn name(&self) -> &str;
+
+    fn category(&self) -> PresetCategory;
+
+    /// Options the preset takes; the registry shows controls for them and
+    /// hands the values to `tick` through `ctx.params`
+    fn params(&self) -> Vec<ParamSpec> {
+        Vec::new()
+    }
+
+    /// Draw the next update
+    fn tick(&mut self, ctx: &PresetContext) -> PresetOutput;
+
+    /// Frames per second unless the user picks another rate
+    fn default_fps(&self) -> f64 {
+        DEFAULT_FPS
+    }
+
+    /// Errors and other messages for the status bar since the last call
+    fn take_messages(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// A preset's parameters plus the frame rate every preset has
+fn schema(preset: &dyn Preset) -> Vec<ParamSpec> {
+    let mut schema = preset.params();
+    schema.push(ParamSpec::range("fps", "Frames per second", 0.5, 60.0, preset.default_fps()));
+    schema
+}
+
+/// A preset as listed in the pickers
+pub struct PresetEntry {
+    pub id: String,
+    pub name: String,
+    pub category: PresetCategory,
+}
+
+/// Presets by id, built-ins first
+pub struct PresetRegistry {
+    builtins: Vec<Box<dyn Preset>>,
+    dynamic: HashMap<String, Box<dyn Preset>>,
+}
+
+impl PresetRegistry {
+    pub fn new() -> Self {
+        Self {
+            builtins: builtins::all(),
+            dynamic: HashMap::new(),
+        }
+    }
+
+    /// Built-ins in picker order, then custom presets in the user's order,
+    /// then scripts
+    pub fn entries(&self, custom: &PresetManager, scripts: &ScriptManager) -> Vec<PresetEntry> {
+        let mut entries: Vec<PresetEntry> = self
+            .builtins
+            .iter()
+            .map(|preset| PresetEntry {
+                id: preset.id().to_string(),
+                name: preset.name().to_string(),
+                category: preset.category(),
+            })
+            .collect();
+        entries.extend(custom.list_presets().into_iter().map(|name| PresetEntry {
+            id: name.clone(),
+            name,
+            category: PresetCategory::Custom,
+        }));
+        entries.extend(scripts.names().map(|name| PresetEntry {
+            id: ScriptPreset::id_for(name),
+            name: name.clone(),
+            category: PresetCategory::Script,
+        }));
+        entries
+    }
+
+    /// The preset for an id, creating custom and script presets on first use
+    fn get(&mut self, id: &str, custom: &PresetManager) -> Option<&mut Box<dyn Preset>> {
+        if let Some(index) = self.builtins.iter().position(|preset| preset.id() == id) {
+            return Some(&mut self.builtins[index]);
+        }
+        if !self.dynamic.contains_key(id) {
+            let preset: Box<dyn Preset> = if let Some(name) = ScriptManager::script_name(id) {
+                Box::new(ScriptPreset::new(name))
+            } else if custom.get_preset(id).is_some() {
+                Box::new(CustomImagePreset::new(id))
+            } else {
+                return None;
+            };
+            self.dynamic.insert(id.to_string(), preset);
+        }
+        self.dynamic.get_mut(id)
+    }
+
+    /// Draw the next update of a preset; unknown ids draw nothing.
+    /// `ctx.params` holds the stored values, which are checked against the
+    /// preset's schema first
+    pub fn tick(&mut self, id: &str, ctx: &PresetContext) -> PresetOutput {
+        let Some(preset) = self.get(id, ctx.custom) else {
+            return PresetOutput::Command(Vec::new());
+        };
+        let params = PresetParams::resolve(&schema(preset.as_ref()), Some(ctx.params));
+        preset.tick(&PresetContext { params: &params, ..ctx.clone() })
+    }
+
+    /// Frames per second a preset should be drawn at, given its stored parameters
+    pub fn fps(&mut self, id: &str, custom: &PresetManager, stored: Option<&PresetParams>) -> f64 {
+        match self.get(id, custom) {
+            Some(preset) => PresetParams::resolve(&schema(preset.as_ref()), stored).number("fps"),
+            None => DEFAULT_FPS,
+        }
+    }
+
+    /// Messages from every preset since the last call
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.builtins
+            .iter_mut()
+            .chain(self.dynamic.values_mut())
+            .flat_map(|preset| preset.take_messages())
+            .collect()
+    }
+
+    /// Drop custom and script presets so they start afresh, e.g. after the
+    /// scripts were reloaded
+    pub fn reset_dynamic(&mut self) {
+        self.dynamic.clear();
+    }
+
+    /// Controls for a preset's parameters, editing the side's stored values
+    pub fn settings_ui(
+        &mut self,
+        id: &str,
+        custom: &PresetManager,
+        stored: &mut HashMap<String, PresetParams>,
+        ui: &mut egui::Ui,
+    ) {
+        let Some(preset) = self.get(id, custom) else {
+            return;
+        };
+        let schema = schema(preset.as_ref());
+        let mut params = PresetParams::resolve(&schema, stored.get(id));
+        if params_editor(ui, &schema, &mut params) {
+            stored.insert(id.to_string(), params);
+        }
+        if ui.button("Defaults").clicked() {
+            stored.remove(id);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+// src/render.rs
+//! One render thread per side draws the side's preset on its own frame
+//! clock, so animations stay smooth when the GUI is busy or hidden. The GUI
+//! decides what each side should show and sends it, along with a snapshot
+//! of the metrics on every tick and the timer when it changes, over a
+//! channel.
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::Side;
+use crate::audio::SpectrumFeed;
+use crate::device::Device;
+use crate::notifications::NotificationBanner;
+use crate::params::PresetParams;
+use crate::presets::{image_data_to_command, PresetManager};
+use crate::registry::{PresetContext, PresetOutput, PresetRegistry};
+use crate::script::ScriptManager;
+use crate::stats::DiskSpace;
+use crate::timer::Timer;
+use crate::transition::{SideTransition, TransitionSettings};
+use crate::utils::BatteryStatus;
+
+/// Frame rate while a transition runs, whatever the preset's own rate
+const TRANSITION_FPS: f64 = 20.0;
+
+/// How often a scrolling or blinking notification banner is redrawn
+const BANNER_FRAME_INTERVAL: Duration = Duration::from_millis(80);
+
+/// How long the thread sleeps when it has nothing to draw
+const IDLE_WAIT: Duration = Duration::from_millis(250);
+
+/// What a side should show: a preset, reached through a transition
+#[derive(Clone, PartialEq, Debug)]
+pub struct RenderPlan {
+    pub preset: String,
+    pub transition: TransitionSettings,
+}
+
+/// The app state presets draw from, copied for the render thread
+#[derive(Clone, Default)]
+pub struct RenderInputs {
+    pub cpu_percent: u8,
+    pub ram_percent: u8,
+    pub net_rx_percent: u8,
+    pub net_tx_percent: u8,
+    pub disks: Vec<DiskSpace>,
+    pub disk_io_history: Vec<(u8, u8)>,
+    pub temperature: f32,
+    pub temp_warning: f32,
+    pub temp_critical: f32,
+    pub fan_percent: u8,
+    pub batteries: Vec<BatteryStatus>,
+    pub idle_frame: u8,
+    pub api_frame: Option<Vec<u8>>,
+    pub audio: Option<SpectrumFeed>,
+    /// Shared rather than copied each tick
+    pub custom: Arc<PresetManager>,
+    /// This side's preset parameters, by preset id
+    pub params: HashMap<String, PresetParams>,
+}
+
+impl RenderInputs {
+    fn context<'a>(
+        &'a self,
+        side: Side,
+        timer: &'a Timer,
+        scripts: &'a ScriptManager,
+        params: &'a PresetParams,
+    ) -> PresetContext<'a> {
+        PresetContext {
+            side,
+            cpu_percent: self.cpu_percent,
+            ram_percent: self.ram_percent,
+            net_rx_percent: self.net_rx_percent,
+            net_tx_percent: self.net_tx_percent,
+            disks: &self.disks,
+            disk_io_history: &self.disk_io_history,
+            temperature: self.temperature,
+            temp_warning: self.temp_warning,
+            temp_critical: self.temp_critical,
+            fan_percent: self.fan_percent,
+            batteries: &self.batteries,
+            timer,
+            idle_frame: self.idle_frame,
+            api_frame: self.api_frame.as_deref(),
+            audio: self.audio.as_ref(),
+            scripts,
+            custom: &self.custom,
+            params,
+        }
+    }
+}
+
+enum RenderCommand {
+    /// `None` while something else (an alert, notification, stream or
+    /// sleep) owns the display
+    Show(Option<RenderPlan>),
+    Inputs(Box<RenderInputs>),
+    /// Sent only when the timer changes, as it doesn't on most ticks
+    Timer(Timer),
+    Scripts(ScriptManager),
+    /// A notification to show over the preset until it's done, or `None`
+    /// to drop the current one
+    Banner(Option<NotificationBanner>),
+    /// Forget what was on display, e.g. after a disconnect
+    Reset,
+}
+
+pub enum RenderEvent {
+    /// The preset the side is now showing or transitioning to
+    Showing(Option<String>),
+    /// A message for the status bar, e.g. a script error
+    Message(String),
+}
+
+/// Handle to a side's render thread; the thread exits when it's dropped
+pub struct RenderThread {
+    tx: Sender<RenderCommand>,
+    pub rx: Receiver<RenderEvent>,
+    device: Arc<Mutex<Option<Arc<Mutex<Device>>>>>,
+}
+
+impl RenderThread {
+    pub fn spawn<F>(side: Side, scripts: ScriptManager, notify: F) -> Self
+    where
+        F: Fn() + Send + 'static,
+    {
+        let (tx, commands) = mpsc::channel();
+        let (events, rx) = mpsc::channel();
+        let device = Arc::new(Mutex::new(None));
+        let mut worker = Worker {
+            side,
+            commands,
+            events,
+            notify: Box::new(notify),
+            device: device.clone(),
+            scripts,
+            registry: PresetRegistry::new(),
+            transition: SideTransition::new(),
+            plan: None,
+            inputs: RenderInputs::default(),
+            timer: Timer::new(),
+            banner: None,
+        };
+        thread::spawn(move || worker.run());
+        Self { tx, rx, device }
+    }
+
+    /// Point the thread at the side's device, or none when disconnected
+    pub fn set_device(&self, device: Option<Arc<Mutex<Device>>>) {
+        *self.device.lock().unwrap_or_else(|e| e.into_inner()) = device;
+    }
+
+    pub fn show(&self, plan: Option<RenderPlan>) {
+        let _ = self.tx.send(RenderCommand::Show(plan));
+    }
+
+    pub fn update(&self, inputs: RenderInputs) {
+        let _ = self.tx.send(RenderCommand::Inputs(Box::new(inputs)));
+    }
+
+    pub fn set_timer(&self, timer: Timer) {
+        let _ = self.tx.send(RenderCommand::Timer(timer));
+    }
+
+    pub fn set_banner(&self, banner: Option<NotificationBanner>) {
+        let _ = self.tx.send(RenderCommand::Banner(banner));
+    }
+
+    /// Restart script presets from the reloaded sources
+    pub fn set_scripts(&self, scripts: ScriptManager) {
+        let _ = self.tx.send(RenderCommand::Scripts(scripts));
+    }
+
+    pub fn reset(&self) {
+        let _ = self.tx.send(RenderCommand::Reset);
+    }
+}
+
+struct Worker {
+    side: Side,
+    commands: Receiver<RenderCommand>,
+    events: Sender<RenderEvent>,
+    notify: Box<dyn Fn() + Send>,
+    device: Arc<Mutex<Option<Arc<Mutex<Device>>>>>,
+    scripts: ScriptManager,
+    registry: PresetRegistry,
+    transition: SideTransition,
+    plan: Option<RenderPlan>,
+    inputs: RenderInputs,
+    timer: Timer,
+    banner: Option<NotificationBanner>,
+}
+
+impl Worker {
+    fn run(&mut self) {
+        let mut next_frame = Instant::now();
+        loop {
+            let wait = if self.plan.is_some() {
+                next_frame.saturating_duration_since(Instant::now())
+            } else {
+                IDLE_WAIT
+            };
+            match self.commands.recv_timeout(wait) {
+                Ok(command) => {
+                    if self.handle(command) {
+                        next_frame = Instant::now();
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            if Instant::now() < next_frame {
+                continue;
+            }
+
+            let fps = self.draw();
+            next_frame = Instant::now() + Duration::from_secs_f64(1.0 / fps);
+        }
+    }
+
+    /// Apply a command; returns true when the side should be redrawn now
+    fn handle(&mut self, command: RenderCommand) -> bool {
+        match command {
+            RenderCommand::Show(plan) => {
+                let changed = plan != self.plan;
+                self.plan = plan;
+                changed
+            }
+            RenderCommand::Inputs(inputs) => {
+                self.inputs = *inputs;
+                false
+            }
+            RenderCommand::Timer(timer) => {
+                self.timer = timer;
+                false
+            }
+            RenderCommand::Scripts(scripts) => {
+                self.scripts = scripts;
+                self.registry.reset_dynamic();
+                false
+            }
+            RenderCommand::Banner(banner) => {
+                self.banner = banner;
+                true
+            }
+            RenderCommand::Reset => {
+                self.transition.reset();
+                self.banner = None;
+                self.send(RenderEvent::Showing(None));
+                false
+            }
+        }
+    }
+
+    /// Draw one frame of the notification banner or the planned preset;
+    /// returns the rate to draw at
+    fn draw(&mut self) -> f64 {
+        let now = Instant::now();
+        let device = self.device.lock().unwrap_or_else(|e| e.into_inner()).clone();
+
+        // A banner runs its course even while something else owns the side
+        if let Some(banner) = &self.banner {
+            match banner.frame(now) {
+                Some(frame) => {
+                    if let (Some(device), Some(_)) = (&device, &self.plan) {
+                        if let Ok(mut dev) = device.lock() {
+                            dev.send(image_data_to_command(&frame));
+                        }
+                    }
+                    return 1.0 / BANNER_FRAME_INTERVAL.as_secs_f64();
+                }
+                None => self.banner = None,
+            }
+        }
+
+        let Some(plan) = self.plan.clone() else {
+            return 1.0 / IDLE_WAIT.as_secs_f64();
+        };
+        let Some(device) = device else {
+            return 1.0 / IDLE_WAIT.as_secs_f64();
+        };
+
+        let was_showing = self.transition.shown_preset().map(str::to_string);
+        self.transition.select(&plan.preset, plan.transition, now);
+        if self.transition.shown_preset() != was_showing.as_deref() {
+            self.send(RenderEvent::Showing(Some(plan.preset.clone())));
+        }
+
+        let no_params = PresetParams::default();
+        let params = self.inputs.params.get(&plan.preset).unwrap_or(&no_params);
+        let ctx = self.inputs.context(self.side, &self.timer, &self.scripts, params);
+        let output = self.registry.tick(&plan.preset, &ctx);
+        for message in self.registry.take_messages() {
+            self.send(RenderEvent::Message(message));
+        }
+
+        let command = transition_command(&mut self.transition, output, now);
+        if !command.is_empty() {
+            if let Ok(mut dev) = device.lock() {
+                dev.send(command);
+            }
+        }
+
+        if self.transition.is_active() {
+            TRANSITION_FPS
+        } else {
+            self.registry.fps(&plan.preset, &self.inputs.custom, self.inputs.params.get(&plan.preset))
+        }
+    }
+
+    fn send(&self, event: RenderEvent) {
+        if self.events.send(event).is_ok() {
+            (self.notify)();
+        }
+    }
+}
+
+/// Command to send for a preset's output, blended into a running transition
+/// if there is one; remembers the frame shown for the next transition
+fn transition_command(transition: &mut SideTransition, output: PresetOutput, now: Instant) -> Vec<u8> {
+    match transition.frame(output.frame(), now) {
+        Some(frame) => {
+            transition.record(Some(&frame));
+            image_data_to_command(&frame)
+        }
+        None => {
+            transition.record(output.frame());
+            output.into_command()
+        }
+    }
+}
@@ -0,0 +1,76 @@
+// src/sampler.rs
+//! Measures what the app reacts to on every tick: system metrics,
+//! batteries, idle time and the lid. It runs on the app's clock thread, so
+//! slow sources (sysinfo, battery, idle helpers) never hold up the GUI.
+use std::time::Duration;
+
+use crate::idle::{self, IdleSource};
+#[cfg(target_os = "linux")]
+use crate::power;
+use crate::stats::{DiskIoRate, DiskSpace, FanReading, NetworkRate, Stats, TemperatureReading};
+use crate::utils::{get_battery_statuses, BatteryStatus};
+
+/// Everything one tick measured
+pub struct Sample {
+    pub cpu_percent: u8,
+    pub ram_percent: u8,
+    pub net_rx_percent: u8,
+    pub net_tx_percent: u8,
+    pub net_rate: NetworkRate,
+    pub net_interfaces: Vec<String>,
+    pub disk_space: Vec<DiskSpace>,
+    pub disk_io_rate: DiskIoRate,
+    pub disk_io_history: Vec<(u8, u8)>,
+    pub temperatures: Vec<TemperatureReading>,
+    pub fans: Vec<FanReading>,
+    pub batteries: Vec<BatteryStatus>,
+    pub idle_time: Option<Duration>,
+    /// The idle source that answered, for display
+    pub idle_source: &'static str,
+    pub lid_closed: bool,
+}
+
+pub struct Sampler {
+    stats: Stats,
+    idle_source: Box<dyn IdleSource>,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        let mut stats = Stats::new();
+        stats.refresh();
+        Self {
+            stats,
+            idle_source: idle::default_idle_source(),
+        }
+    }
+
+    /// Take one sample; network figures are for `interface`, or for all
+    /// interfaces together
+    pub fn sample(&mut self, interface: Option<&str>) -> Sample {
+        let stats = &mut self.stats;
+        stats.refresh();
+        let (net_rx_percent, net_tx_percent) = stats.network_usage(interface);
+        let idle_time = self.idle_source.idle_time();
+        Sample {
+            cpu_percent: stats.cpu_usage(),
+            ram_percent: stats.ram_usage(),
+            net_rx_percent,
+            net_tx_percent,
+            net_rate: stats.network_rate(interface),
+            net_interfaces: stats.network_interfaces(),
+            disk_space: stats.disk_space(),
+            disk_io_rate: stats.disk_io_rate(),
+            disk_io_history: stats.disk_io_history(),
+            temperatures: stats.temperatures(),
+            fans: stats.fans(),
+            batteries: get_battery_statuses(),
+            idle_time,
+            idle_source: self.idle_source.name(),
+            #[cfg(target_os = "linux")]
+            lid_closed: power::lid_closed().unwrap_or(false),
+            #[cfg(not(target_os = "linux"))]
+            lid_closed: false,
+        }
+    }
+}
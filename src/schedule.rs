@@ -0,0 +1,186 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+pub const DAY_LABELS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+/// What a side does while a schedule rule is active
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum ScheduleAction {
+    /// Leave the side on whatever it was set to
+    Keep,
+    Preset(String),
+    Sleep,
+}
+
+impl ScheduleAction {
+    pub fn label(&self) -> String {
+        match self {
+            ScheduleAction::Keep => "Keep".to_string(),
+            ScheduleAction::Preset(name) => name.clone(),
+            ScheduleAction::Sleep => "Sleep".to_string(),
+        }
+    }
+}
+
+/// Applies per-side actions on the selected weekdays between `start_minute`
+/// and `end_minute` (minutes since midnight). A window that ends before it
+/// starts runs past midnight into the next day
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScheduleRule {
+    pub name: String,
+    pub enabled: bool,
+    /// Monday first
+    pub days: [bool; 7],
+    pub start_minute: u16,
+    pub end_minute: u16,
+    pub left: ScheduleAction,
+    pub right: ScheduleAction,
+}
+
+impl Default for ScheduleRule {
+    fn default() -> Self {
+        Self {
+            name: "Work hours".to_string(),
+            enabled: true,
+            days: [true, true, true, true, true, false, false],
+            start_minute: 9 * 60,
+            end_minute: 18 * 60,
+            left: ScheduleAction::Preset("cpu".to_string()),
+            right: ScheduleAction::Preset("clock".to_string()),
+        }
+    }
+}
+
+impl ScheduleRule {
+    /// Whether the rule's window covers `now`
+    pub fn is_active(&self, now: &NaiveDateTime) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let today = now.weekday().num_days_from_monday() as usize;
+        let yesterday = (today + 6) % 7;
+        let minute = (now.hour() * 60 + now.minute()) as u16;
+
+        if self.start_minute <= self.end_minute {
+            self.days[today] && minute >= self.start_minute && minute < self.end_minute
+        } else {
+            // Overnight: the evening part belongs to today's window, the
+            // early morning part to the window that started yesterday
+            (self.days[today] && minute >= self.start_minute)
+                || (self.days[yesterday] && minute < self.end_minute)
+        }
+    }
+}
+
+/// Actions for the left and right side at `now`; the first active rule that
+/// does something other than `Keep` for a side wins
+pub fn active_actions(rules: &[ScheduleRule], now: &NaiveDateTime) -> (ScheduleAction, ScheduleAction) {
+    let mut left = ScheduleAction::Keep;
+    let mut right = ScheduleAction::Keep;
+    for rule in rules.iter().filter(|r| r.is_active(now)) {
+        if left == ScheduleAction::Keep {
+            left = rule.left.clone();
+        }
+        if right == ScheduleAction::Keep {
+            right = rule.right.clone();
+        }
+    }
+    (left, right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// 2024-01-01 was a Monday, so day 1 + n is weekday n (Monday first)
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn window(start: (u16, u16), end: (u16, u16), days: [bool; 7]) -> ScheduleRule {
+        ScheduleRule {
+            days,
+            start_minute: start.0 * 60 + start.1,
+            end_minute: end.0 * 60 + end.1,
+            ..ScheduleRule::default()
+        }
+    }
+
+    const EVERY_DAY: [bool; 7] = [true; 7];
+
+    #[test]
+    fn daytime_window_includes_start_and_excludes_end() {
+        let rule = window((9, 0), (18, 0), EVERY_DAY);
+        assert!(!rule.is_active(&at(1, 8, 59)));
+        assert!(rule.is_active(&at(1, 9, 0)));
+        assert!(rule.is_active(&at(1, 17, 59)));
+        assert!(!rule.is_active(&at(1, 18, 0)));
+    }
+
+    #[test]
+    fn overnight_window_runs_past_midnight() {
+        let rule = window((22, 0), (6, 30), EVERY_DAY);
+        assert!(rule.is_active(&at(1, 23, 15)));
+        assert!(rule.is_active(&at(2, 0, 0)));
+        assert!(rule.is_active(&at(2, 6, 29)));
+        assert!(!rule.is_active(&at(2, 6, 30)));
+        assert!(!rule.is_active(&at(2, 12, 0)));
+    }
+
+    #[test]
+    fn overnight_morning_belongs_to_the_previous_day() {
+        // Friday night only
+        let mut days = [false; 7];
+        days[4] = true;
+        let rule = window((22, 0), (6, 0), days);
+        assert!(!rule.is_active(&at(5, 3, 0)), "Friday morning follows Thursday night");
+        assert!(rule.is_active(&at(5, 23, 0)));
+        assert!(rule.is_active(&at(6, 3, 0)), "Saturday morning follows Friday night");
+        assert!(!rule.is_active(&at(6, 23, 0)));
+    }
+
+    #[test]
+    fn day_mask_limits_the_window() {
+        let rule = window((9, 0), (18, 0), [true, true, true, true, true, false, false]);
+        assert!(rule.is_active(&at(5, 12, 0)), "Friday");
+        assert!(!rule.is_active(&at(6, 12, 0)), "Saturday");
+        assert!(!rule.is_active(&at(7, 12, 0)), "Sunday");
+        assert!(rule.is_active(&at(8, 12, 0)), "Monday");
+    }
+
+    #[test]
+    fn disabled_rule_is_never_active() {
+        let rule = ScheduleRule {
+            enabled: false,
+            ..window((0, 0), (23, 59), EVERY_DAY)
+        };
+        assert!(!rule.is_active(&at(1, 12, 0)));
+    }
+
+    #[test]
+    fn first_active_rule_wins_per_side() {
+        let rules = [
+            ScheduleRule {
+                left: ScheduleAction::Sleep,
+                right: ScheduleAction::Keep,
+                ..window((20, 0), (23, 0), EVERY_DAY)
+            },
+            ScheduleRule {
+                left: ScheduleAction::Preset("cpu".to_string()),
+                right: ScheduleAction::Preset("clock".to_string()),
+                ..window((18, 0), (23, 0), EVERY_DAY)
+            },
+        ];
+
+        assert_eq!(
+            active_actions(&rules, &at(1, 21, 0)),
+            (ScheduleAction::Sleep, ScheduleAction::Preset("clock".to_string()))
+        );
+        assert_eq!(
+            active_actions(&rules, &at(1, 19, 0)),
+            (ScheduleAction::Preset("cpu".to_string()), ScheduleAction::Preset("clock".to_string()))
+        );
+        assert_eq!(active_actions(&rules, &at(1, 12, 0)), (ScheduleAction::Keep, ScheduleAction::Keep));
+    }
+}
@@ -0,0 +1,1220 @@
+// src/script.rs
+//! A small scripting language for user presets.
+//!
+//! Scripts look like this and run once per frame:
+//! ```text
+//! // state.* keeps its value between frames and starts out as 0
+//! state.y = (state.y + 1) % HEIGHT;
+//! clear();
+//! line(0, state.y, WIDTH - 1, state.y, 255);
+//! rect(0, HEIGHT - cpu() * HEIGHT / 100, WIDTH, HEIGHT, 60);
+//! ```
+//! Statements: `let x = e;`, `x = e;` (also `+=` `-=` `*=` `/=`, and on
+//! `a[i]` and `state.x`), `if c { } else if c { } else { }`, `while c { }`,
+//! `for i in a..b { }`, `for v in array { }`, `break;`, `continue;`.
+//! Values are numbers, booleans, strings and arrays (`[1, 2]`, `a[i]`).
+use chrono::{DateTime, Datelike, Local, Timelike};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::animations::Rng;
+use crate::presets::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Statements, calls and plotted line pixels one frame may run before the
+/// script is stopped
+const STEP_BUDGET: u64 = 1_000_000;
+
+/// How deeply blocks and expressions may nest before parsing gives up,
+/// which keeps a hostile script from overflowing the stack
+const MAX_NESTING: usize = 64;
+
+/// Line endpoints are clamped this far around the matrix so the loop
+/// stays short and the arithmetic can't overflow
+const LINE_MARGIN: i64 = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Bool(bool),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Num(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "string",
+            Value::Array(_) => "array",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, ScriptError> {
+    Err(ScriptError {
+        line,
+        message: message.into(),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Lexer
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+    Eof,
+}
+
+/// Longest first, so `..` wins over `.` and `<=` over `<`
+const PUNCTUATION: [&str; 29] = [
+    "..", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=", "*=", "/=", "(", ")", "{", "}", "[", "]", ",", ";", ".", "+",
+    "-", "*", "/", "%", "=", "<", ">", "!",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ScriptError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c.is_ascii_digit() {
+            let start = i;
+            // A `.` only belongs to the number when a digit follows, so `0..9` is a range
+            while i < chars.len()
+                && (chars[i].is_ascii_digit() || (chars[i] == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())))
+            {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse().map_err(|_| ScriptError {
+                line,
+                message: format!("bad number '{}'", text),
+            })?;
+            tokens.push((Token::Num(number), line));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), line));
+        } else if c == '"' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return error(line, "unterminated string"),
+                    Some('"') => break,
+                    Some('\\') => {
+                        text.push(match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some(&other) => other,
+                            None => return error(line, "unterminated string"),
+                        });
+                        i += 2;
+                    }
+                    Some(&other) => {
+                        text.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+            tokens.push((Token::Str(text), line));
+        } else {
+            let rest: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let Some(punct) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) else {
+                return error(line, format!("unexpected character '{}'", c));
+            };
+            i += punct.len();
+            tokens.push((Token::Punct(punct), line));
+        }
+    }
+    tokens.push((Token::Eof, line));
+    Ok(tokens)
+}
+
+// ---------------------------------------------------------------------------
+// Syntax tree and parser
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Literal(Value),
+    Array(Vec<Expr>),
+    Var(String),
+    State(String),
+    Index(Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+/// Where an assignment stores its value
+#[derive(Debug)]
+enum Place {
+    Var(String),
+    State(String),
+}
+
+#[derive(Debug)]
+enum StmtKind {
+    Let(String, Expr),
+    Assign(Place, Option<Expr>, Option<BinOp>, Expr),
+    If(Vec<(Expr, Vec<Stmt>)>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    ForRange(String, Expr, Expr, Vec<Stmt>),
+    ForEach(String, Expr, Vec<Stmt>),
+    Expr(Expr),
+    Break,
+    Continue,
+}
+
+#[derive(Debug)]
+struct Stmt {
+    kind: StmtKind,
+    line: usize,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Token::Punct(p) if *p == punct)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(name) if name == keyword)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ScriptError> {
+        if self.eat_punct(punct) {
+            Ok(())
+        } else {
+            error(self.line(), format!("expected '{}', found {}", punct, describe(self.peek())))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ScriptError> {
+        match self.next() {
+            Token::Ident(name) if !is_keyword(&name) => Ok(name),
+            other => error(self.line(), format!("expected a name, found {}", describe(&other))),
+        }
+    }
+
+    /// Run a recursive rule one level deeper
+    fn nested<T>(&mut self, rule: impl FnOnce(&mut Self) -> Result<T, ScriptError>) -> Result<T, ScriptError> {
+        if self.depth == MAX_NESTING {
+            return error(self.line(), "nested too deeply");
+        }
+        self.depth += 1;
+        let result = rule(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn program(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        let mut statements = Vec::new();
+        while *self.peek() != Token::Eof {
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        self.nested(Self::block_body)
+    }
+
+    fn block_body(&mut self) -> Result<Vec<Stmt>, ScriptError> {
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        while !self.eat_punct("}") {
+            if *self.peek() == Token::Eof {
+                return error(self.line(), "missing '}'");
+            }
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, ScriptError> {
+        let line = self.line();
+        let kind = if self.is_keyword("let") {
+            self.next();
+            let name = self.ident()?;
+            self.expect("=")?;
+            let value = self.expression()?;
+            self.expect(";")?;
+            StmtKind::Let(name, value)
+        } else if self.is_keyword("if") {
+            let mut branches = Vec::new();
+            let mut otherwise = Vec::new();
+            self.next();
+            branches.push((self.expression()?, self.block()?));
+            while self.is_keyword("else") {
+                self.next();
+                if self.is_keyword("if") {
+                    self.next();
+                    branches.push((self.expression()?, self.block()?));
+                } else {
+                    otherwise = self.block()?;
+                    break;
+                }
+            }
+            StmtKind::If(branches, otherwise)
+        } else if self.is_keyword("while") {
+            self.next();
+            StmtKind::While(self.expression()?, self.block()?)
+        } else if self.is_keyword("for") {
+            self.next();
+            let name = self.ident()?;
+            if !self.is_keyword("in") {
+                return error(self.line(), "expected 'in'");
+            }
+            self.next();
+            let first = self.expression()?;
+            if self.eat_punct("..") {
+                let end = self.expression()?;
+                StmtKind::ForRange(name, first, end, self.block()?)
+            } else {
+                StmtKind::ForEach(name, first, self.block()?)
+            }
+        } else if self.is_keyword("break") || self.is_keyword("continue") {
+            let kind = if self.is_keyword("break") { StmtKind::Break } else { StmtKind::Continue };
+            self.next();
+            self.expect(";")?;
+            kind
+        } else {
+            let expr = self.expression()?;
+            let op = ["=", "+=", "-=", "*=", "/="].into_iter().find(|op| self.is_punct(op));
+            match op {
+                Some(op) => {
+                    self.next();
+                    let (place, index) = match expr {
+                        Expr::Var(name) => (Place::Var(name), None),
+                        Expr::State(name) => (Place::State(name), None),
+                        Expr::Index(base, index) => match *base {
+                            Expr::Var(name) => (Place::Var(name), Some(*index)),
+                            Expr::State(name) => (Place::State(name), Some(*index)),
+                            _ => return error(line, "can only assign to one level of indexing"),
+                        },
+                        _ => return error(line, "can only assign to a variable, state.name or an array element"),
+                    };
+                    let op = match op {
+                        "+=" => Some(BinOp::Add),
+                        "-=" => Some(BinOp::Sub),
+                        "*=" => Some(BinOp::Mul),
+                        "/=" => Some(BinOp::Div),
+                        _ => None,
+                    };
+                    let value = self.expression()?;
+                    self.expect(";")?;
+                    StmtKind::Assign(place, index, op, value)
+                }
+                None => {
+                    self.expect(";")?;
+                    StmtKind::Expr(expr)
+                }
+            }
+        };
+        Ok(Stmt { kind, line })
+    }
+
+    fn expression(&mut self) -> Result<Expr, ScriptError> {
+        self.nested(Self::or)
+    }
+
+    fn or(&mut self) -> Result<Expr, ScriptError> {
+        let mut left = self.and()?;
+        while self.eat_punct("||") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, ScriptError> {
+        let mut left = self.comparison()?;
+        while self.eat_punct("&&") {
+            left = Expr::And(Box::new(left), Box::new(self.comparison()?));
+        }
+        Ok(left)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ScriptError> {
+        let left = self.additive()?;
+        let op = match self.peek() {
+            Token::Punct("==") => BinOp::Eq,
+            Token::Punct("!=") => BinOp::Ne,
+            Token::Punct("<") => BinOp::Lt,
+            Token::Punct("<=") => BinOp::Le,
+            Token::Punct(">") => BinOp::Gt,
+            Token::Punct(">=") => BinOp::Ge,
+            _ => return Ok(left),
+        };
+        self.next();
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.additive()?)))
+    }
+
+    fn additive(&mut self) -> Result<Expr, ScriptError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Token::Punct("+") => BinOp::Add,
+                Token::Punct("-") => BinOp::Sub,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ScriptError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Punct("*") => BinOp::Mul,
+                Token::Punct("/") => BinOp::Div,
+                Token::Punct("%") => BinOp::Rem,
+                _ => return Ok(left),
+            };
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ScriptError> {
+        if self.eat_punct("-") {
+            Ok(Expr::Neg(Box::new(self.nested(Self::unary)?)))
+        } else if self.eat_punct("!") {
+            Ok(Expr::Not(Box::new(self.nested(Self::unary)?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, ScriptError> {
+        let mut expr = self.primary()?;
+        while self.eat_punct("[") {
+            let index = self.expression()?;
+            self.expect("]")?;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ScriptError> {
+        let line = self.line();
+        match self.next() {
+            Token::Num(n) => Ok(Expr::Literal(Value::Num(n))),
+            Token::Str(s) => Ok(Expr::Literal(Value::Str(s))),
+            Token::Punct("(") => {
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Punct("[") => {
+                let mut items = Vec::new();
+                if !self.eat_punct("]") {
+                    loop {
+                        items.push(self.expression()?);
+                        if self.eat_punct("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Expr::Array(items))
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "WIDTH" => Ok(Expr::Literal(Value::Num(MATRIX_WIDTH as f64))),
+                "HEIGHT" => Ok(Expr::Literal(Value::Num(MATRIX_HEIGHT as f64))),
+                "state" => {
+                    self.expect(".")?;
+                    Ok(Expr::State(self.ident()?))
+                }
+                _ if is_keyword(&name) => error(line, format!("unexpected '{}'", name)),
+                _ if self.eat_punct("(") => {
+                    let mut args = Vec::new();
+                    if !self.eat_punct(")") {
+                        loop {
+                            args.push(self.expression()?);
+                            if self.eat_punct(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    Ok(Expr::Call(name, args))
+                }
+                _ => Ok(Expr::Var(name)),
+            },
+            other => error(line, format!("unexpected {}", describe(&other))),
+        }
+    }
+}
+
+fn is_keyword(name: &str) -> bool {
+    matches!(
+        name,
+        "let" | "if" | "else" | "while" | "for" | "in" | "break" | "continue" | "true" | "false" | "state"
+    )
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Num(n) => format!("number {}", n),
+        Token::Str(_) => "a string".to_string(),
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Punct(p) => format!("'{}'", p),
+        Token::Eof => "end of script".to_string(),
+    }
+}
+
+/// A parsed script, ready to run
+#[derive(Debug)]
+pub struct Program {
+    statements: Vec<Stmt>,
+}
+
+impl Program {
+    pub fn parse(source: &str) -> Result<Self, ScriptError> {
+        let tokens = tokenize(source)?;
+        let statements = Parser { tokens, pos: 0, depth: 0 }.program()?;
+        Ok(Self { statements })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Interpreter
+
+/// What a script can see of the outside world for one frame
+pub struct ScriptInputs {
+    pub cpu: f64,
+    pub ram: f64,
+    pub net_down: f64,
+    pub net_up: f64,
+    pub disk: f64,
+    pub temperature: f64,
+    /// -1 without a battery
+    pub battery: f64,
+    pub charging: bool,
+    pub now: DateTime<Local>,
+}
+
+/// A running script with the state it keeps between frames
+pub struct ScriptInstance {
+    program: Program,
+    state: HashMap<String, Value>,
+    framebuffer: Vec<u8>,
+    frame: u64,
+    started: Instant,
+    rng: Rng,
+    /// Last thing passed to `print`
+    pub output: Option<String>,
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+}
+
+struct Run<'a> {
+    instance: &'a mut ScriptInstance,
+    inputs: &'a ScriptInputs,
+    scopes: Vec<HashMap<String, Value>>,
+    steps: u64,
+}
+
+impl ScriptInstance {
+    pub fn new(program: Program) -> Self {
+        Self {
+            program,
+            state: HashMap::new(),
+            framebuffer: vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT],
+            frame: 0,
+            started: Instant::now(),
+            rng: Rng::seeded(0x2545_F491_4F6C_DD1D),
+            output: None,
+        }
+    }
+
+    /// Run the script once and return the frame it drew; the framebuffer is
+    /// kept, so a script that doesn't `clear()` draws over its last frame
+    pub fn render(&mut self, inputs: &ScriptInputs) -> Result<Vec<u8>, ScriptError> {
+        let program = std::mem::replace(&mut self.program, Program { statements: Vec::new() });
+        let result = Run {
+            instance: self,
+            inputs,
+            scopes: vec![HashMap::new()],
+            steps: 0,
+        }
+        .block(&program.statements)
+        .map(|_| ());
+        self.program = program;
+        self.frame += 1;
+        result?;
+        Ok(self.framebuffer.clone())
+    }
+}
+
+impl Run<'_> {
+    fn step(&mut self, line: usize) -> Result<(), ScriptError> {
+        charge(&mut self.steps, line)
+    }
+
+    fn block(&mut self, statements: &[Stmt]) -> Result<Flow, ScriptError> {
+        self.scopes.push(HashMap::new());
+        let result = self.statements(statements);
+        self.scopes.pop();
+        result
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) -> Result<Flow, ScriptError> {
+        for statement in statements {
+            match self.statement(statement)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<Flow, ScriptError> {
+        let line = stmt.line;
+        self.step(line)?;
+        match &stmt.kind {
+            StmtKind::Let(name, value) => {
+                let value = self.eval(value, line)?;
+                self.scopes.last_mut().expect("a scope is always open").insert(name.clone(), value);
+            }
+            StmtKind::Assign(place, index, op, value) => {
+                let mut value = self.eval(value, line)?;
+                let index = index.as_ref().map(|index| self.eval(index, line)).transpose()?;
+                let current = match place {
+                    Place::Var(name) => self.var(name, line)?.clone(),
+                    Place::State(name) => self.instance.state.get(name).cloned().unwrap_or(Value::Num(0.0)),
+                };
+                let mut target = current;
+                let slot = match &index {
+                    Some(index) => array_slot(&mut target, index, line)?,
+                    None => &mut target,
+                };
+                if let Some(op) = op {
+                    value = binary(*op, slot.clone(), value, line)?;
+                }
+                *slot = value;
+                match place {
+                    Place::Var(name) => *self.var(name, line)? = target,
+                    Place::State(name) => {
+                        self.instance.state.insert(name.clone(), target);
+                    }
+                }
+            }
+            StmtKind::If(branches, otherwise) => {
+                for (condition, body) in branches {
+                    if self.condition(condition, line)? {
+                        return self.block(body);
+                    }
+                }
+                return self.block(otherwise);
+            }
+            StmtKind::While(condition, body) => {
+                while self.condition(condition, line)? {
+                    self.step(line)?;
+                    if let Flow::Break = self.block(body)? {
+                        break;
+                    }
+                }
+            }
+            StmtKind::ForRange(name, start, end, body) => {
+                let start = number(self.eval(start, line)?, line)?.floor() as i64;
+                let end = number(self.eval(end, line)?, line)?.ceil() as i64;
+                for i in start..end {
+                    self.step(line)?;
+                    if let Flow::Break = self.loop_body(name, Value::Num(i as f64), body)? {
+                        break;
+                    }
+                }
+            }
+            StmtKind::ForEach(name, items, body) => {
+                let Value::Array(items) = self.eval(items, line)? else {
+                    return error(line, "for ... in needs a range (a..b) or an array");
+                };
+                for item in items {
+                    self.step(line)?;
+                    if let Flow::Break = self.loop_body(name, item, body)? {
+                        break;
+                    }
+                }
+            }
+            StmtKind::Expr(expr) => {
+                self.eval(expr, line)?;
+            }
+            StmtKind::Break => return Ok(Flow::Break),
+            StmtKind::Continue => return Ok(Flow::Continue),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn loop_body(&mut self, name: &str, value: Value, body: &[Stmt]) -> Result<Flow, ScriptError> {
+        self.scopes.push(HashMap::from([(name.to_string(), value)]));
+        let result = self.block(body);
+        self.scopes.pop();
+        result
+    }
+
+    fn condition(&mut self, expr: &Expr, line: usize) -> Result<bool, ScriptError> {
+        match self.eval(expr, line)? {
+            Value::Bool(b) => Ok(b),
+            other => error(line, format!("condition must be true or false, not a {}", other.type_name())),
+        }
+    }
+
+    fn var(&mut self, name: &str, line: usize) -> Result<&mut Value, ScriptError> {
+        match self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            Some(value) => Ok(value),
+            None => error(line, format!("unknown variable '{}'", name)),
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, line: usize) -> Result<Value, ScriptError> {
+        Ok(match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Array(items) => Value::Array(items.iter().map(|item| self.eval(item, line)).collect::<Result<_, _>>()?),
+            Expr::Var(name) => self.var(name, line)?.clone(),
+            Expr::State(name) => self.instance.state.get(name).cloned().unwrap_or(Value::Num(0.0)),
+            Expr::Index(base, index) => {
+                let mut base = self.eval(base, line)?;
+                let index = self.eval(index, line)?;
+                array_slot(&mut base, &index, line)?.clone()
+            }
+            Expr::Neg(inner) => Value::Num(-number(self.eval(inner, line)?, line)?),
+            Expr::Not(inner) => Value::Bool(!self.condition(inner, line)?),
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left, line)?;
+                let right = self.eval(right, line)?;
+                binary(*op, left, right, line)?
+            }
+            Expr::And(left, right) => Value::Bool(self.condition(left, line)? && self.condition(right, line)?),
+            Expr::Or(left, right) => Value::Bool(self.condition(left, line)? || self.condition(right, line)?),
+            Expr::Call(name, args) => {
+                self.step(line)?;
+                let args = args.iter().map(|arg| self.eval(arg, line)).collect::<Result<Vec<_>, _>>()?;
+                self.call(name, args, line)?
+            }
+        })
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>, line: usize) -> Result<Value, ScriptError> {
+        let inputs = self.inputs;
+        let arity = |count: usize| -> Result<(), ScriptError> {
+            if args.len() == count {
+                Ok(())
+            } else {
+                error(line, format!("{}() takes {} argument(s), got {}", name, count, args.len()))
+            }
+        };
+        let num = |i: usize| number(args[i].clone(), line);
+        let int = |i: usize| number(args[i].clone(), line).map(|n| n.round() as i64);
+        let brightness = |i: usize| number(args[i].clone(), line).map(|n| n.clamp(0.0, 255.0).round() as u8);
+        let fb = &mut self.instance.framebuffer;
+
+        Ok(match name {
+            // Drawing
+            "clear" => {
+                arity(0)?;
+                fb.fill(0);
+                Value::Num(0.0)
+            }
+            "fill" => {
+                arity(1)?;
+                fb.fill(brightness(0)?);
+                Value::Num(0.0)
+            }
+            "set" => {
+                arity(3)?;
+                plot(fb, int(0)?, int(1)?, brightness(2)?);
+                Value::Num(0.0)
+            }
+            "get" => {
+                arity(2)?;
+                let (x, y) = (int(0)?, int(1)?);
+                let inside = (0..MATRIX_WIDTH as i64).contains(&x) && (0..MATRIX_HEIGHT as i64).contains(&y);
+                Value::Num(if inside { fb[x as usize + y as usize * MATRIX_WIDTH] as f64 } else { 0.0 })
+            }
+            "line" => {
+                arity(5)?;
+                let clamp_x = |x: i64| x.clamp(-LINE_MARGIN, MATRIX_WIDTH as i64 + LINE_MARGIN);
+                let clamp_y = |y: i64| y.clamp(-LINE_MARGIN, MATRIX_HEIGHT as i64 + LINE_MARGIN);
+                let (mut x, mut y) = (clamp_x(int(0)?), clamp_y(int(1)?));
+                let (x1, y1, b) = (clamp_x(int(2)?), clamp_y(int(3)?), brightness(4)?);
+                // Bresenham
+                let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+                let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+                let mut err = dx + dy;
+                loop {
+                    charge(&mut self.steps, line)?;
+                    plot(fb, x, y, b);
+                    if x == x1 && y == y1 {
+                        break;
+                    }
+                    let e2 = 2 * err;
+                    if e2 >= dy {
+                        err += dy;
+                        x += sx;
+                    }
+                    if e2 <= dx {
+                        err += dx;
+                        y += sy;
+                    }
+                }
+                Value::Num(0.0)
+            }
+            "rect" => {
+                arity(5)?;
+                let (x, y, w, h, b) = (int(0)?, int(1)?, int(2)?, int(3)?, brightness(4)?);
+                for py in y.max(0)..y.saturating_add(h).min(MATRIX_HEIGHT as i64) {
+                    for px in x.max(0)..x.saturating_add(w).min(MATRIX_WIDTH as i64) {
+                        plot(fb, px, py, b);
+                    }
+                }
+                Value::Num(0.0)
+            }
+            // Metrics
+            "cpu" => metric(arity(0), inputs.cpu)?,
+            "ram" => metric(arity(0), inputs.ram)?,
+            "net_down" => metric(arity(0), inputs.net_down)?,
+            "net_up" => metric(arity(0), inputs.net_up)?,
+            "disk" => metric(arity(0), inputs.disk)?,
+            "temperature" => metric(arity(0), inputs.temperature)?,
+            "battery" => metric(arity(0), inputs.battery)?,
+            "charging" => {
+                arity(0)?;
+                Value::Bool(inputs.charging)
+            }
+            // Time
+            "time" => metric(arity(0), self.instance.started.elapsed().as_secs_f64())?,
+            "frame" => metric(arity(0), self.instance.frame as f64)?,
+            "hour" => metric(arity(0), inputs.now.hour() as f64)?,
+            "minute" => metric(arity(0), inputs.now.minute() as f64)?,
+            "second" => metric(arity(0), inputs.now.second() as f64)?,
+            "weekday" => metric(arity(0), inputs.now.weekday().num_days_from_monday() as f64)?,
+            // Maths
+            "sin" | "cos" | "abs" | "floor" | "ceil" | "round" | "sqrt" => {
+                arity(1)?;
+                let x = num(0)?;
+                Value::Num(match name {
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "abs" => x.abs(),
+                    "floor" => x.floor(),
+                    "ceil" => x.ceil(),
+                    "round" => x.round(),
+                    _ => x.sqrt(),
+                })
+            }
+            "min" | "max" | "pow" => {
+                arity(2)?;
+                let (a, b) = (num(0)?, num(1)?);
+                Value::Num(match name {
+                    "min" => a.min(b),
+                    "max" => a.max(b),
+                    _ => a.powf(b),
+                })
+            }
+            "clamp" => {
+                arity(3)?;
+                Value::Num(num(0)?.max(num(1)?).min(num(2)?))
+            }
+            "random" => {
+                arity(0)?;
+                Value::Num(self.instance.rng.next_f64())
+            }
+            // Arrays and output
+            "len" => {
+                arity(1)?;
+                match &args[0] {
+                    Value::Array(items) => Value::Num(items.len() as f64),
+                    Value::Str(s) => Value::Num(s.chars().count() as f64),
+                    other => return error(line, format!("len() needs an array or string, not a {}", other.type_name())),
+                }
+            }
+            "push" => {
+                arity(2)?;
+                let mut args = args.into_iter();
+                let Some(Value::Array(mut items)) = args.next() else {
+                    return error(line, "push() needs an array first");
+                };
+                items.extend(args);
+                Value::Array(items)
+            }
+            "print" => {
+                let text = args.iter().map(Value::to_string).collect::<Vec<_>>().join(" ");
+                self.instance.output = Some(text);
+                Value::Num(0.0)
+            }
+            _ => return error(line, format!("unknown function '{}'", name)),
+        })
+    }
+}
+
+/// Count one unit of work against the frame's budget
+fn charge(steps: &mut u64, line: usize) -> Result<(), ScriptError> {
+    *steps += 1;
+    if *steps > STEP_BUDGET {
+        return error(line, "script ran too long (endless loop?)");
+    }
+    Ok(())
+}
+
+fn metric(arity: Result<(), ScriptError>, value: f64) -> Result<Value, ScriptError> {
+    arity.map(|_| Value::Num(value))
+}
+
+fn plot(framebuffer: &mut [u8], x: i64, y: i64, brightness: u8) {
+    if (0..MATRIX_WIDTH as i64).contains(&x) && (0..MATRIX_HEIGHT as i64).contains(&y) {
+        framebuffer[x as usize + y as usize * MATRIX_WIDTH] = brightness;
+    }
+}
+
+fn number(value: Value, line: usize) -> Result<f64, ScriptError> {
+    match value {
+        Value::Num(n) => Ok(n),
+        other => error(line, format!("expected a number, got a {}", other.type_name())),
+    }
+}
+
+fn array_slot<'v>(array: &'v mut Value, index: &Value, line: usize) -> Result<&'v mut Value, ScriptError> {
+    let Value::Array(items) = array else {
+        return error(line, format!("can't index a {}", array.type_name()));
+    };
+    let i = number(index.clone(), line)?;
+    let len = items.len();
+    match items.get_mut(i as usize) {
+        Some(slot) if i >= 0.0 => Ok(slot),
+        _ => error(line, format!("index {} out of range for an array of {}", i, len)),
+    }
+}
+
+fn binary(op: BinOp, left: Value, right: Value, line: usize) -> Result<Value, ScriptError> {
+    Ok(match (op, left, right) {
+        (BinOp::Eq, a, b) => Value::Bool(a == b),
+        (BinOp::Ne, a, b) => Value::Bool(a != b),
+        (BinOp::Add, Value::Str(a), b) => Value::Str(format!("{}{}", a, b)),
+        (BinOp::Add, a, Value::Str(b)) => Value::Str(format!("{}{}", a, b)),
+        (BinOp::Add, Value::Array(mut a), Value::Array(b)) => {
+            a.extend(b);
+            Value::Array(a)
+        }
+        (op, Value::Num(a), Value::Num(b)) => match op {
+            BinOp::Add => Value::Num(a + b),
+            BinOp::Sub => Value::Num(a - b),
+            BinOp::Mul => Value::Num(a * b),
+            BinOp::Div if b == 0.0 => return error(line, "division by zero"),
+            BinOp::Div => Value::Num(a / b),
+            BinOp::Rem if b == 0.0 => return error(line, "division by zero"),
+            // Always non-negative, so `(x - 1) % WIDTH` wraps around
+            BinOp::Rem => Value::Num(a.rem_euclid(b)),
+            BinOp::Lt => Value::Bool(a < b),
+            BinOp::Le => Value::Bool(a <= b),
+            BinOp::Gt => Value::Bool(a > b),
+            BinOp::Ge => Value::Bool(a >= b),
+            BinOp::Eq | BinOp::Ne => unreachable!("handled above"),
+        },
+        (op, a, b) => return error(line, format!("can't apply {:?} to a {} and a {}", op, a.type_name(), b.type_name())),
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Script presets
+
+/// Folder scripts are loaded from, next to `custom_presets.json`
+pub const SCRIPT_DIR: &str = "scripts";
+
+/// File extension of preset scripts
+pub const SCRIPT_EXTENSION: &str = "led";
+
+/// Preset ids for scripts are `script:<file name without extension>`
+pub const SCRIPT_PREFIX: &str = "script:";
+
+/// The scripts in `SCRIPT_DIR`. Cheap to clone, so render threads get
+/// their own copy after each reload; each script preset parses and runs
+/// its own instance
+#[derive(Clone, Default)]
+pub struct ScriptManager {
+    sources: Arc<BTreeMap<String, String>>,
+}
+
+impl ScriptManager {
+    /// Load every script; syntax errors show up once a script is used
+    pub fn load() -> Self {
+        let mut manager = Self::default();
+        manager.reload();
+        manager
+    }
+
+    /// Re-read the script folder; returns the syntax errors found
+    pub fn reload(&mut self) -> Vec<String> {
+        let mut sources = BTreeMap::new();
+        let mut errors = Vec::new();
+        if let Ok(entries) = fs::read_dir(SCRIPT_DIR) {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                if path.extension().and_then(|e| e.to_str()) != Some(SCRIPT_EXTENSION) {
+                    continue;
+                }
+                let (Some(name), Ok(source)) = (path.file_stem().and_then(|s| s.to_str()), fs::read_to_string(&path)) else {
+                    continue;
+                };
+                if let Err(e) = Program::parse(&source) {
+                    errors.push(format!("Script '{}' {}", name, e));
+                }
+                sources.insert(name.to_string(), source);
+            }
+        }
+        self.sources = Arc::new(sources);
+        errors
+    }
+
+    /// Script names, sorted
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.sources.keys()
+    }
+
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.sources.get(name).map(String::as_str)
+    }
+
+    /// The script a preset id refers to, if it names one
+    pub fn script_name(preset: &str) -> Option<&str> {
+        preset.strip_prefix(SCRIPT_PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> ScriptInputs {
+        ScriptInputs {
+            cpu: 42.0,
+            ram: 0.0,
+            net_down: 0.0,
+            net_up: 0.0,
+            disk: 0.0,
+            temperature: 0.0,
+            battery: -1.0,
+            charging: false,
+            now: Local::now(),
+        }
+    }
+
+    fn run(source: &str) -> Result<ScriptInstance, ScriptError> {
+        let mut instance = ScriptInstance::new(Program::parse(source)?);
+        instance.render(&inputs())?;
+        Ok(instance)
+    }
+
+    /// What the script passed to `print`
+    fn printed(source: &str) -> String {
+        run(source).unwrap().output.unwrap_or_default()
+    }
+
+    fn failure(source: &str) -> ScriptError {
+        run(source).err().expect("script should fail")
+    }
+
+    #[test]
+    fn arithmetic_follows_precedence() {
+        assert_eq!(printed("print(1 + 2 * 3 - 4 / 2);"), "5");
+        assert_eq!(printed("print((1 + 2) * 3, -2 * -3, 7 % 3, (0 - 1) % 8);"), "9 6 1 7");
+        assert_eq!(printed("print(1 < 2 && 2 <= 2, 1 > 2 || !true, 3 == 3, 3 != 3);"), "true false true false");
+        assert_eq!(printed("print(\"cpu \" + cpu(), [1] + [2, 3], 0.5);"), "cpu 42 [1, 2, 3] 0.5");
+    }
+
+    #[test]
+    fn statements_and_state() {
+        let source = "
+            let total = 0;
+            for i in 0..10 {
+                if i % 2 == 0 { continue; } else if i > 7 { break; }
+                total += i;
+            }
+            let items = push([], total);
+            state.frames += 1;
+            print(total, len(items), items[0], state.frames);
+        ";
+        let mut instance = run(source).unwrap();
+        assert_eq!(instance.output.as_deref(), Some("16 1 16 1"));
+        instance.render(&inputs()).unwrap();
+        assert_eq!(instance.output.as_deref(), Some("16 1 16 2"));
+    }
+
+    #[test]
+    fn drawing_lands_in_the_framebuffer() {
+        let mut instance = ScriptInstance::new(Program::parse("clear(); line(0, 0, 3, 3, 200); rect(5, 0, 2, 2, 9);").unwrap());
+        let frame = instance.render(&inputs()).unwrap();
+        for i in 0..4 {
+            assert_eq!(frame[i + i * MATRIX_WIDTH], 200);
+        }
+        assert_eq!(frame[1], 0);
+        assert_eq!(frame[5], 9);
+        assert_eq!(frame[6 + MATRIX_WIDTH], 9);
+        assert_eq!(frame.iter().filter(|&&b| b != 0).count(), 8);
+    }
+
+    #[test]
+    fn errors_report_their_line() {
+        let error = failure("let x = 1;\nlet y = x / 0;");
+        assert_eq!((error.line, error.message.as_str()), (2, "division by zero"));
+
+        let error = failure("\n\nprint(missing);");
+        assert_eq!((error.line, error.message.as_str()), (3, "unknown variable 'missing'"));
+
+        assert_eq!(failure("let a = 1 + true;").message, "can't apply Add to a number and a bool");
+        assert_eq!(failure("nope();").message, "unknown function 'nope'");
+        assert_eq!(failure("sin(1, 2);").message, "sin() takes 1 argument(s), got 2");
+        assert_eq!(failure("let x = (1;").message, "expected ')', found ';'");
+        assert_eq!(failure("if true {").message, "missing '}'");
+    }
+
+    #[test]
+    fn endless_loops_run_out_of_budget() {
+        let error = failure("let i = 0;\nwhile true { i += 1; }");
+        assert_eq!(error.message, "script ran too long (endless loop?)");
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn long_lines_are_clamped_and_charged() {
+        // Endpoints far off the matrix are clamped, so this is quick and safe
+        let mut instance = run("line(-99999999999999999999, 2, 99999999999999999999, 2, 255);").unwrap();
+        let frame = instance.render(&inputs()).unwrap();
+        assert!(frame[2 * MATRIX_WIDTH..3 * MATRIX_WIDTH].iter().all(|&b| b == 255));
+
+        // Each plotted pixel counts, so many lines in one frame are stopped
+        let error = failure("for i in 0..10000 { line(-1000, 0, 1000, 0, 255); }");
+        assert_eq!(error.message, "script ran too long (endless loop?)");
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let deep = format!("let x = {}1{};", "(".repeat(1000), ")".repeat(1000));
+        assert_eq!(Program::parse(&deep).unwrap_err().message, "nested too deeply");
+        assert_eq!(Program::parse(&format!("let x = {}1;", "-".repeat(1000))).unwrap_err().message, "nested too deeply");
+        let blocks = format!("{}{}", "if true { ".repeat(1000), "}".repeat(1000));
+        assert_eq!(Program::parse(&blocks).unwrap_err().message, "nested too deeply");
+
+        let shallow = format!("print({}1{});", "(".repeat(20), ")".repeat(20));
+        assert_eq!(printed(&shallow), "1");
+    }
+
+    #[test]
+    fn random_is_repeatable_and_in_range() {
+        let source = "let r = []; for i in 0..100 { r = push(r, random()); } print(r);";
+        let first = printed(source);
+        assert_eq!(first, printed(source));
+        let mut instance = run("state.r = random();").unwrap();
+        instance.render(&inputs()).unwrap();
+        let Some(Value::Num(r)) = instance.state.get("r") else { panic!("no state") };
+        assert!((0.0..1.0).contains(r));
+    }
+}
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::alerts::AlertRule;
+use crate::api::ApiSettings;
+use crate::audio::AudioSettings;
+use crate::idle::IdleSettings;
+use crate::mqtt::MqttSettings;
+use crate::notifications::NotificationSettings;
+use crate::params::PresetParams;
+use crate::playlist::Playlist;
+use crate::schedule::ScheduleRule;
+use crate::transition::TransitionSettings;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub alert_rules: Vec<AlertRule>,
+    pub schedules: Vec<ScheduleRule>,
+    pub left_playlist: Playlist,
+    pub right_playlist: Playlist,
+    pub left_transition: TransitionSettings,
+    pub right_transition: TransitionSettings,
+    /// Preset parameters per side, by preset id
+    pub left_preset_params: HashMap<String, PresetParams>,
+    pub right_preset_params: HashMap<String, PresetParams>,
+    pub idle: IdleSettings,
+    pub power: PowerSettings,
+    pub temperature: TemperatureSettings,
+    pub notifications: NotificationSettings,
+    pub api: ApiSettings,
+    pub mqtt: MqttSettings,
+    pub audio: AudioSettings,
+    /// Set when settings.json could not be read; saving is refused so the
+    /// defaults standing in never replace the user's file
+    #[serde(skip)]
+    damaged: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct PowerSettings {
+    pub sleep_on_suspend: bool,
+    pub sleep_when_lid_closed: bool,
+}
+
+impl Default for PowerSettings {
+    fn default() -> Self {
+        Self {
+            sleep_on_suspend: true,
+            sleep_when_lid_closed: true,
+        }
+    }
+}
+
+/// Temperatures (°C) at which the temperature preset turns to its warning
+/// and critical looks
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct TemperatureSettings {
+    pub warning: f32,
+    pub critical: f32,
+}
+
+impl Default for TemperatureSettings {
+    fn default() -> Self {
+        Self {
+            warning: 75.0,
+            critical: 90.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load_from_file() -> Result<Self, String> {
+        Self::load(Path::new(SETTINGS_FILE))
+    }
+
+    /// Read settings from `path`; a missing file gives the defaults, one
+    /// that can't be read or parsed is an error
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                serde_json::from_str(&content).map_err(|e| format!("{} is damaged: {}", path.display(), e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("could not read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Defaults standing in for settings that failed to load; they refuse to
+    /// save until `set_aside_damaged` moved the file out of the way
+    pub fn damaged() -> Self {
+        Self {
+            damaged: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn damaged_path() -> PathBuf {
+        PathBuf::from(format!("{}.damaged", SETTINGS_FILE))
+    }
+
+    /// Keep the unreadable file as settings.json.damaged and allow saving
+    pub fn set_aside_damaged(&mut self) -> io::Result<()> {
+        match fs::rename(SETTINGS_FILE, Self::damaged_path()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        self.damaged = false;
+        Ok(())
+    }
+
+    pub fn save_to_file(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.damaged {
+            return Err(format!("{} could not be loaded; choose to use the defaults first", SETTINGS_FILE).into());
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(SETTINGS_FILE, json)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_gives_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = Settings::load(&dir.path().join("settings.json")).unwrap();
+        assert!(settings.alert_rules.is_empty());
+        assert!(settings.power.sleep_on_suspend);
+    }
+
+    #[test]
+    fn damaged_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, "{ \"idle\": ").unwrap();
+        let error = Settings::load(&path).err().unwrap();
+        assert!(error.contains("is damaged"), "{}", error);
+
+        // The stand-in defaults never save over it
+        assert!(Settings::damaged().save_to_file().is_err());
+    }
+
+    #[test]
+    fn unknown_and_missing_fields_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        fs::write(&path, r#"{"power": {"sleep_on_suspend": false}, "retired": 1}"#).unwrap();
+        let settings = Settings::load(&path).unwrap();
+        assert!(!settings.power.sleep_on_suspend);
+        assert!(settings.power.sleep_when_lid_closed);
+    }
+}
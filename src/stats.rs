@@ -1,7 +1,71 @@
-use sysinfo::System;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+use sysinfo::{Components, Disks, Networks, System};
+
+/// Lowest throughput (bytes/sec) the network bars scale against, so idle
+/// background traffic doesn't show up as a full bar
+const NETWORK_SCALE_FLOOR: f64 = 64.0 * 1024.0;
+/// Fraction of the previous peak kept on each refresh when auto-scaling
+const NETWORK_PEAK_DECAY: f64 = 0.95;
+/// Lowest throughput (bytes/sec) the disk I/O sparkline scales against
+const DISK_IO_SCALE_FLOOR: f64 = 1024.0 * 1024.0;
+/// Number of disk I/O samples kept, one per matrix row
+const DISK_IO_HISTORY_LEN: usize = 34;
+/// Size of a sector as reported by /proc/diskstats
+#[cfg(target_os = "linux")]
+const DISKSTATS_SECTOR_SIZE: u64 = 512;
+
+#[derive(Clone, Copy, Default)]
+pub struct NetworkRate {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct DiskIoRate {
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TemperatureReading {
+    pub label: String,
+    pub celsius: f32,
+    pub critical: Option<f32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FanReading {
+    pub label: String,
+    pub rpm: u32,
+    /// Top speed the driver reports, when it knows it
+    pub max_rpm: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct DiskSpace {
+    pub mount_point: String,
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+    pub usage_percent: u8,
+}
 
 pub struct Stats {
     sys: System,
+    networks: Networks,
+    last_network_refresh: Instant,
+    network_rates: HashMap<String, NetworkRate>,
+    /// Recent peak throughput per interface, and for all of them under `None`
+    network_peaks: HashMap<Option<String>, NetworkRate>,
+    disks: Disks,
+    last_disk_io_refresh: Instant,
+    #[cfg(target_os = "linux")]
+    disk_io_counters: HashMap<String, (u64, u64)>,
+    disk_io_rate: DiskIoRate,
+    disk_io_history: VecDeque<DiskIoRate>,
+    components: Components,
+    #[cfg(target_os = "linux")]
+    hwmon: crate::hwmon::HwmonSensors,
 }
 
 impl Stats {
@@ -9,22 +73,50 @@ impl Stats {
         let mut sys = System::new();
         sys.refresh_cpu();
         sys.refresh_memory();
-        Self { sys }
+        Self {
+            sys,
+            networks: Networks::new_with_refreshed_list(),
+            last_network_refresh: Instant::now(),
+            network_rates: HashMap::new(),
+            network_peaks: HashMap::new(),
+            disks: Disks::new_with_refreshed_list(),
+            last_disk_io_refresh: Instant::now(),
+            #[cfg(target_os = "linux")]
+            disk_io_counters: HashMap::new(),
+            disk_io_rate: DiskIoRate::default(),
+            disk_io_history: VecDeque::with_capacity(DISK_IO_HISTORY_LEN),
+            components: Components::new_with_refreshed_list(),
+            #[cfg(target_os = "linux")]
+            hwmon: crate::hwmon::HwmonSensors::default(),
+        }
     }
 
+    /// Take one sample of CPU, memory, network, disk I/O and sensors; the
+    /// getters below read from it
     pub fn refresh(&mut self) {
         self.sys.refresh_cpu();
         self.sys.refresh_memory();
+        self.refresh_networks();
+        self.refresh_disk_io();
+        self.refresh_sensors();
     }
 
-    pub fn cpu_usage(&mut self) -> u8 {
-        self.sys.refresh_cpu();
+    /// Refresh temperature components, and on Linux read the hwmon tree once
+    /// for both temperatures and fans
+    fn refresh_sensors(&mut self) {
+        self.components.refresh();
+        #[cfg(target_os = "linux")]
+        {
+            self.hwmon = crate::hwmon::read_sensors(std::path::Path::new(crate::hwmon::HWMON_ROOT));
+        }
+    }
+
+    pub fn cpu_usage(&self) -> u8 {
         let usage = self.sys.global_cpu_info().cpu_usage();
         (usage.clamp(0.0, 100.0) as u8).min(255)
     }
 
-    pub fn ram_usage(&mut self) -> u8 {
-        self.sys.refresh_memory();
+    pub fn ram_usage(&self) -> u8 {
         let total_memory = self.sys.total_memory();
         let used_memory = self.sys.used_memory();
         let usage = if total_memory > 0 {
@@ -34,4 +126,343 @@ impl Stats {
         };
         (usage.clamp(0.0, 100.0) as u8).min(255)
     }
+
+    /// Refresh network counters, recompute per-interface throughput from the
+    /// bytes moved since the previous refresh and decay the auto-scale peaks
+    pub fn refresh_networks(&mut self) {
+        let elapsed = self.last_network_refresh.elapsed().as_secs_f64();
+        self.last_network_refresh = Instant::now();
+        self.networks.refresh_list();
+
+        self.network_rates.clear();
+        if elapsed <= 0.0 {
+            return;
+        }
+        for (name, data) in self.networks.list() {
+            self.network_rates.insert(
+                name.clone(),
+                NetworkRate {
+                    rx_bytes_per_sec: data.received() as f64 / elapsed,
+                    tx_bytes_per_sec: data.transmitted() as f64 / elapsed,
+                },
+            );
+        }
+
+        let mut interfaces: Vec<Option<String>> = self.network_rates.keys().cloned().map(Some).collect();
+        interfaces.push(None);
+        for interface in interfaces {
+            let rate = self.network_rate(interface.as_deref());
+            let peak = self.network_peaks.entry(interface).or_default();
+            peak.rx_bytes_per_sec = decay_peak(peak.rx_bytes_per_sec, rate.rx_bytes_per_sec);
+            peak.tx_bytes_per_sec = decay_peak(peak.tx_bytes_per_sec, rate.tx_bytes_per_sec);
+        }
+    }
+
+    /// Names of all known network interfaces, sorted
+    pub fn network_interfaces(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.networks.list().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Throughput of a single interface, or the sum of all interfaces for `None`
+    pub fn network_rate(&self, interface: Option<&str>) -> NetworkRate {
+        match interface {
+            Some(name) => self.network_rates.get(name).copied().unwrap_or_default(),
+            None => self
+                .network_rates
+                .values()
+                .fold(NetworkRate::default(), |acc, rate| NetworkRate {
+                    rx_bytes_per_sec: acc.rx_bytes_per_sec + rate.rx_bytes_per_sec,
+                    tx_bytes_per_sec: acc.tx_bytes_per_sec + rate.tx_bytes_per_sec,
+                }),
+        }
+    }
+
+    /// (download, upload) as a percentage of the recent peak throughput as of
+    /// the last refresh, so the bars auto-scale to the link's activity
+    pub fn network_usage(&self, interface: Option<&str>) -> (u8, u8) {
+        let rate = self.network_rate(interface);
+        let peak = self
+            .network_peaks
+            .get(&interface.map(str::to_string))
+            .copied()
+            .unwrap_or_default();
+
+        let rx_peak = peak.rx_bytes_per_sec.max(NETWORK_SCALE_FLOOR);
+        let tx_peak = peak.tx_bytes_per_sec.max(NETWORK_SCALE_FLOOR);
+        let rx = (rate.rx_bytes_per_sec / rx_peak * 100.0).clamp(0.0, 100.0) as u8;
+        let tx = (rate.tx_bytes_per_sec / tx_peak * 100.0).clamp(0.0, 100.0) as u8;
+        (rx, tx)
+    }
+
+    /// Refresh and return space usage for every mounted disk
+    pub fn disk_space(&mut self) -> Vec<DiskSpace> {
+        self.disks.refresh_list();
+        self.disks
+            .list()
+            .iter()
+            .map(|disk| {
+                let total = disk.total_space();
+                let available = disk.available_space();
+                let usage = if total > 0 {
+                    (total - available) as f64 / total as f64 * 100.0
+                } else {
+                    0.0
+                };
+                DiskSpace {
+                    mount_point: disk.mount_point().to_string_lossy().into_owned(),
+                    total_bytes: total,
+                    available_bytes: available,
+                    usage_percent: usage.clamp(0.0, 100.0) as u8,
+                }
+            })
+            .collect()
+    }
+
+    /// Recompute disk read/write throughput since the previous refresh and
+    /// push it onto the sparkline history
+    pub fn refresh_disk_io(&mut self) {
+        let elapsed = self.last_disk_io_refresh.elapsed().as_secs_f64();
+        self.last_disk_io_refresh = Instant::now();
+
+        let (read_bytes, write_bytes) = self.disk_io_bytes_since_refresh();
+        self.disk_io_rate = if elapsed > 0.0 {
+            DiskIoRate {
+                read_bytes_per_sec: read_bytes as f64 / elapsed,
+                write_bytes_per_sec: write_bytes as f64 / elapsed,
+            }
+        } else {
+            DiskIoRate::default()
+        };
+
+        if self.disk_io_history.len() == DISK_IO_HISTORY_LEN {
+            self.disk_io_history.pop_front();
+        }
+        self.disk_io_history.push_back(self.disk_io_rate);
+    }
+
+    /// Bytes read and written across all physical disks since the last call
+    #[cfg(target_os = "linux")]
+    fn disk_io_bytes_since_refresh(&mut self) -> (u64, u64) {
+        let Ok(content) = std::fs::read_to_string("/proc/diskstats") else {
+            return (0, 0);
+        };
+        let counters: HashMap<String, (u64, u64)> = parse_diskstats(&content)
+            .into_iter()
+            .filter(|(name, _)| is_physical_disk(name))
+            .collect();
+
+        let mut read = 0;
+        let mut written = 0;
+        for (name, (sectors_read, sectors_written)) in &counters {
+            if let Some((old_read, old_written)) = self.disk_io_counters.get(name) {
+                read += sectors_read.saturating_sub(*old_read) * DISKSTATS_SECTOR_SIZE;
+                written += sectors_written.saturating_sub(*old_written) * DISKSTATS_SECTOR_SIZE;
+            }
+        }
+        self.disk_io_counters = counters;
+        (read, written)
+    }
+
+    /// Bytes read and written by all processes since the last call, used
+    /// where there is no system-wide disk counter to read
+    #[cfg(not(target_os = "linux"))]
+    fn disk_io_bytes_since_refresh(&mut self) -> (u64, u64) {
+        self.sys.refresh_processes();
+        self.sys.processes().values().fold((0, 0), |(read, written), process| {
+            let usage = process.disk_usage();
+            (read + usage.read_bytes, written + usage.written_bytes)
+        })
+    }
+
+    pub fn disk_io_rate(&self) -> DiskIoRate {
+        self.disk_io_rate
+    }
+
+    /// Disk I/O history, oldest first, as (read, write) percentages of the
+    /// busiest sample so the sparkline auto-scales
+    pub fn disk_io_history(&self) -> Vec<(u8, u8)> {
+        let peak = self
+            .disk_io_history
+            .iter()
+            .map(|rate| rate.read_bytes_per_sec.max(rate.write_bytes_per_sec))
+            .fold(DISK_IO_SCALE_FLOOR, f64::max);
+        self.disk_io_history
+            .iter()
+            .map(|rate| {
+                (
+                    (rate.read_bytes_per_sec / peak * 100.0).clamp(0.0, 100.0) as u8,
+                    (rate.write_bytes_per_sec / peak * 100.0).clamp(0.0, 100.0) as u8,
+                )
+            })
+            .collect()
+    }
+
+    /// All temperature sensors as of the last refresh, hottest first
+    /// On Linux, hwmon sensors that sysinfo doesn't report are added too
+    pub fn temperatures(&self) -> Vec<TemperatureReading> {
+        let mut readings: Vec<TemperatureReading> = self
+            .components
+            .list()
+            .iter()
+            .filter(|c| c.temperature().is_finite())
+            .map(|c| TemperatureReading {
+                label: c.label().to_string(),
+                celsius: c.temperature(),
+                critical: c.critical(),
+            })
+            .collect();
+
+        #[cfg(target_os = "linux")]
+        for reading in &self.hwmon.temperatures {
+            if !readings.iter().any(|r| r.label == reading.label) {
+                readings.push(reading.clone());
+            }
+        }
+
+        readings.sort_by(|a, b| b.celsius.total_cmp(&a.celsius));
+        readings
+    }
+
+    /// Fan speeds as of the last refresh, only available through hwmon on Linux
+    pub fn fans(&self) -> Vec<FanReading> {
+        #[cfg(target_os = "linux")]
+        {
+            self.hwmon.fans.clone()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            Vec::new()
+        }
+    }
+}
+
+/// Previous peak after one refresh's decay, raised to the current rate
+fn decay_peak(peak: f64, rate: f64) -> f64 {
+    (peak * NETWORK_PEAK_DECAY).max(rate).max(NETWORK_SCALE_FLOOR)
+}
+
+/// Parse `/proc/diskstats` into sectors (read, written) per device name
+#[cfg(target_os = "linux")]
+fn parse_diskstats(content: &str) -> HashMap<String, (u64, u64)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 10 {
+                return None;
+            }
+            let sectors_read = fields[5].parse().ok()?;
+            let sectors_written = fields[9].parse().ok()?;
+            Some((fields[2].to_string(), (sectors_read, sectors_written)))
+        })
+        .collect()
+}
+
+/// Whole physical disks only, so partitions and virtual devices layered on
+/// top of them aren't counted twice
+#[cfg(target_os = "linux")]
+fn is_physical_disk(name: &str) -> bool {
+    is_disk_in(name, std::path::Path::new("/sys/block"))
+}
+
+/// Partitions have no entry of their own in `sys_block`, whole disks do
+#[cfg(target_os = "linux")]
+fn is_disk_in(name: &str, sys_block: &std::path::Path) -> bool {
+    const VIRTUAL_PREFIXES: [&str; 5] = ["loop", "ram", "zram", "dm-", "md"];
+    if VIRTUAL_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+        return false;
+    }
+    sys_block.join(name).exists()
+}
+
+/// Format a byte count for display, e.g. "12.3 GB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Format a byte rate for display, e.g. "1.2 MB/s"
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    const UNITS: [&str; 4] = ["B/s", "KB/s", "MB/s", "GB/s"];
+    let mut value = bytes_per_sec;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    const DISKSTATS: &str = "\
+   7       0 loop0 52 0 2136 12 0 0 0 0 0 40 12 0 0 0 0 0 0
+   1       0 ram0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ 259       0 nvme0n1 183221 51302 11012446 29417 251764 180034 16113258 201844 0 183900 246017 0 0 0 0 10231 14756
+ 259       1 nvme0n1p1 422 1120 16866 112 2 0 2 0 0 140 112 0 0 0 0 0 0
+ 259       2 nvme0n1p2 182694 50182 10991052 29276 241533 180034 16113256 199931 0 183704 229207 0 0 0 0 0 0
+   8       0 sda 2048 10 81920 900 512 3 4096 300 0 1000 1200
+   8       1 sda1 2000 10 80000 880 500 3 4000 290 0 980 1170
+ 253       0 dm-0 182530 0 10988394 39212 421567 0 16113256 1094468 0 191804 1133680 0 0 0 0 0 0
+ 252       0 zram0 97 0 4872 1 1021 0 8168 9 0 24 10 0 0 0 0 0 0
+   9       0 md127 10 0 80 0 20 0 160 0 0 0 0
+   8      16 sdb 1 2 3
+";
+
+    #[test]
+    fn diskstats_give_sectors_per_device() {
+        let counters = parse_diskstats(DISKSTATS);
+        let cases = [
+            ("nvme0n1", Some((11012446, 16113258))),
+            ("nvme0n1p2", Some((10991052, 16113256))),
+            ("sda", Some((81920, 4096))),
+            ("loop0", Some((2136, 0))),
+            // Too few fields to be a disk line
+            ("sdb", None),
+            ("sdc", None),
+        ];
+        for (name, sectors) in cases {
+            assert_eq!(counters.get(name).copied(), sectors, "{}", name);
+        }
+    }
+
+    #[test]
+    fn only_whole_physical_disks_count() {
+        let sys_block = tempfile::tempdir().unwrap();
+        // /sys/block lists whole disks, virtual ones included, but no partitions
+        for disk in ["nvme0n1", "sda", "loop0", "ram0", "zram0", "dm-0", "md127"] {
+            std::fs::create_dir(sys_block.path().join(disk)).unwrap();
+        }
+
+        let cases = [
+            ("nvme0n1", true),
+            ("sda", true),
+            ("nvme0n1p1", false),
+            ("nvme0n1p2", false),
+            ("sda1", false),
+            ("loop0", false),
+            ("ram0", false),
+            ("zram0", false),
+            ("dm-0", false),
+            ("md127", false),
+        ];
+        for (name, physical) in cases {
+            assert_eq!(is_disk_in(name, sys_block.path()), physical, "{}", name);
+        }
+
+        let mut counted: Vec<String> =
+            parse_diskstats(DISKSTATS).into_keys().filter(|name| is_disk_in(name, sys_block.path())).collect();
+        counted.sort();
+        assert_eq!(counted, ["nvme0n1", "sda"]);
+    }
 }
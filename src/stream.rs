@@ -0,0 +1,320 @@
+// src/stream.rs
+use sha1::{Digest, Sha1};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::api::Side;
+use crate::device::Device;
+use crate::presets::{image_data_to_command, MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// A side counts as streaming for this long after its last frame, so the
+/// app's own presets stay off the matrix between frames
+const STREAM_HOLD: Duration = Duration::from_secs(1);
+
+/// How often each client is told how many frames were sent and dropped
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest WebSocket message accepted; frames are far smaller
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Magic GUID from RFC 6455 used to answer the opening handshake
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const FRAME_BYTES: usize = MATRIX_WIDTH * MATRIX_HEIGHT;
+
+/// The newest frame waiting for one side's serial link
+#[derive(Default)]
+struct SideStream {
+    device: Mutex<Option<Arc<Mutex<Device>>>>,
+    pending: Mutex<Option<Vec<u8>>>,
+    ready: Condvar,
+    last_frame: Mutex<Option<Instant>>,
+    sent: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl SideStream {
+    /// Queue a frame, replacing one the serial link hasn't taken yet
+    fn push(&self, frame: Vec<u8>) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.replace(frame).is_some() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        *self.last_frame.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+        self.ready.notify_one();
+    }
+
+    /// Forward frames to the device one at a time; the blocking serial write
+    /// paces the stream to what the link can carry
+    fn run_writer(&self) {
+        loop {
+            let frame = {
+                let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    if let Some(frame) = pending.take() {
+                        break frame;
+                    }
+                    pending = self.ready.wait(pending).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            let device = self.device.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            match device {
+                Some(device) => {
+                    if let Ok(mut dev) = device.lock() {
+                        dev.send(image_data_to_command(&frame));
+                    }
+                    self.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                None => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// Routes streamed frames to the matrices, one writer thread per side
+#[derive(Default)]
+pub struct StreamHub {
+    left: SideStream,
+    right: SideStream,
+}
+
+impl StreamHub {
+    pub fn new() -> Arc<Self> {
+        let hub = Arc::new(Self::default());
+        for side in [Side::Left, Side::Right] {
+            let hub = hub.clone();
+            thread::spawn(move || hub.side(side).run_writer());
+        }
+        hub
+    }
+
+    fn side(&self, side: Side) -> &SideStream {
+        match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        }
+    }
+
+    /// Point the writers at the currently connected devices
+    pub fn set_devices(&self, left: Option<Arc<Mutex<Device>>>, right: Option<Arc<Mutex<Device>>>) {
+        *self.left.device.lock().unwrap_or_else(|e| e.into_inner()) = left;
+        *self.right.device.lock().unwrap_or_else(|e| e.into_inner()) = right;
+    }
+
+    /// Whether a client has sent this side a frame recently
+    pub fn is_streaming(&self, side: Side) -> bool {
+        let last_frame = *self.side(side).last_frame.lock().unwrap_or_else(|e| e.into_inner());
+        last_frame.is_some_and(|at| at.elapsed() < STREAM_HOLD)
+    }
+
+    /// Queue one message's worth of pixels: 306 bytes for `side`, or 612
+    /// bytes for an 18x34 image spanning both matrices, left half first
+    pub fn push(&self, side: Side, data: &[u8]) -> Result<(), String> {
+        match data.len() {
+            FRAME_BYTES => self.side(side).push(data.to_vec()),
+            len if len == 2 * FRAME_BYTES => {
+                let (mut left, mut right) = (Vec::with_capacity(FRAME_BYTES), Vec::with_capacity(FRAME_BYTES));
+                for row in data.chunks(2 * MATRIX_WIDTH) {
+                    left.extend_from_slice(&row[..MATRIX_WIDTH]);
+                    right.extend_from_slice(&row[MATRIX_WIDTH..]);
+                }
+                self.left.push(left);
+                self.right.push(right);
+            }
+            len => {
+                return Err(format!(
+                    "frames must be {} or {} bytes, got {}",
+                    FRAME_BYTES,
+                    2 * FRAME_BYTES,
+                    len
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    fn stats_json(&self) -> String {
+        let side = |s: &SideStream| {
+            serde_json::json!({
+                "sent": s.sent.load(Ordering::Relaxed),
+                "dropped": s.dropped.load(Ordering::Relaxed),
+            })
+        };
+        serde_json::json!({ "left": side(&self.left), "right": side(&self.right) }).to_string()
+    }
+}
+
+/// `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    let mut sha = Sha1::new();
+    sha.update(key.trim().as_bytes());
+    sha.update(WEBSOCKET_GUID.as_bytes());
+    base64_encode(&sha.finalize())
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// WebSocket opcodes
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Finish the opening handshake and serve the connection on its own thread
+/// Binary messages are frames for `side` (or both sides when spanned);
+/// once a second the client gets a text message with sent/dropped counts
+pub fn serve(stream: TcpStream, key: &str, side: Side, hub: Arc<StreamHub>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )?;
+    stream.set_read_timeout(None)?;
+
+    let writer = Arc::new(Mutex::new(writer));
+    let closed = Arc::new(AtomicBool::new(false));
+
+    // Stats go out from a second thread so a quiet client still gets them
+    let (stats_writer, stats_closed, stats_hub) = (writer.clone(), closed.clone(), hub.clone());
+    thread::spawn(move || {
+        while !stats_closed.load(Ordering::Relaxed) {
+            thread::sleep(STATS_INTERVAL);
+            let message = stats_hub.stats_json();
+            let mut writer = stats_writer.lock().unwrap_or_else(|e| e.into_inner());
+            if write_message(&mut *writer, OP_TEXT, message.as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+
+    thread::spawn(move || {
+        let _ = read_messages(stream, side, &hub, &writer);
+        closed.store(true, Ordering::Relaxed);
+        if let Ok(writer) = writer.lock() {
+            let _ = writer.shutdown(std::net::Shutdown::Both);
+        }
+    });
+    Ok(())
+}
+
+fn read_messages(mut stream: TcpStream, side: Side, hub: &StreamHub, writer: &Mutex<TcpStream>) -> io::Result<()> {
+    let mut message = Vec::new();
+    let mut message_op = OP_BINARY;
+    loop {
+        let (fin, op, payload) = read_frame(&mut stream)?;
+        match op {
+            OP_PING => {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                write_message(&mut *writer, OP_PONG, &payload)?;
+            }
+            OP_PONG => {}
+            OP_CLOSE => {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = write_message(&mut *writer, OP_CLOSE, &payload);
+                return Ok(());
+            }
+            OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                if op != OP_CONTINUATION {
+                    message_op = op;
+                    message.clear();
+                }
+                message.extend_from_slice(&payload);
+                if message.len() > MAX_MESSAGE_BYTES {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"));
+                }
+                if !fin {
+                    continue;
+                }
+                // Text messages aren't frames; they're accepted and ignored
+                if message_op == OP_BINARY {
+                    if let Err(e) = hub.push(side, &message) {
+                        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                        let error = serde_json::json!({ "error": e }).to_string();
+                        write_message(&mut *writer, OP_TEXT, error.as_bytes())?;
+                    }
+                }
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown opcode")),
+        }
+    }
+}
+
+/// Read one (masked) frame from the client: (final fragment, opcode, payload)
+fn read_frame(stream: &mut TcpStream) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    let fin = header[0] & 0x80 != 0;
+    let op = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as usize
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    if len > MAX_MESSAGE_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame too large"));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((fin, op, payload))
+}
+
+/// Write one unmasked, unfragmented frame, as servers do
+fn write_message(writer: &mut impl Write, op: u8, payload: &[u8]) -> io::Result<()> {
+    let mut frame = vec![0x80 | op];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
@@ -0,0 +1,348 @@
+use std::time::{Duration, Instant};
+
+/// How long the display flashes after a countdown or Pomodoro phase ends
+const COMPLETION_FLASH: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TimerMode {
+    Countdown,
+    Stopwatch,
+    Pomodoro,
+}
+
+impl TimerMode {
+    pub const ALL: [TimerMode; 3] = [TimerMode::Countdown, TimerMode::Stopwatch, TimerMode::Pomodoro];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimerMode::Countdown => "Countdown",
+            TimerMode::Stopwatch => "Stopwatch",
+            TimerMode::Pomodoro => "Pomodoro",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PomodoroPhase {
+    Work,
+    Break,
+}
+
+/// A countdown, stopwatch or Pomodoro timer
+/// All time-dependent methods take `now` so the caller owns the clock
+#[derive(Clone, PartialEq)]
+pub struct Timer {
+    pub mode: TimerMode,
+    pub countdown: Duration,
+    pub work: Duration,
+    pub short_break: Duration,
+    phase: PomodoroPhase,
+    started_at: Option<Instant>,
+    accumulated: Duration,
+    completed_at: Option<Instant>,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self {
+            mode: TimerMode::Countdown,
+            countdown: Duration::from_secs(5 * 60),
+            work: Duration::from_secs(25 * 60),
+            short_break: Duration::from_secs(5 * 60),
+            phase: PomodoroPhase::Work,
+            started_at: None,
+            accumulated: Duration::ZERO,
+            completed_at: None,
+        }
+    }
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn phase(&self) -> PomodoroPhase {
+        self.phase
+    }
+
+    pub fn start(&mut self, now: Instant) {
+        if self.started_at.is_none() {
+            self.started_at = Some(now);
+            self.completed_at = None;
+        }
+    }
+
+    pub fn pause(&mut self, now: Instant) {
+        if let Some(started) = self.started_at.take() {
+            self.accumulated += now.duration_since(started);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.started_at = None;
+        self.accumulated = Duration::ZERO;
+        self.completed_at = None;
+        self.phase = PomodoroPhase::Work;
+    }
+
+    /// Switch mode, stopping and clearing the current run
+    pub fn set_mode(&mut self, mode: TimerMode) {
+        self.mode = mode;
+        self.reset();
+    }
+
+    /// Time counted so far in the current run or phase
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        self.accumulated
+            + self
+                .started_at
+                .map(|started| now.duration_since(started))
+                .unwrap_or(Duration::ZERO)
+    }
+
+    /// Length of the current run, or `None` for the open-ended stopwatch
+    pub fn length(&self) -> Option<Duration> {
+        match self.mode {
+            TimerMode::Countdown => Some(self.countdown),
+            TimerMode::Stopwatch => None,
+            TimerMode::Pomodoro => Some(match self.phase {
+                PomodoroPhase::Work => self.work,
+                PomodoroPhase::Break => self.short_break,
+            }),
+        }
+    }
+
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.length().map(|length| length.saturating_sub(self.elapsed(now)))
+    }
+
+    /// Fraction of the current run still to go, 1.0 when just started
+    pub fn fraction_remaining(&self, now: Instant) -> Option<f32> {
+        let length = self.length()?;
+        if length.is_zero() {
+            return Some(0.0);
+        }
+        Some(self.remaining(now)?.as_secs_f32() / length.as_secs_f32())
+    }
+
+    /// Time shown on the display: remaining for countdowns, elapsed for the stopwatch
+    pub fn display_time(&self, now: Instant) -> Duration {
+        self.remaining(now).unwrap_or_else(|| self.elapsed(now))
+    }
+
+    /// Advance the timer: stop a finished countdown, or move a Pomodoro on to
+    /// its next phase. Returns true when something just completed
+    pub fn tick(&mut self, now: Instant) -> bool {
+        if !self.is_running() || self.remaining(now) != Some(Duration::ZERO) {
+            return false;
+        }
+
+        self.completed_at = Some(now);
+        self.accumulated = Duration::ZERO;
+        match self.mode {
+            TimerMode::Countdown => self.started_at = None,
+            TimerMode::Pomodoro => {
+                self.phase = match self.phase {
+                    PomodoroPhase::Work => PomodoroPhase::Break,
+                    PomodoroPhase::Break => PomodoroPhase::Work,
+                };
+                self.started_at = Some(now);
+            }
+            TimerMode::Stopwatch => {}
+        }
+        true
+    }
+
+    /// Whether the display should still be flashing for a recent completion
+    pub fn is_flashing(&self, now: Instant) -> bool {
+        self.completed_at
+            .map(|completed| now.duration_since(completed) < COMPLETION_FLASH)
+            .unwrap_or(false)
+    }
+}
+
+/// A timer action coming from the GUI, CLI or control socket
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimerCommand {
+    Countdown(Duration),
+    Stopwatch,
+    Pomodoro { work: Duration, short_break: Duration },
+    Start,
+    Pause,
+    Reset,
+}
+
+impl TimerCommand {
+    /// Parse the arguments after `timer`, e.g. `countdown 25m`,
+    /// `pomodoro 50m 10m`, `stopwatch`, `start`, `pause`, `reset`
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        match args {
+            ["countdown", length] => Ok(TimerCommand::Countdown(parse_duration(length)?)),
+            ["stopwatch"] => Ok(TimerCommand::Stopwatch),
+            ["pomodoro"] => Ok(TimerCommand::Pomodoro {
+                work: Duration::from_secs(25 * 60),
+                short_break: Duration::from_secs(5 * 60),
+            }),
+            ["pomodoro", work, short_break] => Ok(TimerCommand::Pomodoro {
+                work: parse_duration(work)?,
+                short_break: parse_duration(short_break)?,
+            }),
+            ["start"] => Ok(TimerCommand::Start),
+            ["pause"] => Ok(TimerCommand::Pause),
+            ["reset"] => Ok(TimerCommand::Reset),
+            _ => Err(
+                "usage: timer countdown <len> | stopwatch | pomodoro [<work> <break>] | start | pause | reset"
+                    .to_string(),
+            ),
+        }
+    }
+
+    /// Apply the command; mode commands also start the timer
+    pub fn apply(&self, timer: &mut Timer, now: Instant) {
+        match self {
+            TimerCommand::Countdown(length) => {
+                timer.countdown = *length;
+                timer.set_mode(TimerMode::Countdown);
+                timer.start(now);
+            }
+            TimerCommand::Stopwatch => {
+                timer.set_mode(TimerMode::Stopwatch);
+                timer.start(now);
+            }
+            TimerCommand::Pomodoro { work, short_break } => {
+                timer.work = *work;
+                timer.short_break = *short_break;
+                timer.set_mode(TimerMode::Pomodoro);
+                timer.start(now);
+            }
+            TimerCommand::Start => timer.start(now),
+            TimerCommand::Pause => timer.pause(now),
+            TimerCommand::Reset => timer.reset(),
+        }
+    }
+}
+
+/// Parse durations like `90`, `90s`, `25m`, `1h` or `1h30m` (bare numbers are seconds)
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}'", text);
+    if text.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+
+    let mut total = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        let secs = match c {
+            'h' => value.checked_mul(3600),
+            'm' => value.checked_mul(60),
+            's' => Some(value),
+            _ => return Err(invalid()),
+        };
+        total = secs.and_then(|secs| secs.checked_add(total)).ok_or_else(invalid)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse() {
+        let cases = [
+            ("90", Some(90)),
+            ("90s", Some(90)),
+            ("25m", Some(25 * 60)),
+            ("1h", Some(3600)),
+            ("1h30m", Some(5400)),
+            ("1h30m15s", Some(5415)),
+            ("", None),
+            ("m", None),
+            ("10x", None),
+            ("10m5", None),
+            ("-5m", None),
+        ];
+        for (text, secs) in cases {
+            assert_eq!(parse_duration(text).ok(), secs.map(Duration::from_secs), "{}", text);
+        }
+    }
+
+    #[test]
+    fn huge_durations_are_rejected() {
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).is_err());
+        assert!(parse_duration(&format!("{}m", u64::MAX / 30)).is_err());
+        assert!(parse_duration(&format!("{}s{}s", u64::MAX, 1)).is_err());
+    }
+
+    #[test]
+    fn countdown_stops_when_done() {
+        let start = Instant::now();
+        let mut timer = Timer::new();
+        timer.countdown = Duration::from_secs(60);
+        timer.start(start);
+
+        assert!(!timer.tick(start + Duration::from_secs(59)));
+        assert!(timer.is_running());
+        assert!(timer.tick(start + Duration::from_secs(60)));
+        assert!(!timer.is_running());
+        assert!(timer.is_flashing(start + Duration::from_secs(61)));
+        assert!(!timer.is_flashing(start + Duration::from_secs(60) + COMPLETION_FLASH));
+        // Nothing more happens once it has stopped
+        assert!(!timer.tick(start + Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn pomodoro_alternates_phases() {
+        let start = Instant::now();
+        let mut timer = Timer::new();
+        timer.set_mode(TimerMode::Pomodoro);
+        timer.work = Duration::from_secs(25);
+        timer.short_break = Duration::from_secs(5);
+        timer.start(start);
+
+        assert_eq!(timer.phase(), PomodoroPhase::Work);
+        assert!(timer.tick(start + Duration::from_secs(25)));
+        assert_eq!(timer.phase(), PomodoroPhase::Break);
+        assert!(timer.is_running());
+        assert_eq!(timer.remaining(start + Duration::from_secs(25)), Some(Duration::from_secs(5)));
+
+        assert!(!timer.tick(start + Duration::from_secs(29)));
+        assert!(timer.tick(start + Duration::from_secs(30)));
+        assert_eq!(timer.phase(), PomodoroPhase::Work);
+
+        timer.reset();
+        assert_eq!(timer.phase(), PomodoroPhase::Work);
+        assert!(!timer.is_running());
+    }
+
+    #[test]
+    fn paused_time_does_not_count() {
+        let start = Instant::now();
+        let mut timer = Timer::new();
+        timer.countdown = Duration::from_secs(60);
+        timer.start(start);
+        timer.pause(start + Duration::from_secs(20));
+        timer.start(start + Duration::from_secs(100));
+
+        assert_eq!(timer.remaining(start + Duration::from_secs(100)), Some(Duration::from_secs(40)));
+        assert!(!timer.tick(start + Duration::from_secs(139)));
+        assert!(timer.tick(start + Duration::from_secs(140)));
+    }
+}
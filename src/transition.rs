@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use crate::utils::{MATRIX_HEIGHT, MATRIX_WIDTH};
+
+/// Host-side effect used when a side switches from one preset to another
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum TransitionKind {
+    Cut,
+    Crossfade,
+    WipeUp,
+    WipeDown,
+    Slide,
+    Dissolve,
+    Pixelate,
+}
+
+impl TransitionKind {
+    pub const ALL: [TransitionKind; 7] = [
+        TransitionKind::Cut,
+        TransitionKind::Crossfade,
+        TransitionKind::WipeUp,
+        TransitionKind::WipeDown,
+        TransitionKind::Slide,
+        TransitionKind::Dissolve,
+        TransitionKind::Pixelate,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TransitionKind::Cut => "Cut",
+            TransitionKind::Crossfade => "Crossfade",
+            TransitionKind::WipeUp => "Wipe Up",
+            TransitionKind::WipeDown => "Wipe Down",
+            TransitionKind::Slide => "Slide",
+            TransitionKind::Dissolve => "Dissolve",
+            TransitionKind::Pixelate => "Pixelate",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct TransitionSettings {
+    pub kind: TransitionKind,
+    pub duration_ms: u64,
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        Self {
+            kind: TransitionKind::Crossfade,
+            duration_ms: 600,
+        }
+    }
+}
+
+/// Whether a frame covers the whole matrix, as every effect but a cut needs
+fn is_full_frame(frame: &[u8]) -> bool {
+    frame.len() == MATRIX_WIDTH * MATRIX_HEIGHT
+}
+
+/// Blend two frames for a transition `progress` between 0.0 (all `from`)
+/// and 1.0 (all `to`)
+/// Frames of any other size (e.g. a short custom image) just cut to `to`
+pub fn blend(kind: TransitionKind, from: &[u8], to: &[u8], progress: f32) -> Vec<u8> {
+    if !is_full_frame(from) || !is_full_frame(to) {
+        return to.to_vec();
+    }
+    let progress = progress.clamp(0.0, 1.0);
+    let mut frame = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+
+    match kind {
+        TransitionKind::Cut => frame.copy_from_slice(to),
+        TransitionKind::Crossfade => {
+            for (i, pixel) in frame.iter_mut().enumerate() {
+                *pixel = (from[i] as f32 * (1.0 - progress) + to[i] as f32 * progress).round() as u8;
+            }
+        }
+        TransitionKind::WipeUp | TransitionKind::WipeDown => {
+            let edge = (progress * MATRIX_HEIGHT as f32).round() as usize;
+            for row in 0..MATRIX_HEIGHT {
+                // Wipe up reveals the new frame from the bottom edge upwards
+                let revealed = match kind {
+                    TransitionKind::WipeUp => row >= MATRIX_HEIGHT - edge,
+                    _ => row < edge,
+                };
+                let source = if revealed { to } else { from };
+                let range = row * MATRIX_WIDTH..(row + 1) * MATRIX_WIDTH;
+                frame[range.clone()].copy_from_slice(&source[range]);
+            }
+        }
+        TransitionKind::Slide => {
+            // The new frame pushes the old one out through the top
+            let offset = (progress * MATRIX_HEIGHT as f32).round() as usize;
+            for row in 0..MATRIX_HEIGHT {
+                let source_row = row + offset;
+                let (source, source_row) = if source_row < MATRIX_HEIGHT {
+                    (from, source_row)
+                } else {
+                    (to, source_row - MATRIX_HEIGHT)
+                };
+                let dest = row * MATRIX_WIDTH..(row + 1) * MATRIX_WIDTH;
+                let src = source_row * MATRIX_WIDTH..(source_row + 1) * MATRIX_WIDTH;
+                frame[dest].copy_from_slice(&source[src]);
+            }
+        }
+        TransitionKind::Dissolve => {
+            for (i, pixel) in frame.iter_mut().enumerate() {
+                *pixel = if dissolve_threshold(i) < progress { to[i] } else { from[i] };
+            }
+        }
+        TransitionKind::Pixelate => {
+            // Blocks grow over the old frame, then shrink over the new one
+            let (source, amount) = if progress < 0.5 {
+                (from, progress * 2.0)
+            } else {
+                (to, (1.0 - progress) * 2.0)
+            };
+            let block = 1 + (amount * 4.0).round() as usize;
+            pixelate(source, block, &mut frame);
+        }
+    }
+
+    frame
+}
+
+/// Fixed pseudo-random order in which pixels switch during a dissolve
+fn dissolve_threshold(index: usize) -> f32 {
+    let mut x = index as u32 ^ 0x9E37_79B9;
+    x = x.wrapping_mul(0x85EB_CA6B);
+    x ^= x >> 13;
+    x = x.wrapping_mul(0xC2B2_AE35);
+    x ^= x >> 16;
+    (x % 1000) as f32 / 1000.0
+}
+
+/// Replace each `block` x `block` square with its average brightness
+fn pixelate(source: &[u8], block: usize, frame: &mut [u8]) {
+    for block_row in (0..MATRIX_HEIGHT).step_by(block) {
+        for block_col in (0..MATRIX_WIDTH).step_by(block) {
+            let rows = block_row..(block_row + block).min(MATRIX_HEIGHT);
+            let cols = block_col..(block_col + block).min(MATRIX_WIDTH);
+
+            let mut sum = 0u32;
+            let mut count = 0u32;
+            for row in rows.clone() {
+                for col in cols.clone() {
+                    sum += source[col + row * MATRIX_WIDTH] as u32;
+                    count += 1;
+                }
+            }
+            let average = (sum / count.max(1)) as u8;
+            for row in rows.clone() {
+                for col in cols.clone() {
+                    frame[col + row * MATRIX_WIDTH] = average;
+                }
+            }
+        }
+    }
+}
+
+struct ActiveTransition {
+    kind: TransitionKind,
+    from: Vec<u8>,
+    started: Instant,
+    duration: Duration,
+}
+
+/// Tracks what a side last showed and any transition in progress
+/// Firmware patterns have no host frame, so they count as a black frame:
+/// switching to or from one fades through black
+#[derive(Default)]
+pub struct SideTransition {
+    shown_preset: Option<String>,
+    last_frame: Option<Vec<u8>>,
+    active: Option<ActiveTransition>,
+}
+
+impl SideTransition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The preset the side is showing or transitioning to
+    pub fn shown_preset(&self) -> Option<&str> {
+        self.shown_preset.as_deref()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Note which preset is about to be shown; a change of preset starts a
+    /// transition away from whatever was on display
+    pub fn select(&mut self, preset: &str, settings: TransitionSettings, now: Instant) {
+        if self.shown_preset.as_deref() == Some(preset) {
+            return;
+        }
+        // The very first preset on a side just appears
+        let had_preset = self.shown_preset.is_some();
+        self.shown_preset = Some(preset.to_string());
+        if !had_preset || settings.kind == TransitionKind::Cut || settings.duration_ms == 0 {
+            self.active = None;
+            return;
+        }
+
+        self.active = Some(ActiveTransition {
+            kind: settings.kind,
+            from: self
+                .last_frame
+                .clone()
+                .unwrap_or_else(|| vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT]),
+            started: now,
+            duration: Duration::from_millis(settings.duration_ms),
+        });
+    }
+
+    /// Frame to show now while a transition runs towards `incoming` (the new
+    /// preset's frame, `None` for a firmware pattern). Returns `None` when no
+    /// transition is running, i.e. the preset's own output should be sent
+    pub fn frame(&mut self, incoming: Option<&[u8]>, now: Instant) -> Option<Vec<u8>> {
+        let active = self.active.as_ref()?;
+        let progress = now.duration_since(active.started).as_secs_f32() / active.duration.as_secs_f32();
+        if progress >= 1.0 {
+            self.active = None;
+            return None;
+        }
+
+        let black = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+        let to = incoming.unwrap_or(&black);
+        // Nothing to blend with an odd-sized frame, so show the new one as is
+        if !is_full_frame(&active.from) || !is_full_frame(to) {
+            self.active = None;
+            return None;
+        }
+        Some(blend(active.kind, &active.from, to, progress))
+    }
+
+    /// Remember what is on display, to transition away from it later
+    pub fn record(&mut self, frame: Option<&[u8]>) {
+        self.last_frame = frame.map(|f| f.to_vec());
+    }
+
+    /// Forget everything, e.g. after a disconnect
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: usize = MATRIX_WIDTH * MATRIX_HEIGHT;
+
+    #[test]
+    fn every_kind_blends_full_frames() {
+        let from = vec![0u8; FRAME];
+        let to = vec![200u8; FRAME];
+        for kind in TransitionKind::ALL {
+            assert_eq!(blend(kind, &from, &to, 0.5).len(), FRAME, "{:?}", kind);
+            assert_eq!(blend(kind, &from, &to, 1.0), to, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn short_frames_cut_instead_of_panicking() {
+        let full = vec![100u8; FRAME];
+        let short = vec![255u8; 10];
+        for kind in TransitionKind::ALL {
+            assert_eq!(blend(kind, &full, &short, 0.5), short, "{:?}", kind);
+            assert_eq!(blend(kind, &short, &full, 0.5), full, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn transition_to_short_frame_is_skipped() {
+        let start = Instant::now();
+        let settings = TransitionSettings::default();
+        let mut transition = SideTransition::new();
+        transition.select("first", settings, start);
+        transition.record(Some(&[50u8; FRAME]));
+        transition.select("short", settings, start);
+        assert!(transition.is_active());
+
+        let short = [255u8; 10];
+        assert_eq!(transition.frame(Some(&short), start + Duration::from_millis(100)), None);
+        assert!(!transition.is_active());
+
+        // Nor does a short frame on display break the next transition
+        transition.record(Some(&short));
+        transition.select("full", settings, start);
+        assert_eq!(transition.frame(Some(&[0u8; FRAME]), start + Duration::from_millis(100)), None);
+    }
+}
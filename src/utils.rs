@@ -1,123 +1,720 @@
-use chrono::{Local, Timelike};
+use chrono::{Datelike, Timelike};
+use std::time::Duration;
 
 pub const MATRIX_WIDTH: usize = 9;
 pub const MATRIX_HEIGHT: usize = 34;
 
-/// Generate a simple clock display pattern as brightness values
-/// Shows HH:MM using a simple pixel arrangement
-pub fn render_clock_display() -> Vec<u8> {
-    let now = Local::now();
-    let hours = now.hour() as u8;
-    let minutes = now.minute() as u8;
+/// Layout used by the clock preset
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClockFace {
+    /// Hours stacked over minutes as digits
+    Digits,
+    /// Day and month as digits with a weekday indicator
+    Date,
+    /// Hour digits over an area that fills up as the hour passes
+    MinuteFill,
+}
+
+impl ClockFace {
+    pub const ALL: [ClockFace; 3] = [ClockFace::Digits, ClockFace::Date, ClockFace::MinuteFill];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClockFace::Digits => "Digits",
+            ClockFace::Date => "Date",
+            ClockFace::MinuteFill => "Minute Fill",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClockOptions {
+    pub face: ClockFace,
+    pub twelve_hour: bool,
+    pub show_seconds: bool,
+}
+
+impl Default for ClockOptions {
+    fn default() -> Self {
+        Self {
+            face: ClockFace::Digits,
+            twelve_hour: false,
+            show_seconds: true,
+        }
+    }
+}
+
+/// First row of the seconds progress area at the bottom of the display
+const SECONDS_BAR_START_ROW: usize = 24;
+
+/// Generate a clock display pattern as brightness values
+/// Takes the time to show so callers decide the clock source
+pub fn render_clock_display<T: Datelike + Timelike>(options: &ClockOptions, now: &T) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let hours = display_hour(now.hour(), options.twelve_hour);
+
+    match options.face {
+        ClockFace::Digits => {
+            render_two_digits(&mut image_data, hours, 1, 2, 255);
+            // Colon between the stacked digits, blinking every second
+            if now.second() % 2 == 0 {
+                image_data[3 + 8 * MATRIX_WIDTH] = 120;
+                image_data[5 + 8 * MATRIX_WIDTH] = 120;
+            }
+            render_two_digits(&mut image_data, now.minute() as u8, 1, 10, 255);
+            if options.twelve_hour {
+                let glyph = if now.hour() < 12 { &LETTER_A } else { &LETTER_P };
+                render_glyph(&mut image_data, glyph, 1, 17, 120);
+                render_glyph(&mut image_data, &LETTER_M, 5, 17, 120);
+            }
+            if options.show_seconds {
+                render_seconds_bar(&mut image_data, now.second());
+            }
+        }
+        ClockFace::Date => {
+            render_two_digits(&mut image_data, now.day() as u8, 1, 2, 255);
+            render_two_digits(&mut image_data, now.month() as u8, 1, 9, 180);
+            // One pixel per weekday, Monday first, with today lit
+            let today = now.weekday().num_days_from_monday() as usize;
+            for day in 0..7 {
+                image_data[(1 + day) + 16 * MATRIX_WIDTH] = if day == today { 255 } else { 30 };
+            }
+            render_two_digits(&mut image_data, (now.year() % 100) as u8, 1, 19, 60);
+        }
+        ClockFace::MinuteFill => {
+            render_two_digits(&mut image_data, hours, 1, 1, 255);
+            // Pixels below the hour fill row by row as the hour passes
+            let first_row = 8;
+            let cells = (MATRIX_HEIGHT - first_row) * MATRIX_WIDTH;
+            let elapsed = now.minute() * 60 + now.second();
+            let lit = (elapsed as usize * cells) / 3600;
+            for cell in 0..cells {
+                image_data[first_row * MATRIX_WIDTH + cell] = if cell < lit { 150 } else { 10 };
+            }
+        }
+    }
+
+    image_data
+}
+
+/// First row of the draining bar in the timer display
+const TIMER_BAR_START_ROW: usize = 17;
+
+/// Generate a timer display pattern as brightness values
+/// Shows the time as stacked digits (minutes over seconds, or hours over
+/// minutes from an hour up) above a bar that drains as `fraction_remaining`
+/// drops; the open-ended stopwatch gets the seconds bar from the clock instead.
+/// `dimmed` lowers the bar brightness, e.g. during a Pomodoro break, and
+/// `flashing` alternates the whole display on and off
+pub fn render_timer_display(time: Duration, fraction_remaining: Option<f32>, dimmed: bool, flashing: bool, frame: u8) -> Vec<u8> {
+    if flashing && frame % 2 == 1 {
+        return vec![255u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    }
 
     let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let secs = time.as_secs();
+    let (upper, lower) = if secs >= 3600 {
+        ((secs / 3600).min(99), (secs / 60) % 60)
+    } else {
+        (secs / 60, secs % 60)
+    };
+    render_two_digits(&mut image_data, upper as u8, 1, 2, 255);
+    render_two_digits(&mut image_data, lower as u8, 1, 9, 255);
 
-    // Display hours in top section (binary representation)
-    render_binary_number(&mut image_data, hours, 0, 0);
-    // Display minutes in bottom section (binary representation)
-    render_binary_number(&mut image_data, minutes, 0, 17);
+    match fraction_remaining {
+        Some(fraction) => {
+            let bar_rows = MATRIX_HEIGHT - TIMER_BAR_START_ROW;
+            let filled_rows = (fraction.clamp(0.0, 1.0) * bar_rows as f32).ceil() as usize;
+            let fill = if dimmed { 60 } else { 180 };
+            for row in TIMER_BAR_START_ROW..MATRIX_HEIGHT {
+                let filled = MATRIX_HEIGHT - row <= filled_rows;
+                for col in 0..MATRIX_WIDTH {
+                    image_data[col + row * MATRIX_WIDTH] = if filled { fill } else { 5 };
+                }
+            }
+        }
+        None => render_seconds_bar(&mut image_data, (secs % 60) as u32),
+    }
 
     image_data
 }
 
+/// Hour to show for 12h or 24h mode
+fn display_hour(hour: u32, twelve_hour: bool) -> u8 {
+    if !twelve_hour {
+        return hour as u8;
+    }
+    match hour % 12 {
+        0 => 12,
+        h => h as u8,
+    }
+}
+
+/// Fill the bottom block pixel by pixel as the minute passes
+fn render_seconds_bar(image_data: &mut [u8], second: u32) {
+    let cells = (MATRIX_HEIGHT - SECONDS_BAR_START_ROW) * MATRIX_WIDTH;
+    let lit = ((second as usize + 1) * cells) / 60;
+    for cell in 0..cells {
+        image_data[SECONDS_BAR_START_ROW * MATRIX_WIDTH + cell] = if cell < lit { 80 } else { 5 };
+    }
+}
+
+/// Charging state of a single battery
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChargeState {
+    Charging,
+    Discharging,
+    Full,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct BatteryStatus {
+    pub percent: f32,
+    pub state: ChargeState,
+    /// Time to full while charging, time to empty while discharging
+    pub time_remaining: Option<Duration>,
+}
+
+/// Get the status of every battery in the system
+/// Returns an empty list when there is no battery (or it can't be read)
+pub fn get_battery_statuses() -> Vec<BatteryStatus> {
+    use battery::units::ratio::percent;
+    use battery::units::time::second;
+
+    let Ok(manager) = battery::Manager::new() else {
+        return Vec::new();
+    };
+    let Ok(batteries) = manager.batteries() else {
+        return Vec::new();
+    };
+
+    batteries
+        .flatten()
+        .map(|bat| {
+            let state = match bat.state() {
+                battery::State::Charging => ChargeState::Charging,
+                battery::State::Discharging | battery::State::Empty => ChargeState::Discharging,
+                battery::State::Full => ChargeState::Full,
+                _ => ChargeState::Unknown,
+            };
+            let time_remaining = match state {
+                ChargeState::Charging => bat.time_to_full(),
+                ChargeState::Discharging => bat.time_to_empty(),
+                _ => None,
+            }
+            .map(|t| Duration::from_secs_f32(t.get::<second>().max(0.0)));
+
+            BatteryStatus {
+                percent: bat.state_of_charge().get::<percent>().clamp(0.0, 100.0),
+                state,
+                time_remaining,
+            }
+        })
+        .collect()
+}
+
+/// Average charge across all batteries, or `None` without a battery
+pub fn average_battery_percentage(batteries: &[BatteryStatus]) -> Option<f32> {
+    if batteries.is_empty() {
+        return None;
+    }
+    Some(batteries.iter().map(|b| b.percent).sum::<f32>() / batteries.len() as f32)
+}
+
+/// Rows used by the battery icon(s); the rest shows time remaining
+const BATTERY_ICON_ROWS: usize = 22;
+
+/// Brightness levels of the battery display, and the charge at or below which
+/// the fill switches to the medium and low levels
+#[derive(Clone, Copy, Debug)]
+pub struct BatteryStyle {
+    pub outline: u8,
+    pub high: u8,
+    pub medium: u8,
+    pub low: u8,
+    pub medium_below: f32,
+    pub low_below: f32,
+}
+
+impl Default for BatteryStyle {
+    fn default() -> Self {
+        Self {
+            outline: 120,
+            high: 100,
+            medium: 150,
+            low: 255,
+            medium_below: 50.0,
+            low_below: 20.0,
+        }
+    }
+}
+
 /// Generate a battery display pattern as brightness values
-/// Shows battery percentage as a bar
-pub fn render_battery_display() -> Vec<u8> {
+/// Draws one battery icon per battery (side by side) filled to its charge,
+/// with a fill animation while charging and the time to full/empty as
+/// stacked hours and minutes digits underneath. Without a battery, shows a
+/// crossed-out battery instead of pretending it's full
+pub fn render_battery_display(batteries: &[BatteryStatus], frame: u8, style: &BatteryStyle) -> Vec<u8> {
     let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
-    
-    // Try to get battery percentage
-    let percent = get_battery_percentage().unwrap_or(100.0);
-    render_battery_bar(&mut image_data, percent as u8);
-    
-    // Also display percentage as binary number
-    render_binary_number(&mut image_data, percent as u8, 0, 8);
+
+    if batteries.is_empty() {
+        render_battery_outline(&mut image_data, 0, MATRIX_WIDTH, style.outline);
+        // Cross through the body
+        for row in 2..BATTERY_ICON_ROWS - 1 {
+            let t = (row - 2) as f32 / (BATTERY_ICON_ROWS - 4) as f32;
+            let col = 1 + (t * (MATRIX_WIDTH - 3) as f32).round() as usize;
+            image_data[col + row * MATRIX_WIDTH] = 255;
+            image_data[(MATRIX_WIDTH - 1 - col) + row * MATRIX_WIDTH] = 255;
+        }
+        return image_data;
+    }
+
+    // Split the width between up to three batteries with a one column gap
+    let count = batteries.len().min(3);
+    let width = (MATRIX_WIDTH + 1) / count - 1;
+    for (i, battery) in batteries.iter().take(count).enumerate() {
+        let col_start = i * (width + 1);
+        render_battery_outline(&mut image_data, col_start, width, style.outline);
+        render_battery_fill(&mut image_data, col_start, width, battery, frame, style);
+    }
+
+    let time_remaining = batteries.iter().filter_map(|b| b.time_remaining).max();
+    if let Some(time) = time_remaining {
+        let minutes = time.as_secs() / 60;
+        let hours = (minutes / 60).min(99) as u8;
+        render_two_digits(&mut image_data, hours, 1, BATTERY_ICON_ROWS + 1, 200);
+        render_two_digits(&mut image_data, (minutes % 60) as u8, 1, BATTERY_ICON_ROWS + 1 + DIGIT_HEIGHT + 1, 120);
+    }
 
     image_data
 }
 
-/// Get battery percentage from the system
-fn get_battery_percentage() -> Option<f32> {
-    #[cfg(target_os = "windows")]
-    {
-        use std::process::Command;
-        
-        // On Windows, try to get battery info
-        if let Ok(output) = Command::new("powershell")
-            .args(&["-Command", "Get-CimInstance -ClassName Win32_Battery | Select-Object -ExpandProperty EstimatedChargeRemaining"])
-            .output()
-        {
-            if let Ok(text) = String::from_utf8(output.stdout) {
-                if let Ok(percent) = text.trim().parse::<f32>() {
-                    return Some(percent);
-                }
+/// Draw a battery outline with a terminal cap on top
+fn render_battery_outline(image_data: &mut [u8], col_start: usize, width: usize, brightness: u8) {
+    let col_end = col_start + width;
+    // Cap, one column narrower on each side where there is room
+    let cap_start = if width > 2 { col_start + 1 } else { col_start };
+    let cap_end = if width > 2 { col_end - 1 } else { col_end };
+    image_data[cap_start..cap_end].fill(brightness);
+    for row in 1..BATTERY_ICON_ROWS {
+        for col in col_start..col_end {
+            let edge = row == 1 || row == BATTERY_ICON_ROWS - 1 || col == col_start || col == col_end - 1;
+            if edge {
+                image_data[col + row * MATRIX_WIDTH] = brightness;
             }
         }
     }
-    
-    #[cfg(not(target_os = "windows"))]
-    {
-        // On Linux/Mac, try battery crate as fallback
-        if let Ok(manager) = battery::Manager::new() {
-            if let Ok(batteries) = manager.batteries() {
-                for battery in batteries {
-                    if let Ok(bat) = battery {
-                        let percent = (bat.energy().as_f32() / bat.energy_full().as_f32()) * 100.0;
-                        return Some(percent);
-                    }
-                }
+}
+
+/// Fill a battery body from the bottom; while charging a bright band sweeps
+/// up through the fill
+fn render_battery_fill(
+    image_data: &mut [u8],
+    col_start: usize,
+    width: usize,
+    battery: &BatteryStatus,
+    frame: u8,
+    style: &BatteryStyle,
+) {
+    let body_rows = BATTERY_ICON_ROWS - 3;
+    let filled_rows = ((battery.percent / 100.0) * body_rows as f32).round() as usize;
+    let brightness = if battery.percent > style.medium_below {
+        style.high
+    } else if battery.percent > style.low_below {
+        style.medium
+    } else {
+        style.low
+    };
+    let sweep_row = if battery.state == ChargeState::Charging && filled_rows > 0 {
+        Some(frame as usize % (filled_rows + 1))
+    } else {
+        None
+    };
+
+    // Inside the outline; very narrow batteries have no interior, so fill
+    // the outline columns themselves
+    let (inner_start, inner_end) = if width > 2 {
+        (col_start + 1, col_start + width - 1)
+    } else {
+        (col_start, col_start + width)
+    };
+    for level in 0..filled_rows {
+        let row = BATTERY_ICON_ROWS - 2 - level;
+        let value = if sweep_row == Some(level) { 255 } else { brightness };
+        for col in inner_start..inner_end {
+            image_data[col + row * MATRIX_WIDTH] = value;
+        }
+    }
+}
+
+/// Height of the digit font in rows
+const DIGIT_HEIGHT: usize = 5;
+
+/// 3x5 digit font, one row per entry with the 3 low bits as pixels (MSB left)
+const DIGIT_FONT: [[u8; DIGIT_HEIGHT]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b111, 0b001, 0b111, 0b100, 0b111], // 2
+    [0b111, 0b001, 0b111, 0b001, 0b111], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b111, 0b001, 0b111], // 5
+    [0b111, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b010, 0b010, 0b010], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b111], // 9
+];
+
+/// Uppercase letters in the same 3x5 format as the digits
+const LETTER_FONT: [[u8; DIGIT_HEIGHT]; 26] = [
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b110, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+];
+
+const LETTER_A: [u8; DIGIT_HEIGHT] = LETTER_FONT[0];
+const LETTER_P: [u8; DIGIT_HEIGHT] = LETTER_FONT[15];
+const LETTER_M: [u8; DIGIT_HEIGHT] = LETTER_FONT[12];
+
+/// 3x5 glyph for a character of scrolling text; lowercase is drawn as
+/// uppercase and anything without a glyph as a question mark
+fn char_glyph(c: char) -> [u8; DIGIT_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        c @ 'A'..='Z' => LETTER_FONT[(c as u8 - b'A') as usize],
+        c @ '0'..='9' => DIGIT_FONT[(c as u8 - b'0') as usize],
+        ' ' => [0; DIGIT_HEIGHT],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0b110, 0b001, 0b010, 0b000, 0b010], // ?
+    }
+}
+
+/// Draw a 3x5 glyph with its top-left corner at (col, row)
+fn render_glyph(image_data: &mut [u8], glyph: &[u8; DIGIT_HEIGHT], col: usize, row: usize, brightness: u8) {
+    for (dy, bits) in glyph.iter().enumerate() {
+        for dx in 0..3 {
+            if bits & (0b100 >> dx) != 0 && col + dx < MATRIX_WIDTH && row + dy < MATRIX_HEIGHT {
+                image_data[(col + dx) + (row + dy) * MATRIX_WIDTH] = brightness;
             }
         }
     }
-    
-    None
 }
 
-/// Render a horizontal battery bar at the top of the display
-fn render_battery_bar(image_data: &mut [u8], percentage: u8) {
-    let percentage = percentage.min(100);
-    let filled_rows = ((percentage as usize * MATRIX_HEIGHT) / 100).min(MATRIX_HEIGHT);
-    
-    // Draw vertical battery indicator on the left side
-    for row in 0..MATRIX_HEIGHT {
-        for col in 0..2 {
-            let idx = col + row * MATRIX_WIDTH;
-            if MATRIX_HEIGHT - row <= filled_rows {
-                image_data[idx] = if percentage > 50 { 100 } else if percentage > 20 { 150 } else { 255 }; // Green for high, yellow for medium, red for low
-            } else {
-                image_data[idx] = 20; // Dim for empty portion
+/// Draw a single 3x5 digit with its top-left corner at (col, row)
+fn render_digit(image_data: &mut [u8], digit: u8, col: usize, row: usize, brightness: u8) {
+    render_glyph(image_data, &DIGIT_FONT[(digit % 10) as usize], col, row, brightness);
+}
+
+/// Draw a zero-padded two digit number (0-99) starting at (col, row)
+fn render_two_digits(image_data: &mut [u8], value: u8, col: usize, row: usize, brightness: u8) {
+    let value = value.min(99);
+    render_digit(image_data, value / 10, col, row, brightness);
+    render_digit(image_data, value % 10, col + 4, row, brightness);
+}
+
+/// How brightness rises and falls over one breath
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BreathingCurve {
+    /// Straight ramps up and down
+    Linear,
+    /// Eases in and out at either end
+    Sine,
+    /// A quick rise and a long, slow fade
+    Pulse,
+}
+
+impl BreathingCurve {
+    pub const ALL: [BreathingCurve; 3] = [BreathingCurve::Linear, BreathingCurve::Sine, BreathingCurve::Pulse];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BreathingCurve::Linear => "Linear",
+            BreathingCurve::Sine => "Sine",
+            BreathingCurve::Pulse => "Pulse",
+        }
+    }
+
+    /// Level from 0.0 to 1.0 at `phase` (0.0..1.0) through a breath
+    fn level(&self, phase: f32) -> f32 {
+        match self {
+            BreathingCurve::Linear => 1.0 - (2.0 * phase - 1.0).abs(),
+            BreathingCurve::Sine => (1.0 - (phase * std::f32::consts::TAU).cos()) / 2.0,
+            BreathingCurve::Pulse if phase < 0.2 => phase / 0.2,
+            BreathingCurve::Pulse => (1.0 - (phase - 0.2) / 0.8).powi(2),
+        }
+    }
+}
+
+/// Generate breathing animation pattern: the whole display at one
+/// brightness, moving between `floor` and `peak` as `phase` goes from 0.0
+/// to 1.0
+pub fn render_breathing_animation(phase: f32, curve: BreathingCurve, floor: u8, peak: u8) -> Vec<u8> {
+    let level = curve.level(phase.rem_euclid(1.0));
+    let brightness = floor as f32 + (peak as f32 - floor as f32) * level;
+    vec![brightness.round() as u8; MATRIX_WIDTH * MATRIX_HEIGHT]
+}
+
+/// Generate a network throughput display pattern as brightness values
+/// Upload fills the top half upwards, download fills the bottom half downwards
+pub fn render_network_display(download: u8, upload: u8) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let half = MATRIX_HEIGHT / 2;
+
+    let upload_rows = (upload.min(100) as usize * half) / 100;
+    let download_rows = (download.min(100) as usize * half) / 100;
+
+    for row in 0..half {
+        // Distance from the centre line, 1 = closest
+        let distance = half - row;
+        let brightness = if distance <= upload_rows { 200 } else { 10 };
+        for col in 0..MATRIX_WIDTH {
+            image_data[col + row * MATRIX_WIDTH] = brightness;
+        }
+    }
+
+    for row in half..MATRIX_HEIGHT {
+        let distance = row - half + 1;
+        let brightness = if distance <= download_rows { 200 } else { 10 };
+        for col in 0..MATRIX_WIDTH {
+            image_data[col + row * MATRIX_WIDTH] = brightness;
+        }
+    }
+
+    image_data
+}
+
+/// Usage (percent) at which a disk bar starts blinking
+pub const DISK_WARNING_PERCENT: u8 = 90;
+
+/// Generate a disk usage display pattern as brightness values
+/// Draws one vertical bar per disk (up to 3); bars over the warning level blink
+pub fn render_disk_usage_display(usages: &[u8], frame: u8) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let blink_on = frame % 4 < 2;
+
+    // Each bar is two columns wide with a one column gap
+    for (disk, &usage) in usages.iter().take(MATRIX_WIDTH.div_ceil(3)).enumerate() {
+        let usage = usage.min(100);
+        let filled_rows = (usage as usize * MATRIX_HEIGHT) / 100;
+        let warning = usage >= DISK_WARNING_PERCENT;
+
+        for row in 0..MATRIX_HEIGHT {
+            let filled = MATRIX_HEIGHT - row <= filled_rows;
+            let brightness = match (filled, warning) {
+                (true, true) if blink_on => 255,
+                (true, true) => 40,
+                (true, false) => 150,
+                (false, _) => 15,
+            };
+            for col in disk * 3..(disk * 3 + 2).min(MATRIX_WIDTH) {
+                image_data[col + row * MATRIX_WIDTH] = brightness;
             }
         }
     }
+
+    image_data
 }
 
-/// Render a number in binary format (8 bits vertical)
-fn render_binary_number(image_data: &mut [u8], number: u8, col_start: usize, row_start: usize) {
-    for bit in 0..8 {
-        let is_set = (number & (1 << bit)) != 0;
-        let brightness = if is_set { 200 } else { 30 };
-        let row = row_start + (7 - bit); // MSB at top
-        if row < MATRIX_HEIGHT && col_start + 1 < MATRIX_WIDTH {
-            let idx = col_start + row * MATRIX_WIDTH;
-            image_data[idx] = brightness;
-            image_data[idx + 1] = brightness;
+/// Generate a disk I/O sparkline as brightness values
+/// Each row is one sample (newest at the bottom); reads grow leftwards and
+/// writes grow rightwards from the centre column
+pub fn render_disk_io_sparkline(history: &[(u8, u8)]) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let centre = MATRIX_WIDTH / 2;
+
+    let start_row = MATRIX_HEIGHT.saturating_sub(history.len());
+    let samples = &history[history.len().saturating_sub(MATRIX_HEIGHT)..];
+    for (i, &(read, write)) in samples.iter().enumerate() {
+        let row = start_row + i;
+        let read_cols = (read.min(100) as usize * centre).div_ceil(100);
+        let write_cols = (write.min(100) as usize * centre).div_ceil(100);
+
+        image_data[centre + row * MATRIX_WIDTH] = 30;
+        for offset in 1..=read_cols {
+            image_data[centre - offset + row * MATRIX_WIDTH] = 180;
+        }
+        for offset in 1..=write_cols {
+            image_data[centre + offset + row * MATRIX_WIDTH] = 180;
         }
     }
+
+    image_data
 }
 
-/// Generate breathing animation pattern
-pub fn render_breathing_animation(frame: u8) -> Vec<u8> {
+/// Lowest temperature shown on the thermometer scale, in Celsius
+const THERMOMETER_MIN_CELSIUS: f32 = 20.0;
+
+/// Generate a thermometer display pattern as brightness values
+/// The bar scales from 20°C up to just past the critical threshold, gets
+/// brighter past the warning threshold and turns into a blinking alert past
+/// the critical one
+pub fn render_thermometer_display(celsius: f32, warning: f32, critical: f32, frame: u8) -> Vec<u8> {
+    if celsius >= critical {
+        return render_alert_display(frame);
+    }
+
     let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
-    
-    // Create a breathing effect - brightness changes with frame
-    let brightness = if frame < 128 {
-        (frame as f32 / 128.0 * 255.0) as u8
-    } else {
-        (255.0 - (frame as f32 - 128.0) / 128.0 * 255.0) as u8
-    };
+    let bulb_rows = 5;
+    let tube_rows = MATRIX_HEIGHT - bulb_rows;
+    let scale_max = critical + 10.0;
+    let fraction = ((celsius - THERMOMETER_MIN_CELSIUS) / (scale_max - THERMOMETER_MIN_CELSIUS)).clamp(0.0, 1.0);
+    let filled_rows = (fraction * tube_rows as f32).round() as usize;
+    let fill = if celsius >= warning { 255 } else { 120 };
 
-    // Fill entire display with breathing brightness
-    for pixel in image_data.iter_mut() {
-        *pixel = brightness;
+    // Tube: three columns wide in the middle, filling upwards
+    for row in 0..tube_rows {
+        let filled = tube_rows - row <= filled_rows;
+        for col in 3..6 {
+            image_data[col + row * MATRIX_WIDTH] = if filled { fill } else { 15 };
+        }
+    }
+
+    // Bulb: always lit, wider than the tube
+    for row in tube_rows..MATRIX_HEIGHT {
+        for col in 1..MATRIX_WIDTH - 1 {
+            image_data[col + row * MATRIX_WIDTH] = fill;
+        }
+    }
+
+    // Tick marks for the warning threshold on both sides of the tube
+    let warning_fraction = ((warning - THERMOMETER_MIN_CELSIUS) / (scale_max - THERMOMETER_MIN_CELSIUS)).clamp(0.0, 1.0);
+    let warning_row = tube_rows - (warning_fraction * tube_rows as f32).round() as usize;
+    if warning_row < tube_rows {
+        image_data[1 + warning_row * MATRIX_WIDTH] = 200;
+        image_data[MATRIX_WIDTH - 2 + warning_row * MATRIX_WIDTH] = 200;
+    }
+
+    image_data
+}
+
+/// Generate spectrum bars, one column per band from the left, each with a
+/// bright peak-hold marker; `levels` and `peaks` are 0.0..=1.0
+pub fn render_spectrum(levels: &[f32], peaks: &[f32]) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let height = |value: f32| (value.clamp(0.0, 1.0) * MATRIX_HEIGHT as f32).round() as usize;
+
+    for (col, (&level, &peak)) in levels.iter().zip(peaks).take(MATRIX_WIDTH).enumerate() {
+        for filled in 0..height(level) {
+            // Bars get brighter towards the top
+            let brightness = 40 + (filled * 140 / MATRIX_HEIGHT) as u8;
+            image_data[col + (MATRIX_HEIGHT - 1 - filled) * MATRIX_WIDTH] = brightness;
+        }
+        let peak_row = height(peak);
+        if peak_row > 0 {
+            image_data[col + (MATRIX_HEIGHT - peak_row) * MATRIX_WIDTH] = 255;
+        }
+    }
+
+    image_data
+}
+
+/// Rows taken by one character of vertical text, including the gap below it
+pub const TEXT_LINE_HEIGHT: usize = DIGIT_HEIGHT + 1;
+
+/// Draw text as a column of centred 3x5 characters, top to bottom, with the
+/// first character's top row at `offset` (negative when scrolled off the top)
+pub fn render_vertical_text(text: &str, offset: i32, brightness: u8) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    let col = (MATRIX_WIDTH - 3) / 2;
+    for (i, c) in text.chars().enumerate() {
+        let top = offset + (i * TEXT_LINE_HEIGHT) as i32;
+        if top >= MATRIX_HEIGHT as i32 {
+            break;
+        }
+        for (dy, bits) in char_glyph(c).iter().enumerate() {
+            let row = top + dy as i32;
+            if !(0..MATRIX_HEIGHT as i32).contains(&row) {
+                continue;
+            }
+            for dx in 0..3 {
+                if bits & (0b100 >> dx) != 0 {
+                    image_data[(col + dx) + row as usize * MATRIX_WIDTH] = brightness;
+                }
+            }
+        }
+    }
+    image_data
+}
+
+/// 9x7 envelope, one row per entry with the 9 low bits as pixels (MSB left)
+const ENVELOPE_ICON: [u16; 7] = [
+    0b111111111,
+    0b110000011,
+    0b101000101,
+    0b100101001,
+    0b100010001,
+    0b100000001,
+    0b111111111,
+];
+
+/// Generate a blinking envelope for an incoming notification
+pub fn render_notification_icon(frame: u8) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    if frame % 4 >= 2 {
+        return image_data;
+    }
+
+    let top = (MATRIX_HEIGHT - ENVELOPE_ICON.len()) / 2;
+    for (dy, bits) in ENVELOPE_ICON.iter().enumerate() {
+        for col in 0..MATRIX_WIDTH {
+            if bits & (1 << (MATRIX_WIDTH - 1 - col)) != 0 {
+                image_data[col + (top + dy) * MATRIX_WIDTH] = 255;
+            }
+        }
+    }
+    image_data
+}
+
+/// Generate a blinking "!" alert pattern as brightness values
+pub fn render_alert_display(frame: u8) -> Vec<u8> {
+    let mut image_data = vec![0u8; MATRIX_WIDTH * MATRIX_HEIGHT];
+    if frame % 4 >= 2 {
+        return image_data;
+    }
+
+    for row in 0..MATRIX_HEIGHT {
+        // Border
+        for col in 0..MATRIX_WIDTH {
+            if row == 0 || row == MATRIX_HEIGHT - 1 || col == 0 || col == MATRIX_WIDTH - 1 {
+                image_data[col + row * MATRIX_WIDTH] = 255;
+            }
+        }
+        // Exclamation mark: a tall stroke and a dot
+        let in_stroke = (4..23).contains(&row);
+        let in_dot = (26..30).contains(&row);
+        if in_stroke || in_dot {
+            for col in 3..6 {
+                image_data[col + row * MATRIX_WIDTH] = 255;
+            }
+        }
     }
 
     image_data